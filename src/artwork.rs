@@ -0,0 +1,66 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{config::Config, fetch::cache::fnv1a, tags::EmbeddedPicture};
+
+const FOLDER_IMAGE_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+const FOLDER_IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+pub fn cover_dir() -> PathBuf {
+    Config::get().cache_dir.join("covers")
+}
+
+/// Finds art for a local file: the embedded picture first, then an image next to the file.
+pub fn find_local_cover(song_path: &Path, embedded: Option<&EmbeddedPicture>) -> Option<PathBuf> {
+    embedded
+        .and_then(store_embedded)
+        .or_else(|| find_folder_image(song_path))
+}
+
+/// Writes an embedded picture into the cover cache. Identical pictures share one file.
+fn store_embedded(picture: &EmbeddedPicture) -> Option<PathBuf> {
+    let dir = cover_dir();
+    let path = dir.join(format!(
+        "{:016x}.{}",
+        fnv1a(&picture.data),
+        picture.extension()
+    ));
+    if path.exists() {
+        return Some(path);
+    }
+    let result = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, &picture.data));
+    match result {
+        Ok(()) => Some(path),
+        Err(err) => {
+            warn!("Failed to store embedded cover art: {err}");
+            None
+        }
+    }
+}
+
+fn find_folder_image(song_path: &Path) -> Option<PathBuf> {
+    let entries = fs::read_dir(song_path.parent()?).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            FOLDER_IMAGE_NAMES.contains(&stem.as_str())
+                && FOLDER_IMAGE_EXTENSIONS.contains(&extension.as_str())
+        })
+}
+
+pub fn cover_url(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
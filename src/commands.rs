@@ -0,0 +1,561 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    playlists::check_name,
+    songs::{SortBy, SortOrder},
+};
+
+const COMMAND_NAMES: [&str; 18] = [
+    "add",
+    "seek",
+    "vol",
+    "save-playlist",
+    "playlist-new",
+    "playlist-add",
+    "playlist-rename",
+    "playlist-delete",
+    "playlist-load",
+    "playlist-import",
+    "playlist-export",
+    "smart-playlist-new",
+    "rate",
+    "favorite",
+    "stats-export",
+    "shuffle",
+    "sort",
+    "quit",
+];
+
+const SORT_FIELDS: [&str; 8] = [
+    "title",
+    "artist",
+    "album",
+    "year",
+    "rating",
+    "plays",
+    "last-played",
+    "added",
+];
+
+#[derive(Clone, Copy)]
+pub enum SeekPosition {
+    Absolute(u64),
+    Forward(u64),
+    Backward(u64),
+}
+
+#[derive(Clone, Copy)]
+pub enum Volume {
+    Absolute(i32),
+    Relative(i32),
+}
+
+#[derive(Clone, Copy)]
+pub enum Rating {
+    Set(u8),
+    Up,
+    Down,
+}
+
+pub enum Command {
+    Add(PathBuf),
+    Seek(SeekPosition),
+    Volume(Volume),
+    SavePlaylist(String),
+    PlaylistNew(String),
+    /// Appends the selected songs of the focused pane
+    PlaylistAdd(String),
+    /// Renames the open or selected playlist of the Playlists view
+    PlaylistRename(String),
+    PlaylistDelete(String),
+    PlaylistLoad(String),
+    /// Copies an m3u, pls or xspf file into a new playlist
+    PlaylistImport(PathBuf),
+    /// Writes the open or selected playlist, with paths relative to the file if the flag is set
+    PlaylistExport(PathBuf, bool),
+    /// Writes a rule file template for a smart playlist
+    SmartPlaylistNew(String),
+    /// Rates the selected songs of the focused pane
+    Rate(Rating),
+    /// Toggles the selected songs of the focused pane as favorites
+    Favorite,
+    /// Writes the stats of the period shown on the Stats screen as JSON or CSV
+    StatsExport(PathBuf),
+    /// `None` toggles
+    Shuffle(Option<bool>),
+    Sort(SortBy, SortOrder),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim().trim_start_matches(':');
+        let (name, argument) = line
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((line, ""));
+        match name {
+            "add" | "a" => {
+                if argument.is_empty() {
+                    return Err("Usage: add <path>".to_string());
+                }
+                Ok(Command::Add(expand_home(argument)))
+            }
+            "seek" => parse_position(argument)
+                .map(Command::Seek)
+                .ok_or("Usage: seek [+|-]<mm:ss|seconds>".to_string()),
+            "vol" | "volume" => parse_volume(argument)
+                .map(Command::Volume)
+                .ok_or("Usage: vol [+|-]<0-100>".to_string()),
+            "save-playlist" => parse_playlist_name(name, argument).map(Command::SavePlaylist),
+            "playlist-new" => parse_playlist_name(name, argument).map(Command::PlaylistNew),
+            "playlist-add" => parse_playlist_name(name, argument).map(Command::PlaylistAdd),
+            "playlist-rename" => parse_playlist_name(name, argument).map(Command::PlaylistRename),
+            "playlist-delete" => parse_playlist_name(name, argument).map(Command::PlaylistDelete),
+            "playlist-load" => parse_playlist_name(name, argument).map(Command::PlaylistLoad),
+            "smart-playlist-new" => {
+                parse_playlist_name(name, argument).map(Command::SmartPlaylistNew)
+            }
+            "playlist-import" => {
+                if argument.is_empty() {
+                    return Err("Usage: playlist-import <file>".to_string());
+                }
+                Ok(Command::PlaylistImport(expand_home(argument)))
+            }
+            "playlist-export" => {
+                let (relative, path) = match argument.strip_prefix("--relative") {
+                    Some(path) => (true, path.trim()),
+                    None => (false, argument),
+                };
+                if path.is_empty() {
+                    return Err("Usage: playlist-export [--relative] <file>".to_string());
+                }
+                Ok(Command::PlaylistExport(expand_home(path), relative))
+            }
+            "shuffle" => match argument {
+                "on" => Ok(Command::Shuffle(Some(true))),
+                "off" => Ok(Command::Shuffle(Some(false))),
+                "" | "toggle" => Ok(Command::Shuffle(None)),
+                _ => Err("Usage: shuffle [on|off|toggle]".to_string()),
+            },
+            "sort" => parse_sort(argument).ok_or(
+                "Usage: sort <title|artist|album|year|rating|plays|last-played|added> [asc|desc]"
+                    .to_string(),
+            ),
+            "rate" => parse_rating(argument)
+                .map(Command::Rate)
+                .ok_or("Usage: rate <0-5|+|->".to_string()),
+            "favorite" | "fav" => Ok(Command::Favorite),
+            "stats-export" => {
+                if argument.is_empty() {
+                    return Err("Usage: stats-export <file.json|file.csv>".to_string());
+                }
+                Ok(Command::StatsExport(expand_home(argument)))
+            }
+            "quit" | "q" => Ok(Command::Quit),
+            "" => Err("No command".to_string()),
+            _ => Err(format!("Unknown command: {name}")),
+        }
+    }
+}
+
+/// Runs every line of the rc file through `parse`. Blank lines and `#` comments are skipped.
+/// Errors come with their line number.
+pub fn read_rc_file(path: &Path) -> (Vec<Command>, Vec<String>) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match Command::parse(line) {
+            Ok(command) => commands.push(command),
+            Err(err) => errors.push(format!("{}:{}: {err}", path.display(), index + 1)),
+        }
+    }
+    (commands, errors)
+}
+
+pub fn playlist_dir() -> PathBuf {
+    Config::get().data_dir.join("playlists")
+}
+
+/// Completions for the last word of `input`, each one the whole input with that word replaced.
+pub fn complete(input: &str) -> Vec<String> {
+    let (name, argument) = match input.split_once(' ') {
+        Some((name, argument)) => (name, argument),
+        None => {
+            return COMMAND_NAMES
+                .iter()
+                .filter(|command| command.starts_with(input))
+                .map(|command| format!("{command} "))
+                .collect();
+        }
+    };
+    let with_argument = |candidates: Vec<String>| -> Vec<String> {
+        candidates
+            .into_iter()
+            .map(|candidate| format!("{name} {candidate}"))
+            .collect()
+    };
+    let matching = |options: &[&str], prefix: &str| -> Vec<String> {
+        options
+            .iter()
+            .filter(|option| option.starts_with(prefix))
+            .map(|option| option.to_string())
+            .collect()
+    };
+    match name {
+        "add" | "a" | "playlist-import" | "stats-export" => with_argument(complete_path(argument)),
+        "playlist-export" => match argument.strip_prefix("--relative ") {
+            Some(path) => with_argument(
+                complete_path(path)
+                    .into_iter()
+                    .map(|path| format!("--relative {path}"))
+                    .collect(),
+            ),
+            None => with_argument(complete_path(argument)),
+        },
+        "shuffle" => with_argument(matching(&["on", "off", "toggle"], argument)),
+        "sort" => match argument.split_once(' ') {
+            Some((field, order)) => with_argument(
+                matching(&["asc", "desc"], order)
+                    .into_iter()
+                    .map(|order| format!("{field} {order}"))
+                    .collect(),
+            ),
+            None => with_argument(matching(&SORT_FIELDS, argument)),
+        },
+        "save-playlist" | "playlist-add" | "playlist-delete" | "playlist-load" => {
+            let names: Vec<String> = fs::read_dir(playlist_dir())
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .path()
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string())
+                })
+                .filter(|stem| stem.starts_with(argument))
+                .collect();
+            with_argument(names)
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn complete_path(argument: &str) -> Vec<String> {
+    let (dir_part, file_prefix) = match argument.rfind('/') {
+        Some(index) => (&argument[..=index], &argument[index + 1..]),
+        None => ("", argument),
+    };
+    let dir = if dir_part.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir_part)
+    };
+    let mut candidates: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !file_name.starts_with(file_prefix)
+                || (file_name.starts_with('.') && !file_prefix.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            Some(format!(
+                "{dir_part}{file_name}{}",
+                if is_dir { "/" } else { "" }
+            ))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = env::var("HOME").unwrap_or(".".to_string());
+            PathBuf::from(format!("{home}{rest}"))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn parse_playlist_name(command: &str, argument: &str) -> Result<String, String> {
+    if argument.is_empty() {
+        return Err(format!("Usage: {command} <playlist name>"));
+    }
+    check_name(argument).map(|_| argument.to_string())
+}
+
+fn parse_position(argument: &str) -> Option<SeekPosition> {
+    if let Some(time) = argument.strip_prefix('+') {
+        return parse_time(time).map(SeekPosition::Forward);
+    }
+    if let Some(time) = argument.strip_prefix('-') {
+        return parse_time(time).map(SeekPosition::Backward);
+    }
+    parse_time(argument).map(SeekPosition::Absolute)
+}
+
+/// `83`, `1:23` or `1:02:03`, in ms.
+fn parse_time(time: &str) -> Option<u64> {
+    if time.is_empty() {
+        return None;
+    }
+    let mut seconds = 0;
+    for part in time.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(seconds * 1000)
+}
+
+fn parse_volume(argument: &str) -> Option<Volume> {
+    if let Some(step) = argument.strip_prefix('+') {
+        return step.parse().ok().map(Volume::Relative);
+    }
+    if argument.starts_with('-') {
+        return argument.parse().ok().map(Volume::Relative);
+    }
+    argument
+        .parse::<i32>()
+        .ok()
+        .filter(|volume| (0..=100).contains(volume))
+        .map(Volume::Absolute)
+}
+
+fn parse_rating(argument: &str) -> Option<Rating> {
+    match argument {
+        "+" => Some(Rating::Up),
+        "-" => Some(Rating::Down),
+        _ => argument
+            .parse()
+            .ok()
+            .filter(|rating| *rating <= 5)
+            .map(Rating::Set),
+    }
+}
+
+fn parse_sort(argument: &str) -> Option<Command> {
+    let mut words = argument.split_whitespace();
+    let sort_by = match words.next()? {
+        "title" => SortBy::Title,
+        "artist" => SortBy::Artist,
+        "album" => SortBy::Album,
+        "year" => SortBy::Year,
+        "rating" => SortBy::Rating,
+        "plays" => SortBy::PlayCount,
+        "last-played" => SortBy::LastPlayed,
+        "added" => SortBy::Added,
+        _ => return None,
+    };
+    let sort_order = match words.next() {
+        None | Some("asc") => SortOrder::ASC,
+        Some("desc") => SortOrder::DESC,
+        _ => return None,
+    };
+    Some(Command::Sort(sort_by, sort_order))
+}
+
+/// Input and history of the `:` prompt.
+pub struct CommandLine {
+    input: String,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    completions: Vec<String>,
+    completion_index: usize,
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        CommandLine {
+            input: String::new(),
+            history: Vec::new(),
+            history_index: None,
+            completions: Vec::new(),
+            completion_index: 0,
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn open(&mut self) {
+        self.open_with("");
+    }
+
+    /// Opens the prompt with `input` already typed.
+    pub fn open_with(&mut self, input: &str) {
+        self.input = input.to_string();
+        self.history_index = None;
+        self.completions.clear();
+    }
+
+    pub fn add_char(&mut self, char: char) {
+        self.input.push(char);
+        self.completions.clear();
+    }
+
+    pub fn remove_last_char(&mut self) {
+        self.input.pop();
+        self.completions.clear();
+    }
+
+    /// Takes the input and remembers it, skipping repeats of the previous entry.
+    pub fn submit(&mut self) -> String {
+        let input = std::mem::take(&mut self.input);
+        if !input.trim().is_empty() && self.history.last() != Some(&input) {
+            self.history.push(input.clone());
+        }
+        self.history_index = None;
+        self.completions.clear();
+        input
+    }
+
+    pub fn history_previous(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+        self.completions.clear();
+    }
+
+    pub fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.history_index = Some(index + 1);
+            self.input = self.history[index + 1].clone();
+        } else {
+            self.history_index = None;
+            self.input.clear();
+        }
+        self.completions.clear();
+    }
+
+    /// Completes the input, repeated calls cycle through the candidates.
+    pub fn complete(&mut self) {
+        if self.completions.is_empty() {
+            self.completions = complete(&self.input);
+            self.completion_index = 0;
+        } else {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        }
+        if let Some(completion) = self.completions.get(self.completion_index) {
+            self.input = completion.clone();
+        }
+        // A single candidate is final, so the next tab completes from there
+        if self.completions.len() == 1 {
+            self.completions.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_seek_positions() {
+        assert!(matches!(
+            Command::parse("seek 1:23"),
+            Ok(Command::Seek(SeekPosition::Absolute(83_000)))
+        ));
+        assert!(matches!(
+            Command::parse(":seek +10"),
+            Ok(Command::Seek(SeekPosition::Forward(10_000)))
+        ));
+        assert!(matches!(
+            Command::parse("seek -10"),
+            Ok(Command::Seek(SeekPosition::Backward(10_000)))
+        ));
+        assert!(Command::parse("seek").is_err());
+        assert!(Command::parse("seek 1:x").is_err());
+    }
+
+    #[test]
+    fn parses_volume_steps() {
+        assert!(matches!(
+            Command::parse("vol +5"),
+            Ok(Command::Volume(Volume::Relative(5)))
+        ));
+        assert!(matches!(
+            Command::parse("vol -5"),
+            Ok(Command::Volume(Volume::Relative(-5)))
+        ));
+        assert!(matches!(
+            Command::parse("volume 40"),
+            Ok(Command::Volume(Volume::Absolute(40)))
+        ));
+        assert!(Command::parse("vol 101").is_err());
+    }
+
+    #[test]
+    fn parses_sort_and_shuffle() {
+        assert!(matches!(
+            Command::parse("sort artist desc"),
+            Ok(Command::Sort(SortBy::Artist, SortOrder::DESC))
+        ));
+        assert!(matches!(
+            Command::parse("sort plays"),
+            Ok(Command::Sort(SortBy::PlayCount, SortOrder::ASC))
+        ));
+        assert!(Command::parse("sort artist sideways").is_err());
+        assert!(matches!(
+            Command::parse("shuffle on"),
+            Ok(Command::Shuffle(Some(true)))
+        ));
+        assert!(matches!(
+            Command::parse("shuffle"),
+            Ok(Command::Shuffle(None))
+        ));
+        assert!(Command::parse("shuffle maybe").is_err());
+    }
+
+    #[test]
+    fn completes_command_arguments() {
+        assert_eq!(complete("sh"), vec!["shuffle "]);
+        assert_eq!(complete("sort artist d"), vec!["sort artist desc"]);
+        assert_eq!(complete("sort la"), vec!["sort last-played"]);
+        assert!(complete("nothing ").is_empty());
+    }
+
+    #[test]
+    fn repeated_completion_cycles_through_candidates() {
+        let mut command_line = CommandLine::new();
+        command_line.open_with("shuffle o");
+        command_line.complete();
+        assert_eq!(command_line.input(), "shuffle on");
+        command_line.complete();
+        assert_eq!(command_line.input(), "shuffle off");
+        command_line.complete();
+        assert_eq!(command_line.input(), "shuffle on");
+
+        // Typing starts a new completion from the edited input
+        command_line.remove_last_char();
+        command_line.add_char('f');
+        command_line.complete();
+        assert_eq!(command_line.input(), "shuffle off");
+    }
+}
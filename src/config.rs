@@ -1,13 +1,639 @@
+use std::{env, fs, io, path::PathBuf, str::FromStr, sync::OnceLock, time::Duration};
+
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use crate::{
+    MediaDisplayType,
+    commands::expand_home,
+    error::ConfigError,
+    events::keymap::KeymapConfig,
+    logging::{TargetLevels, parse_filters},
+};
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Parser)]
+#[command(version, about = "A terminal music player")]
+pub struct Cli {
+    /// Config file to use instead of $XDG_CONFIG_HOME/music-ratatui/config.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Library folder, can be given more than once. Replaces the roots from the config file
+    #[arg(long = "library", short, value_name = "DIR")]
+    pub libraries: Vec<PathBuf>,
+    /// Same as --library
+    #[arg(value_name = "DIR")]
+    pub library: Option<PathBuf>,
+    /// off, error, warn, info, debug or trace. Overrides RUST_LOG
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+    /// Log file to use instead of $XDG_STATE_HOME/music-ratatui/music-ratatui.log
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+    /// Don't register with the system media controls (MPRIS on Linux)
+    #[arg(long)]
+    pub no_mpris: bool,
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+pub enum CliCommand {
+    /// Control a running instance through its socket
+    Ctl {
+        #[command(subcommand)]
+        request: CtlRequest,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CtlRequest {
+    Play,
+    Pause,
+    Next,
+    /// Add a file or folder to the queue
+    Add {
+        path: PathBuf,
+    },
+    /// Print the player state as JSON
+    Status,
+}
+
+pub struct Theme {
+    /// Focused input and key hints
+    pub accent: Color,
+    /// Background of the selected row
+    pub selection: Color,
+    /// Playing song and current lyric line
+    pub playing: Color,
+    pub error: Color,
+    pub warning: Color,
+}
+
+pub struct ListenBrainzConfig {
+    pub token: String,
+    /// API root, without the `/1/...` path
+    pub url: String,
+}
+
+/// The session key comes from Last.fm's auth flow, e.g. `auth.getMobileSession`.
+pub struct LastfmConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+    pub url: String,
+}
+
 pub struct Config {
-    pub ytdl_libs: String,
-    pub ytdl_output: String,
+    /// Helper binaries for downloads, fetched on first use
+    pub ytdl_libs: PathBuf,
+    pub download_dir: PathBuf,
+    /// File name of downloads, with `{artist}`, `{title}` and `{album}` replaced
+    pub download_template: String,
+    pub library_roots: Vec<PathBuf>,
+    pub extensions: Vec<String>,
+    pub scan_depth: u32,
+    pub musicbrainz_user_agent: String,
+    pub musicbrainz_requests_per_second: f64,
+    pub musicbrainz_max_retries: u32,
+    pub musicbrainz_cache_ttl: Duration,
+    pub cache_dir: PathBuf,
+    pub config_file: PathBuf,
+    pub rc_file: PathBuf,
+    pub data_dir: PathBuf,
+    pub default_view: MediaDisplayType,
+    pub album_art: bool,
+    pub lyrics_provider: bool,
+    /// Applied at startup, libvlc's own default otherwise
+    pub volume: Option<i32>,
+    pub mpris: bool,
+    /// Part of a song that has to be heard for a play, less is a skip
+    pub play_threshold: f64,
+    /// Previous at the start of the queue plays the song before it from the history
+    pub history_fallback: bool,
+    /// Write ratings and play counts to POPM/FMPS tags, not just the library DB
+    pub write_rating_tags: bool,
+    /// Scrobbling is off for services without credentials
+    pub listenbrainz: Option<ListenBrainzConfig>,
+    pub lastfm: Option<LastfmConfig>,
+    /// Remote control socket, see `ipc`
+    pub ipc_enabled: bool,
+    pub ipc_socket: PathBuf,
+    pub log_level: LevelFilter,
+    pub log_file: PathBuf,
+    /// Per-target levels from RUST_LOG, on top of `log_level`
+    pub log_filters: TargetLevels,
+    pub theme: Theme,
+    pub keymap: KeymapConfig,
 }
 
 impl Config {
     pub fn new() -> Self {
+        let home = env::var_os("HOME").map(PathBuf::from);
         Config {
-            ytdl_libs: String::from("libs"),
-            ytdl_output: String::from("output"),
+            ytdl_libs: data_dir().join("libs"),
+            download_dir: home
+                .clone()
+                .map(|home| home.join("Music").join("music-ratatui"))
+                .unwrap_or_else(|| data_dir().join("downloads")),
+            download_template: String::from("{artist} - {title}"),
+            library_roots: vec![home.unwrap_or_else(|| PathBuf::from("."))],
+            extensions: [".mp3", ".ogg", ".wav", ".flac", ".m4a"]
+                .map(String::from)
+                .to_vec(),
+            scan_depth: 2,
+            musicbrainz_user_agent: format!(
+                "music-ratatui/{} ( https://github.com/BenRedblock/music-ratatui )",
+                env!("CARGO_PKG_VERSION")
+            ),
+            musicbrainz_requests_per_second: 1.0,
+            musicbrainz_max_retries: 4,
+            musicbrainz_cache_ttl: Duration::from_secs(60 * 60 * 24 * 7),
+            cache_dir: cache_dir(),
+            config_file: config_dir().join("config.toml"),
+            rc_file: config_dir().join("rc"),
+            data_dir: data_dir(),
+            default_view: MediaDisplayType::Folders,
+            album_art: true,
+            lyrics_provider: true,
+            volume: None,
+            mpris: true,
+            play_threshold: 0.5,
+            history_fallback: true,
+            write_rating_tags: false,
+            listenbrainz: None,
+            lastfm: None,
+            ipc_enabled: true,
+            ipc_socket: runtime_dir().join("music-ratatui.sock"),
+            log_level: LevelFilter::Info,
+            log_file: state_dir().join("music-ratatui.log"),
+            log_filters: Vec::new(),
+            theme: Theme {
+                accent: Color::Yellow,
+                selection: Color::Yellow,
+                playing: Color::LightGreen,
+                error: Color::LightRed,
+                warning: Color::Yellow,
+            },
+            keymap: KeymapConfig::default(),
+        }
+    }
+
+    /// The config file, or the defaults if there is none, with the command line on top.
+    /// A missing explicit `--config` file is an error.
+    pub fn load(cli: &Cli) -> Result<Self, ConfigError> {
+        let mut config = Config::new();
+        if let Some(path) = &cli.config {
+            config.config_file = path.clone();
+            config.rc_file = path.with_file_name("rc");
+        }
+        match fs::read_to_string(&config.config_file) {
+            Ok(content) => config.apply_file(&content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && cli.config.is_none() => {}
+            Err(source) => {
+                return Err(ConfigError::Read {
+                    path: config.config_file,
+                    source,
+                });
+            }
+        }
+
+        let libraries: Vec<PathBuf> = cli
+            .libraries
+            .iter()
+            .chain(cli.library.iter())
+            .cloned()
+            .collect();
+        if !libraries.is_empty() {
+            config.library_roots = libraries;
+        }
+        if let Ok(spec) = env::var("RUST_LOG") {
+            let (log_level, log_filters) =
+                parse_filters(&spec).map_err(|message| ConfigError::Env {
+                    var: "RUST_LOG".to_string(),
+                    message,
+                })?;
+            config.log_level = log_level.unwrap_or(config.log_level);
+            config.log_filters = log_filters;
+        }
+        if let Some(log_file) = &cli.log_file {
+            config.log_file = log_file.clone();
+        }
+        if let Some(log_level) = cli.log_level {
+            config.log_level = log_level;
         }
+        if cli.no_mpris {
+            config.mpris = false;
+        }
+        Ok(config)
     }
+
+    fn apply_file(&mut self, content: &str) -> Result<(), ConfigError> {
+        let file: ConfigFile = toml::from_str(content).map_err(|err| ConfigError::Parse {
+            path: self.config_file.clone(),
+            message: err.to_string().trim_end().to_string(),
+        })?;
+        let path = self.config_file.clone();
+        let invalid = |field: &str, message: String| ConfigError::Invalid {
+            path: path.clone(),
+            field: field.to_string(),
+            message,
+        };
+
+        let library = file.library;
+        if let Some(roots) = library.roots {
+            if roots.is_empty() {
+                return Err(invalid("library.roots", "needs at least one folder".into()));
+            }
+            self.library_roots = roots.iter().map(|root| expand_home(root)).collect();
+        }
+        if let Some(extensions) = library.extensions {
+            if extensions.is_empty() {
+                return Err(invalid("library.extensions", "needs at least one".into()));
+            }
+            self.extensions = extensions
+                .into_iter()
+                .map(|extension| format!(".{}", extension.trim_start_matches('.')))
+                .collect();
+        }
+        if let Some(scan_depth) = library.scan_depth {
+            if scan_depth > MAX_SCAN_DEPTH {
+                return Err(invalid(
+                    "library.scan_depth",
+                    format!("{scan_depth} is too deep, at most {MAX_SCAN_DEPTH}"),
+                ));
+            }
+            self.scan_depth = scan_depth;
+        }
+        self.write_rating_tags = library.write_rating_tags.unwrap_or(self.write_rating_tags);
+
+        let downloads = file.downloads;
+        if let Some(dir) = downloads.dir {
+            self.download_dir = expand_home(&dir);
+        }
+        if let Some(tools_dir) = downloads.tools_dir {
+            self.ytdl_libs = expand_home(&tools_dir);
+        }
+        if let Some(template) = downloads.template {
+            if !template.contains("{title}") {
+                return Err(invalid(
+                    "downloads.template",
+                    format!("{template:?} needs {{title}}, or every download gets the same name"),
+                ));
+            }
+            if template.contains(['/', '\\']) {
+                return Err(invalid(
+                    "downloads.template",
+                    "is a file name, set downloads.dir for the folder".into(),
+                ));
+            }
+            self.download_template = template;
+        }
+
+        let musicbrainz = file.musicbrainz;
+        if let Some(user_agent) = musicbrainz.user_agent {
+            let user_agent = user_agent.trim().to_string();
+            if user_agent.is_empty() || user_agent.chars().any(char::is_control) {
+                return Err(invalid(
+                    "musicbrainz.user_agent",
+                    "needs to be a single non-empty line".into(),
+                ));
+            }
+            // MusicBrainz blocks clients it can't reach, see their rate limiting docs
+            if !user_agent.contains('@') && !user_agent.contains("://") {
+                return Err(invalid(
+                    "musicbrainz.user_agent",
+                    format!(
+                        "{user_agent:?} needs contact info, like \"app/1.0 ( me@example.com )\""
+                    ),
+                ));
+            }
+            self.musicbrainz_user_agent = user_agent;
+        }
+        if let Some(requests_per_second) = musicbrainz.requests_per_second {
+            if requests_per_second <= 0.0 || !requests_per_second.is_finite() {
+                return Err(invalid(
+                    "musicbrainz.requests_per_second",
+                    format!("{requests_per_second} needs to be above 0"),
+                ));
+            }
+            self.musicbrainz_requests_per_second = requests_per_second;
+        }
+        self.musicbrainz_max_retries = musicbrainz
+            .max_retries
+            .unwrap_or(self.musicbrainz_max_retries);
+        if let Some(cache_ttl) = musicbrainz.cache_ttl {
+            self.musicbrainz_cache_ttl = Duration::from_secs(cache_ttl);
+        }
+
+        let ui = file.ui;
+        if let Some(default_view) = ui.default_view {
+            self.default_view = MediaDisplayType::from_name(&default_view).ok_or_else(|| {
+                invalid(
+                    "ui.default_view",
+                    format!(
+                        "unknown view {default_view:?}, expected songs, folders, playlists, search, lyrics, log or history"
+                    ),
+                )
+            })?;
+        }
+        self.album_art = ui.album_art.unwrap_or(self.album_art);
+        self.lyrics_provider = ui.lyrics_provider.unwrap_or(self.lyrics_provider);
+
+        let player = file.player;
+        if let Some(volume) = player.volume {
+            if !(0..=100).contains(&volume) {
+                return Err(invalid(
+                    "player.volume",
+                    format!("{volume} is outside 0 to 100"),
+                ));
+            }
+            self.volume = Some(volume);
+        }
+        self.mpris = player.mpris.unwrap_or(self.mpris);
+        if let Some(play_threshold) = player.play_threshold {
+            if !(0.0..=1.0).contains(&play_threshold) {
+                return Err(invalid(
+                    "player.play_threshold",
+                    format!("{play_threshold} is outside 0 to 1"),
+                ));
+            }
+            self.play_threshold = play_threshold;
+        }
+        self.history_fallback = player.history_fallback.unwrap_or(self.history_fallback);
+
+        let scrobble = file.scrobble;
+        if let Some(listenbrainz) = scrobble.listenbrainz {
+            if listenbrainz.token.trim().is_empty() {
+                return Err(invalid("scrobble.listenbrainz.token", "is empty".into()));
+            }
+            self.listenbrainz = Some(ListenBrainzConfig {
+                token: listenbrainz.token,
+                url: listenbrainz
+                    .url
+                    .unwrap_or(LISTENBRAINZ_URL.to_string())
+                    .trim_end_matches('/')
+                    .to_string(),
+            });
+        }
+        if let Some(lastfm) = scrobble.lastfm {
+            for (field, value) in [
+                ("scrobble.lastfm.api_key", &lastfm.api_key),
+                ("scrobble.lastfm.api_secret", &lastfm.api_secret),
+                ("scrobble.lastfm.session_key", &lastfm.session_key),
+            ] {
+                if value.trim().is_empty() {
+                    return Err(invalid(field, "is empty".into()));
+                }
+            }
+            self.lastfm = Some(LastfmConfig {
+                api_key: lastfm.api_key,
+                api_secret: lastfm.api_secret,
+                session_key: lastfm.session_key,
+                url: lastfm.url.unwrap_or(LASTFM_URL.to_string()),
+            });
+        }
+
+        self.ipc_enabled = file.ipc.enabled.unwrap_or(self.ipc_enabled);
+        if let Some(socket) = &file.ipc.socket {
+            self.ipc_socket = expand_home(socket);
+        }
+
+        if let Some(log_file) = &file.logging.file {
+            self.log_file = expand_home(log_file);
+        }
+        if let Some(level) = file.logging.level {
+            self.log_level = LevelFilter::from_str(&level).map_err(|_| {
+                invalid(
+                    "logging.level",
+                    format!(
+                        "unknown level {level:?}, expected off, error, warn, info, debug or trace"
+                    ),
+                )
+            })?;
+        }
+
+        let theme = file.theme;
+        for (field, value, color) in [
+            ("theme.accent", theme.accent, &mut self.theme.accent),
+            (
+                "theme.selection",
+                theme.selection,
+                &mut self.theme.selection,
+            ),
+            ("theme.playing", theme.playing, &mut self.theme.playing),
+            ("theme.error", theme.error, &mut self.theme.error),
+            ("theme.warning", theme.warning, &mut self.theme.warning),
+        ] {
+            if let Some(value) = value {
+                *color = Color::from_str(&value).map_err(|_| {
+                    invalid(
+                        field,
+                        format!(
+                            "unknown color {value:?}, use a name like \"lightblue\" or \"#rrggbb\""
+                        ),
+                    )
+                })?;
+            }
+        }
+
+        self.keymap = file.keymap;
+        Ok(())
+    }
+
+    /// Problems that don't stop the app from starting.
+    pub fn warnings(&self) -> Vec<String> {
+        self.library_roots
+            .iter()
+            .filter(|root| !root.is_dir())
+            .map(|root| format!("Library folder {} doesn't exist", root.display()))
+            .collect()
+    }
+
+    /// Set once at startup, before anything reads the config.
+    pub fn init(config: Config) {
+        let _ = CONFIG.set(config);
+    }
+
+    /// The loaded config, or the defaults before `init`.
+    pub fn get() -> &'static Config {
+        CONFIG.get_or_init(Config::new)
+    }
+}
+
+const MAX_SCAN_DEPTH: u32 = 20;
+const LISTENBRAINZ_URL: &str = "https://api.listenbrainz.org";
+const LASTFM_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Layout of config.toml. Every value is optional, unknown keys are errors so typos are caught.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    library: LibrarySection,
+    #[serde(default)]
+    downloads: DownloadsSection,
+    #[serde(default)]
+    musicbrainz: MusicBrainzSection,
+    #[serde(default)]
+    ui: UiSection,
+    #[serde(default)]
+    player: PlayerSection,
+    #[serde(default)]
+    scrobble: ScrobbleSection,
+    #[serde(default)]
+    ipc: IpcSection,
+    #[serde(default)]
+    logging: LoggingSection,
+    #[serde(default)]
+    theme: ThemeSection,
+    #[serde(default)]
+    keymap: KeymapConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct LibrarySection {
+    roots: Option<Vec<String>>,
+    extensions: Option<Vec<String>>,
+    scan_depth: Option<u32>,
+    write_rating_tags: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DownloadsSection {
+    dir: Option<String>,
+    template: Option<String>,
+    tools_dir: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct MusicBrainzSection {
+    user_agent: Option<String>,
+    requests_per_second: Option<f64>,
+    max_retries: Option<u32>,
+    /// Seconds
+    cache_ttl: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct UiSection {
+    default_view: Option<String>,
+    album_art: Option<bool>,
+    lyrics_provider: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct PlayerSection {
+    volume: Option<i32>,
+    mpris: Option<bool>,
+    play_threshold: Option<f64>,
+    history_fallback: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ScrobbleSection {
+    listenbrainz: Option<ListenBrainzSection>,
+    lastfm: Option<LastfmSection>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListenBrainzSection {
+    token: String,
+    url: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LastfmSection {
+    api_key: String,
+    api_secret: String,
+    session_key: String,
+    url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct IpcSection {
+    enabled: Option<bool>,
+    socket: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct LoggingSection {
+    level: Option<String>,
+    file: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeSection {
+    accent: Option<String>,
+    selection: Option<String>,
+    playing: Option<String>,
+    error: Option<String>,
+    warning: Option<String>,
+}
+
+fn cache_dir() -> PathBuf {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(|| PathBuf::from(".cache"));
+    base.join("music-ratatui")
+}
+
+fn data_dir() -> PathBuf {
+    let base = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from(".local/share"));
+    base.join("music-ratatui")
+}
+
+fn state_dir() -> PathBuf {
+    let base = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from(".local/state"));
+    base.join("music-ratatui")
+}
+
+/// Sockets go in the user's runtime dir, the state dir is the fallback where there is none.
+fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("music-ratatui"))
+        .unwrap_or_else(state_dir)
+}
+
+fn config_dir() -> PathBuf {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from(".config"));
+    base.join("music-ratatui")
+}
+
+/// Fills the download template. Characters that can't be in a file name are replaced.
+pub fn download_file_name(
+    template: &str,
+    title: &str,
+    artist: Option<&str>,
+    album: Option<&str>,
+) -> String {
+    let name = template
+        .replace("{title}", title)
+        .replace("{artist}", artist.unwrap_or("Unknown artist"))
+        .replace("{album}", album.unwrap_or("Unknown album"));
+    name.replace(['/', '\\', '\0'], "_")
 }
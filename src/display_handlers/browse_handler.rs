@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+
+use ratatui::{
+    style::{Modifier, Style},
+    widgets::ListItem,
+};
+
+use crate::{
+    fetch::discography::ReleaseGroupSummary,
+    song::{Song, SongType},
+    utils::selecthandler::{Movement, SelectHandler, SelectHandlerItem},
+};
+
+#[derive(Clone)]
+pub enum BrowseNode {
+    Heading(String),
+    Song(Song),
+    ReleaseGroup(ReleaseGroupSummary),
+}
+
+impl SelectHandlerItem for BrowseNode {
+    fn list_item(&self) -> ListItem<'_> {
+        match self {
+            BrowseNode::Heading(heading) => {
+                ListItem::new(heading.clone()).style(Style::default().add_modifier(Modifier::BOLD))
+            }
+            BrowseNode::Song(song) => {
+                if song.is_local() || song.is_online_downloaded() {
+                    ListItem::new(format!("✔ {}", song.title))
+                } else {
+                    song.list_item()
+                }
+            }
+            BrowseNode::ReleaseGroup(release_group) => match &release_group.year {
+                Some(year) => ListItem::new(format!("💿 {} ({})", release_group.title, year)),
+                None => ListItem::new(format!("💿 {}", release_group.title)),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BrowsePageKind {
+    SearchResults,
+    Artist,
+    Album,
+}
+
+pub struct BrowsePage {
+    title: String,
+    kind: BrowsePageKind,
+    pub select_handler: SelectHandler<BrowseNode>,
+}
+
+impl BrowsePage {
+    fn new(title: String, kind: BrowsePageKind, nodes: Vec<BrowseNode>) -> Self {
+        let mut select_handler = SelectHandler::new();
+        select_handler.set_items(nodes);
+        BrowsePage {
+            title,
+            kind,
+            select_handler,
+        }
+    }
+}
+
+/// Navigation stack for online results: search results, an artist's discography, an album's tracks.
+pub struct BrowseHandler {
+    pages: Vec<BrowsePage>,
+    loading: bool,
+}
+
+impl BrowseHandler {
+    pub fn new() -> Self {
+        BrowseHandler {
+            pages: vec![BrowsePage::new(
+                "Search".to_string(),
+                BrowsePageKind::SearchResults,
+                Vec::new(),
+            )],
+            loading: false,
+        }
+    }
+
+    pub fn set_results(&mut self, songs: Vec<Song>) {
+        self.pages = vec![BrowsePage::new(
+            "Search".to_string(),
+            BrowsePageKind::SearchResults,
+            songs.into_iter().map(BrowseNode::Song).collect(),
+        )];
+    }
+
+    pub fn push_artist(&mut self, artist: String, release_groups: Vec<ReleaseGroupSummary>) {
+        let mut nodes = Vec::new();
+        let mut last_kind = None;
+        for release_group in release_groups {
+            if last_kind != Some(release_group.kind) {
+                last_kind = Some(release_group.kind);
+                nodes.push(BrowseNode::Heading(release_group.kind.label().to_string()));
+            }
+            nodes.push(BrowseNode::ReleaseGroup(release_group));
+        }
+        self.pages
+            .push(BrowsePage::new(artist, BrowsePageKind::Artist, nodes));
+    }
+
+    pub fn push_album(&mut self, title: String, tracks: Vec<Song>) {
+        self.pages.push(BrowsePage::new(
+            title,
+            BrowsePageKind::Album,
+            tracks.into_iter().map(BrowseNode::Song).collect(),
+        ));
+    }
+
+    pub fn go_to_parent(&mut self) {
+        if self.pages.len() > 1 {
+            self.pages.pop();
+        }
+    }
+
+    pub fn set_loading(&mut self, loading: bool) {
+        self.loading = loading;
+    }
+
+    pub fn breadcrumb(&self) -> String {
+        let path = self
+            .pages
+            .iter()
+            .map(|page| page.title.as_str())
+            .collect::<Vec<&str>>()
+            .join(" > ");
+        if self.loading {
+            format!("{path} (loading...)")
+        } else {
+            path
+        }
+    }
+
+    pub fn current_page_kind(&self) -> BrowsePageKind {
+        self.current_page().kind
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.current_page_mut()
+            .select_handler
+            .move_selection(movement);
+    }
+
+    pub fn select_handler_selected(&self) -> Option<&BrowseNode> {
+        self.current_page().select_handler.select()
+    }
+
+    pub fn select_handler(&mut self) -> &mut SelectHandler<BrowseNode> {
+        &mut self.current_page_mut().select_handler
+    }
+
+    /// Position of the selected song in `songs`, `None` if no song is selected.
+    pub fn selected_song_index(&self) -> Option<usize> {
+        let select_handler = &self.current_page().select_handler;
+        let selected = select_handler.selected_index()?;
+        let items = select_handler.items();
+        matches!(items.get(selected)?, BrowseNode::Song(_)).then(|| {
+            items[..selected]
+                .iter()
+                .filter(|node| matches!(node, BrowseNode::Song(_)))
+                .count()
+        })
+    }
+
+    /// Songs listed on the current page, in order.
+    pub fn songs(&self) -> Vec<Song> {
+        self.current_page()
+            .select_handler
+            .items()
+            .iter()
+            .filter_map(|node| match node {
+                BrowseNode::Song(song) => Some(song.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn mark_downloaded(&mut self, url: &str, path: PathBuf) {
+        for page in &mut self.pages {
+            let mut items = page.select_handler.items().clone();
+            let mut changed = false;
+            for node in &mut items {
+                if let BrowseNode::Song(song) = node
+                    && matches!(&song.song_type, SongType::Online { url: song_url } if song_url == url)
+                {
+                    song.song_type = SongType::OnlineDownloaded {
+                        url: url.to_string(),
+                        path: path.clone(),
+                    };
+                    changed = true;
+                }
+            }
+            if changed {
+                let selected = page.select_handler.state().selected();
+                page.select_handler.set_items(items);
+                page.select_handler.state().select(selected);
+            }
+        }
+    }
+
+    fn current_page(&self) -> &BrowsePage {
+        self.pages.last().expect("Browse stack is never empty")
+    }
+
+    fn current_page_mut(&mut self) -> &mut BrowsePage {
+        self.pages.last_mut().expect("Browse stack is never empty")
+    }
+}
@@ -4,13 +4,16 @@ use log::info;
 use ratatui::widgets::{ListItem, ListState};
 
 use crate::{
+    playlist_file::PlaylistFile,
     song::{Song, SongType},
-    utils::selecthandler::{SelectHandler, SelectHandlerItem},
+    utils::selecthandler::{Movement, SelectHandler, SelectHandlerItem},
 };
 
 #[derive(Clone)]
 pub enum Node {
     Folder(Folder),
+    /// A playlist file, opened like a folder of its songs
+    Playlist(Folder),
     Song(Song),
 }
 
@@ -18,6 +21,11 @@ impl SelectHandlerItem for Node {
     fn list_item(&self) -> ListItem<'_> {
         match self {
             Node::Folder(folder) => folder.list_item(),
+            Node::Playlist(playlist) => ListItem::new(format!(
+                "📃 {} ({})",
+                playlist.name,
+                playlist.children.len()
+            )),
             Node::Song(song) => song.list_item(),
         }
     }
@@ -41,6 +49,7 @@ impl Folder {
     pub fn add_child(&mut self, child: Node) {
         match child {
             Node::Folder(folder) => self.children.insert(0, Node::Folder(folder)),
+            Node::Playlist(playlist) => self.children.insert(0, Node::Playlist(playlist)),
             Node::Song(song) => self.children.push(Node::Song(song)),
         }
     }
@@ -78,7 +87,7 @@ impl Folder {
     }
     pub fn get_folder_at_path(&self, path: PathBuf) -> Option<&Folder> {
         self.children.iter().find_map(|child| {
-            if let Node::Folder(folder) = child {
+            if let Node::Folder(folder) | Node::Playlist(folder) = child {
                 if folder.path.eq(&path) {
                     return Some(folder);
                 }
@@ -118,8 +127,31 @@ impl FolderHandler {
         s.visualize_tree();
         s
     }
-    pub fn insert_songs(&mut self, songs: Vec<Song>) {
+    pub fn insert_songs(&mut self, songs: Vec<Song>, playlist_files: Vec<PlaylistFile>) {
         let mut map: HashMap<PathBuf, Folder> = HashMap::new();
+        for playlist_file in playlist_files {
+            let Some(parent_path) = playlist_file.path.parent() else {
+                continue;
+            };
+            let parent_name = parent_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut playlist = Folder::new(
+                playlist_file
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                playlist_file.path.clone(),
+            );
+            for song in playlist_file.songs {
+                playlist.add_child(Node::Song(song));
+            }
+            map.entry(parent_path.to_path_buf())
+                .or_insert(Folder::new(parent_name, parent_path.to_path_buf()))
+                .add_child(Node::Playlist(playlist));
+        }
         for song in songs {
             if let SongType::Local { ref path } = song.song_type {
                 let parent_path = path.parent().expect("Should exist").to_path_buf();
@@ -160,11 +192,8 @@ impl FolderHandler {
         self.path_stack.clear();
         self.populate_select_handler();
     }
-    pub fn select_handler_up(&mut self) {
-        self.select_handler.up();
-    }
-    pub fn select_handler_down(&mut self) {
-        self.select_handler.down();
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.select_handler.move_selection(movement);
     }
     pub fn select_handler_selected(&self) -> Option<&Node> {
         self.select_handler.select()
@@ -173,7 +202,7 @@ impl FolderHandler {
         match self.select_handler.select() {
             Some(node) => match node {
                 Node::Song(song) => Some(song.clone()),
-                Node::Folder(folder) => {
+                Node::Folder(folder) | Node::Playlist(folder) => {
                     self.go_to_child(&folder.path.clone());
 
                     None
@@ -201,6 +230,14 @@ impl FolderHandler {
                     info!("{}📁 {} ({})", indent, folder.name(), folder.children.len());
                     self.visualize_node_recursive(&folder.children, depth + 1);
                 }
+                Node::Playlist(playlist) => {
+                    info!(
+                        "{}📃 {} ({})",
+                        indent,
+                        playlist.name(),
+                        playlist.children.len()
+                    );
+                }
                 Node::Song(song) => {
                     info!("{}🎵 {}", indent, song.title);
                 }
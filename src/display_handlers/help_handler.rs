@@ -0,0 +1,98 @@
+use crate::events::keymap::{KeyContext, Keymap};
+
+pub enum HelpLine {
+    Heading(&'static str),
+    Binding { keys: String, action: &'static str },
+}
+
+/// State of the help popup: a filter typed by the user and how far the list is scrolled.
+pub struct HelpHandler {
+    query: String,
+    scroll: u16,
+    page_height: u16,
+}
+
+impl HelpHandler {
+    pub fn new() -> Self {
+        HelpHandler {
+            query: String::new(),
+            scroll: 0,
+            page_height: 1,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.query.clear();
+        self.scroll = 0;
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn add_char_to_query(&mut self, char: char) {
+        self.query.push(char);
+        self.scroll = 0;
+    }
+
+    pub fn remove_last_char(&mut self) {
+        self.query.pop();
+        self.scroll = 0;
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines as u16);
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_add(lines as u16);
+    }
+
+    pub fn page_height(&self) -> usize {
+        self.page_height as usize
+    }
+
+    /// Keeps the scroll position inside the list once its height is known.
+    pub fn scroll(&mut self, line_count: usize, height: u16) -> u16 {
+        self.page_height = height.max(1);
+        let max_scroll = (line_count as u16).saturating_sub(height);
+        self.scroll = self.scroll.min(max_scroll);
+        self.scroll
+    }
+
+    /// Every binding grouped by context, keys for the same action on one line.
+    pub fn lines(&self, keymap: &Keymap) -> Vec<HelpLine> {
+        let query = self.query.to_lowercase();
+        let mut lines = Vec::new();
+        for context in KeyContext::ALL {
+            let mut bindings: Vec<(String, &'static str)> = Vec::new();
+            for binding in keymap
+                .bindings()
+                .iter()
+                .filter(|binding| binding.context == context)
+            {
+                let label = binding.action.label();
+                match bindings.iter_mut().find(|(_, action)| *action == label) {
+                    Some((keys, _)) => keys.push_str(&format!(", {}", binding.keys)),
+                    None => bindings.push((binding.keys.to_string(), label)),
+                }
+            }
+            let context_matches = context.label().to_lowercase().contains(&query);
+            bindings.retain(|(keys, action)| {
+                context_matches
+                    || keys.to_lowercase().contains(&query)
+                    || action.to_lowercase().contains(&query)
+            });
+            if bindings.is_empty() {
+                continue;
+            }
+            lines.push(HelpLine::Heading(context.label()));
+            lines.extend(
+                bindings
+                    .into_iter()
+                    .map(|(keys, action)| HelpLine::Binding { keys, action }),
+            );
+        }
+        lines
+    }
+}
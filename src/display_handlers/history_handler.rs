@@ -0,0 +1,142 @@
+use ratatui::{
+    style::{Modifier, Style},
+    widgets::ListItem,
+};
+
+use crate::{
+    events::format_ms_to_duration_string,
+    history::{History, HistoryEntry},
+    song::Song,
+    utils::selecthandler::{Movement, SelectHandler, SelectHandlerItem},
+};
+
+/// Days shown in the pane, older plays stay in the file
+const MAX_DAYS: usize = 30;
+
+#[derive(Clone)]
+pub enum HistoryNode {
+    Day(String),
+    Entry(HistoryEntry),
+}
+
+impl SelectHandlerItem for HistoryNode {
+    fn list_item(&self) -> ListItem<'_> {
+        match self {
+            HistoryNode::Day(day) => {
+                ListItem::new(day.clone()).style(Style::default().add_modifier(Modifier::BOLD))
+            }
+            HistoryNode::Entry(entry) => {
+                let mut text = format!(
+                    "  {} 🎵 {}",
+                    entry.local_time().format("%H:%M"),
+                    entry.title
+                );
+                if let Some(artist) = &entry.artist {
+                    text.push_str(&format!(" - {artist}"));
+                }
+                text.push_str(&format!(
+                    " ({}/{}) [{}]",
+                    format_ms_to_duration_string(entry.listened),
+                    format_ms_to_duration_string(entry.length),
+                    entry.source
+                ));
+                if !entry.completed {
+                    text.push_str(" skipped");
+                }
+                ListItem::new(text)
+            }
+        }
+    }
+}
+
+/// Recent plays, newest first, under a heading for each day.
+pub struct HistoryHandler {
+    history: History,
+    pub select_handler: SelectHandler<HistoryNode>,
+}
+
+impl HistoryHandler {
+    pub fn new() -> Self {
+        let mut handler = HistoryHandler {
+            history: History::load(),
+            select_handler: SelectHandler::new(),
+        };
+        handler.update_items();
+        handler
+    }
+
+    pub fn add(&mut self, entry: HistoryEntry) -> Result<(), String> {
+        let result = self
+            .history
+            .add(entry)
+            .map_err(|err| format!("Failed to save the history: {err}"));
+        self.update_items();
+        result
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        self.history.entries()
+    }
+
+    /// The latest play that started before `before`, for going back past the start of the
+    /// queue.
+    pub fn last_before(&self, before: u64) -> Option<&HistoryEntry> {
+        self.history
+            .entries()
+            .iter()
+            .rev()
+            .find(|entry| entry.started < before && entry.location.is_some())
+    }
+
+    /// The selected play, or every play of the selected day, oldest first.
+    pub fn selected_songs(&self, library: &[Song]) -> Vec<Song> {
+        let items = self.select_handler.items();
+        let Some(index) = self.select_handler.selected_index() else {
+            return Vec::new();
+        };
+        match items.get(index) {
+            Some(HistoryNode::Entry(entry)) => entry.song(library).into_iter().collect(),
+            Some(HistoryNode::Day(_)) => {
+                let mut songs: Vec<Song> = items[index + 1..]
+                    .iter()
+                    .map_while(|node| match node {
+                        HistoryNode::Entry(entry) => Some(entry),
+                        HistoryNode::Day(_) => None,
+                    })
+                    .filter_map(|entry| entry.song(library))
+                    .collect();
+                songs.reverse();
+                songs
+            }
+            None => Vec::new(),
+        }
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.select_handler.move_selection(movement);
+    }
+
+    /// Rebuilds the list, keeping the selection where it was.
+    fn update_items(&mut self) {
+        let selected = self.select_handler.state().selected();
+        let mut items = Vec::new();
+        let mut days = 0;
+        let mut current_day = None;
+        for entry in self.history.entries().iter().rev() {
+            let day = entry.local_time().format("%A, %d %B %Y").to_string();
+            if current_day.as_ref() != Some(&day) {
+                days += 1;
+                if days > MAX_DAYS {
+                    break;
+                }
+                items.push(HistoryNode::Day(day.clone()));
+                current_day = Some(day);
+            }
+            items.push(HistoryNode::Entry(entry.clone()));
+        }
+        self.select_handler.set_items(items);
+        if let Some(selected) = selected {
+            self.select_handler.move_selection(Movement::To(selected));
+        }
+    }
+}
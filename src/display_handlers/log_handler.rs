@@ -0,0 +1,70 @@
+use log::LevelFilter;
+
+use crate::{
+    logging::{LogBuffer, LogLine},
+    utils::selecthandler::{Movement, SelectHandler},
+};
+
+/// Recent log records at or above a level. Sticks to the newest line until the user moves away.
+pub struct LogHandler {
+    buffer: LogBuffer,
+    level: LevelFilter,
+    generation: Option<u64>,
+    follow: bool,
+    pub select_handler: SelectHandler<LogLine>,
+}
+
+impl LogHandler {
+    pub fn new(buffer: LogBuffer) -> Self {
+        LogHandler {
+            buffer,
+            level: LevelFilter::Info,
+            generation: None,
+            follow: true,
+            select_handler: SelectHandler::new(),
+        }
+    }
+
+    /// Copies new records from the buffer. Returns whether anything changed.
+    pub fn refresh(&mut self) -> bool {
+        let generation = self.buffer.generation();
+        if self.generation == Some(generation) {
+            return false;
+        }
+        self.generation = Some(generation);
+        let selected = self.select_handler.state().selected();
+        self.select_handler.set_items(self.buffer.lines(self.level));
+        match selected {
+            Some(index) if !self.follow => self.select_handler.move_selection(Movement::To(index)),
+            _ => self.select_handler.move_selection(Movement::Last),
+        }
+        true
+    }
+
+    /// Error, warn, info, debug, trace, then back to error.
+    pub fn cycle_level(&mut self) {
+        self.level = match self.level {
+            LevelFilter::Error => LevelFilter::Warn,
+            LevelFilter::Warn => LevelFilter::Info,
+            LevelFilter::Info => LevelFilter::Debug,
+            LevelFilter::Debug => LevelFilter::Trace,
+            _ => LevelFilter::Error,
+        };
+        self.follow = true;
+        self.generation = None;
+        self.refresh();
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.select_handler.move_selection(movement);
+        let last = self.select_handler.items().len().saturating_sub(1);
+        self.follow = self.select_handler.state().selected() == Some(last);
+    }
+
+    pub fn title(&self) -> String {
+        match self.level {
+            LevelFilter::Error => "Log (errors)".to_string(),
+            level => format!("Log ({} and above)", level.as_str().to_lowercase()),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    lyrics::{LyricLine, Lyrics},
+    utils::selecthandler::{Movement, SelectHandler},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LyricsStatus {
+    NoSong,
+    Loading,
+    Loaded,
+    NotFound,
+}
+
+/// Lyrics of the current song. The selection follows the sung line until the user moves it.
+pub struct LyricsHandler {
+    song_key: Option<String>,
+    lyrics: Option<Lyrics>,
+    status: LyricsStatus,
+    current_line: Option<usize>,
+    follow: bool,
+    pub select_handler: SelectHandler<LyricLine>,
+}
+
+impl LyricsHandler {
+    pub fn new() -> Self {
+        LyricsHandler {
+            song_key: None,
+            lyrics: None,
+            status: LyricsStatus::NoSong,
+            current_line: None,
+            follow: true,
+            select_handler: SelectHandler::new(),
+        }
+    }
+
+    /// Returns false if lyrics for this song are already loaded or loading.
+    pub fn start_loading(&mut self, song_key: &str) -> bool {
+        if self.song_key.as_deref() == Some(song_key) {
+            return false;
+        }
+        self.song_key = Some(song_key.to_string());
+        self.lyrics = None;
+        self.status = LyricsStatus::Loading;
+        self.current_line = None;
+        self.follow = true;
+        self.select_handler.set_items(Vec::new());
+        true
+    }
+
+    /// Ignored if the song changed while the lyrics were loading.
+    pub fn set_lyrics(&mut self, song_key: &str, lyrics: Option<Lyrics>) {
+        if self.song_key.as_deref() != Some(song_key) {
+            return;
+        }
+        match lyrics {
+            Some(lyrics) => {
+                self.select_handler.set_items(lyrics.lines.clone());
+                self.lyrics = Some(lyrics);
+                self.status = LyricsStatus::Loaded;
+            }
+            None => self.status = LyricsStatus::NotFound,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = LyricsHandler::new();
+    }
+
+    pub fn update_position(&mut self, position: u64) {
+        let Some(lyrics) = &self.lyrics else {
+            return;
+        };
+        self.current_line = lyrics.current_line(position);
+        if self.follow && self.current_line.is_some() {
+            self.select_handler.state().select(self.current_line);
+        }
+    }
+
+    pub fn current_line(&self) -> Option<usize> {
+        self.current_line
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.follow = false;
+        self.select_handler.move_selection(movement);
+    }
+
+    /// Start time of the selected line. Following resumes, since playback jumps to that line.
+    pub fn select_handler_select(&mut self) -> Option<u64> {
+        let time = self.select_handler.select().and_then(|line| line.time)?;
+        self.follow = true;
+        Some(time)
+    }
+
+    pub fn title(&self) -> String {
+        match (&self.status, &self.lyrics) {
+            (LyricsStatus::Loaded, Some(lyrics)) if lyrics.is_synced() => {
+                format!("Lyrics ({}, synced)", lyrics.source.label())
+            }
+            (LyricsStatus::Loaded, Some(lyrics)) => format!("Lyrics ({})", lyrics.source.label()),
+            (LyricsStatus::Loading, _) => "Lyrics (loading...)".to_string(),
+            (LyricsStatus::NotFound, _) => "Lyrics (none found)".to_string(),
+            _ => "Lyrics".to_string(),
+        }
+    }
+}
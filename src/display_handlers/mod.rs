@@ -1 +1,8 @@
+pub mod browse_handler;
 pub mod folder_handler;
+pub mod help_handler;
+pub mod history_handler;
+pub mod log_handler;
+pub mod lyrics_handler;
+pub mod notification_handler;
+pub mod playlist_handler;
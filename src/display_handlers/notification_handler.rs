@@ -0,0 +1,58 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const NOTIFICATION_TIME: Duration = Duration::from_secs(6);
+const MAX_NOTIFICATIONS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    Warning,
+    Error,
+}
+
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+    created_at: Instant,
+}
+
+/// Recent errors and warnings, shown in a corner of the screen until they expire.
+pub struct NotificationHandler {
+    notifications: VecDeque<Notification>,
+}
+
+impl NotificationHandler {
+    pub fn new() -> Self {
+        NotificationHandler {
+            notifications: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, level: NotificationLevel, message: impl Into<String>) {
+        let message = message.into();
+        // The same problem again only refreshes the one already shown
+        self.notifications
+            .retain(|notification| notification.message != message);
+        self.notifications.push_back(Notification {
+            level,
+            message,
+            created_at: Instant::now(),
+        });
+        while self.notifications.len() > MAX_NOTIFICATIONS {
+            self.notifications.pop_front();
+        }
+    }
+
+    /// Drops expired notifications and returns the rest, oldest first.
+    pub fn visible(&mut self) -> &VecDeque<Notification> {
+        self.notifications
+            .retain(|notification| notification.created_at.elapsed() < NOTIFICATION_TIME);
+        &self.notifications
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifications.is_empty()
+    }
+}
@@ -0,0 +1,263 @@
+use std::{fs, path::PathBuf};
+
+use ratatui::widgets::ListItem;
+
+use crate::{
+    commands::playlist_dir,
+    playlists::{self, Playlist, check_name, smart_playlist_path},
+    smart_playlists::TEMPLATE,
+    song::Song,
+    utils::selecthandler::{Movement, SelectHandler, SelectHandlerItem},
+};
+
+#[derive(Clone)]
+pub enum PlaylistNode {
+    Playlist(Playlist),
+    Song(Song),
+}
+
+impl SelectHandlerItem for PlaylistNode {
+    fn list_item(&self) -> ListItem<'_> {
+        match self {
+            PlaylistNode::Playlist(playlist) => playlist.list_item(),
+            PlaylistNode::Song(song) => song.list_item(),
+        }
+    }
+}
+
+/// The saved playlists, or the songs of the one that is open.
+pub struct PlaylistHandler {
+    playlists: Vec<Playlist>,
+    /// Name of the open playlist
+    open: Option<String>,
+    pub select_handler: SelectHandler<PlaylistNode>,
+}
+
+impl PlaylistHandler {
+    pub fn new() -> Self {
+        PlaylistHandler {
+            playlists: Vec::new(),
+            open: None,
+            select_handler: SelectHandler::new(),
+        }
+    }
+
+    /// Reads the playlists again and re-evaluates smart playlists, e.g. when the library changed.
+    pub fn load(&mut self, library: &[Song]) {
+        self.playlists = playlists::load_all(library);
+        self.update_items();
+    }
+
+    /// Evaluates the smart playlists again, e.g. after ratings or play counts changed.
+    pub fn refresh_smart(&mut self, library: &[Song]) {
+        for playlist in &mut self.playlists {
+            if let Some(rules) = &playlist.rules {
+                playlist.songs = rules.evaluate(library);
+            }
+        }
+        self.update_items();
+    }
+
+    pub fn songs(&self, name: &str) -> Option<Vec<Song>> {
+        self.find(name).map(|playlist| playlist.songs.clone())
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        check_name(name)?;
+        if self.find(name).is_some() {
+            return Err(format!("Playlist {name} already exists"));
+        }
+        self.save(Playlist {
+            name: name.to_string(),
+            songs: Vec::new(),
+            rules: None,
+        })
+    }
+
+    /// Writes a smart playlist template to edit. It shows up once the playlists are loaded again.
+    pub fn create_smart(&mut self, name: &str) -> Result<PathBuf, String> {
+        check_name(name)?;
+        if self.find(name).is_some() {
+            return Err(format!("Playlist {name} already exists"));
+        }
+        let path = smart_playlist_path(name);
+        fs::create_dir_all(playlist_dir())
+            .and_then(|_| fs::write(&path, TEMPLATE))
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Adds songs to the end of a playlist, creating it if needed.
+    pub fn append(&mut self, name: &str, songs: Vec<Song>) -> Result<(), String> {
+        check_name(name)?;
+        let mut playlist = self.find(name).cloned().unwrap_or(Playlist {
+            name: name.to_string(),
+            songs: Vec::new(),
+            rules: None,
+        });
+        if playlist.rules.is_some() {
+            return Err(format!(
+                "{name} is a smart playlist, edit its rules instead"
+            ));
+        }
+        playlist.songs.extend(songs);
+        self.save(playlist)
+    }
+
+    /// Replaces the songs of a playlist, creating it if needed.
+    pub fn replace(&mut self, name: &str, songs: Vec<Song>) -> Result<(), String> {
+        check_name(name)?;
+        if self
+            .find(name)
+            .is_some_and(|playlist| playlist.rules.is_some())
+        {
+            return Err(format!(
+                "{name} is a smart playlist, edit its rules instead"
+            ));
+        }
+        self.save(Playlist {
+            name: name.to_string(),
+            songs,
+            rules: None,
+        })
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        check_name(new_name)?;
+        if self.find(new_name).is_some() {
+            return Err(format!("Playlist {new_name} already exists"));
+        }
+        let index = self.index(name)?;
+        let playlist = &self.playlists[index];
+        let (from, to) = (playlist.path(), playlist.path_for(new_name));
+        fs::rename(&from, &to)
+            .map_err(|err| format!("Failed to rename {}: {err}", from.display()))?;
+        self.playlists[index].name = new_name.to_string();
+        if self.open.as_deref() == Some(name) {
+            self.open = Some(new_name.to_string());
+        }
+        self.sort();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        let index = self.index(name)?;
+        let path = self.playlists[index].path();
+        fs::remove_file(&path)
+            .map_err(|err| format!("Failed to delete {}: {err}", path.display()))?;
+        self.playlists.remove(index);
+        if self.open.as_deref() == Some(name) {
+            self.open = None;
+        }
+        self.update_items();
+        Ok(())
+    }
+
+    /// Opens the selected playlist, or returns its songs and the selected one's index if a
+    /// playlist is already open.
+    pub fn open_selected(&mut self) -> Option<(Vec<Song>, usize)> {
+        let selected = self.select_handler.select()?.clone();
+        let index = self.select_handler.state().selected()?;
+        match selected {
+            PlaylistNode::Playlist(playlist) => {
+                self.open = Some(playlist.name);
+                self.update_items();
+                self.select_handler.move_selection(Movement::First);
+                None
+            }
+            PlaylistNode::Song(_) => {
+                let songs = self.songs(self.open.as_deref()?)?;
+                Some((songs, index))
+            }
+        }
+    }
+
+    pub fn go_to_parent(&mut self) {
+        let Some(name) = self.open.take() else {
+            return;
+        };
+        self.update_items();
+        if let Ok(index) = self.index(&name) {
+            self.select_handler.move_selection(Movement::To(index));
+        }
+    }
+
+    /// The selected song, or all songs of the selected playlist.
+    pub fn selected_songs(&self) -> Vec<Song> {
+        match self.select_handler.select() {
+            Some(PlaylistNode::Playlist(playlist)) => playlist.songs.clone(),
+            Some(PlaylistNode::Song(song)) => vec![song.clone()],
+            None => Vec::new(),
+        }
+    }
+
+    /// The open playlist, or the selected one in the list of playlists.
+    pub fn selected_name(&self) -> Option<String> {
+        match (&self.open, self.select_handler.select()) {
+            (Some(name), _) => Some(name.clone()),
+            (None, Some(PlaylistNode::Playlist(playlist))) => Some(playlist.name.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        self.select_handler.move_selection(movement);
+    }
+
+    pub fn title(&self) -> String {
+        match &self.open {
+            Some(name) => format!("Playlists > {name}"),
+            None => "Playlists".to_string(),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&Playlist> {
+        self.playlists.iter().find(|playlist| playlist.name == name)
+    }
+
+    fn index(&self, name: &str) -> Result<usize, String> {
+        self.playlists
+            .iter()
+            .position(|playlist| playlist.name == name)
+            .ok_or(format!("No playlist named {name}"))
+    }
+
+    fn save(&mut self, playlist: Playlist) -> Result<(), String> {
+        playlist.save()?;
+        match self.index(&playlist.name) {
+            Ok(index) => self.playlists[index] = playlist,
+            Err(_) => self.playlists.push(playlist),
+        }
+        self.sort();
+        Ok(())
+    }
+
+    fn sort(&mut self) {
+        self.playlists
+            .sort_by_key(|playlist| playlist.name.to_lowercase());
+        self.update_items();
+    }
+
+    /// Rebuilds the list, keeping the selection where it was.
+    fn update_items(&mut self) {
+        let selected = self.select_handler.state().selected();
+        let items = match self.open.as_deref().and_then(|name| self.find(name)) {
+            Some(playlist) => playlist
+                .songs
+                .iter()
+                .cloned()
+                .map(PlaylistNode::Song)
+                .collect(),
+            None => self
+                .playlists
+                .iter()
+                .cloned()
+                .map(PlaylistNode::Playlist)
+                .collect(),
+        };
+        self.select_handler.set_items(items);
+        if let Some(selected) = selected {
+            self.select_handler.move_selection(Movement::To(selected));
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::{io, path::PathBuf};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Failed to start libvlc, playback is unavailable")]
+    VlcUnavailable,
+    #[error("Can't play {title}, skipped")]
+    Unplayable { title: String },
+    #[error("Media controls unavailable: {0}")]
+    MediaControls(String),
+    #[error("The player stopped, restart to play music again")]
+    PlayerStopped,
+    #[error("Logging disabled: {0}")]
+    Logging(String),
+    #[error("MusicBrainz unavailable, online search is off: {0}")]
+    MusicBrainzUnavailable(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("Invalid config {}:\n{message}", path.display())]
+    Parse { path: PathBuf, message: String },
+    #[error("Invalid config {}: {field} {message}", path.display())]
+    Invalid {
+        path: PathBuf,
+        field: String,
+        message: String,
+    },
+    #[error("Invalid {var}: {message}")]
+    Env { var: String, message: String },
+}
@@ -1,115 +1,335 @@
-use std::{sync::mpsc::Sender, thread};
+use std::time::{Duration, Instant};
 
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, KeyEvent};
 
-use crate::events::ApplicationEvent;
+use crate::events::keymap::{KeyCombo, KeyContext, KeySequence, Keymap, SequenceMatch};
 
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+const MAX_COUNT: usize = 9999;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Quit,
     SwitchWindow,
     MoveUp,
     MoveDown,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    MoveToTop,
+    MoveToBottom,
     Select,
-    Space,
+    TogglePause,
     NextSong,
     PreviousSong,
+    FocusSearch,
+    SubmitSearch,
+    LeaveSearch,
+    DeleteChar,
+    ShowSongs,
+    ShowFolders,
+    ShowPlaylists,
+    ShowSearch,
+    ShowLyrics,
+    ShowLog,
+    ShowHistory,
+    ShowStats,
+    CloseStats,
+    CycleStatsPeriod,
+    CycleLogLevel,
+    EnqueueSelected,
+    OpenArtist,
+    Download,
+    AddToPlaylist,
+    NewPlaylist,
+    RenamePlaylist,
+    DeletePlaylist,
+    RateUp,
+    RateDown,
+    ToggleFavorite,
+    GoToParent,
+    SeekToLine,
+    ToggleRemainingTime,
+    ShowHelp,
+    CloseHelp,
+    OpenCommandLine,
+    RunCommand,
+    CloseCommandLine,
+    CompleteCommand,
+    PreviousCommand,
+    NextCommand,
     Char(char),
-    Backspace,
-    Esc,
 }
 
+/// Names used for actions in the config file. `Char` is text input and can't be bound.
+const ACTION_NAMES: [(&str, Action); 50] = [
+    ("quit", Action::Quit),
+    ("switch-window", Action::SwitchWindow),
+    ("move-up", Action::MoveUp),
+    ("move-down", Action::MoveDown),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("half-page-up", Action::HalfPageUp),
+    ("half-page-down", Action::HalfPageDown),
+    ("move-to-top", Action::MoveToTop),
+    ("move-to-bottom", Action::MoveToBottom),
+    ("select", Action::Select),
+    ("toggle-pause", Action::TogglePause),
+    ("next-song", Action::NextSong),
+    ("previous-song", Action::PreviousSong),
+    ("focus-search", Action::FocusSearch),
+    ("submit-search", Action::SubmitSearch),
+    ("leave-search", Action::LeaveSearch),
+    ("delete-char", Action::DeleteChar),
+    ("show-songs", Action::ShowSongs),
+    ("show-folders", Action::ShowFolders),
+    ("show-playlists", Action::ShowPlaylists),
+    ("show-search", Action::ShowSearch),
+    ("show-lyrics", Action::ShowLyrics),
+    ("show-log", Action::ShowLog),
+    ("show-history", Action::ShowHistory),
+    ("show-stats", Action::ShowStats),
+    ("close-stats", Action::CloseStats),
+    ("cycle-stats-period", Action::CycleStatsPeriod),
+    ("cycle-log-level", Action::CycleLogLevel),
+    ("enqueue-selected", Action::EnqueueSelected),
+    ("open-artist", Action::OpenArtist),
+    ("download", Action::Download),
+    ("add-to-playlist", Action::AddToPlaylist),
+    ("new-playlist", Action::NewPlaylist),
+    ("rename-playlist", Action::RenamePlaylist),
+    ("delete-playlist", Action::DeletePlaylist),
+    ("rate-up", Action::RateUp),
+    ("rate-down", Action::RateDown),
+    ("toggle-favorite", Action::ToggleFavorite),
+    ("go-to-parent", Action::GoToParent),
+    ("seek-to-line", Action::SeekToLine),
+    ("toggle-remaining-time", Action::ToggleRemainingTime),
+    ("show-help", Action::ShowHelp),
+    ("close-help", Action::CloseHelp),
+    ("command-line", Action::OpenCommandLine),
+    ("run-command", Action::RunCommand),
+    ("close-command-line", Action::CloseCommandLine),
+    ("complete-command", Action::CompleteCommand),
+    ("previous-command", Action::PreviousCommand),
+    ("next-command", Action::NextCommand),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTION_NAMES
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTION_NAMES
+            .iter()
+            .find(|(_, action)| action == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("char")
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::SwitchWindow => "switch pane",
+            Action::MoveUp => "up",
+            Action::MoveDown => "down",
+            Action::PageUp => "page up",
+            Action::PageDown => "page down",
+            Action::HalfPageUp => "half page up",
+            Action::HalfPageDown => "half page down",
+            Action::MoveToTop => "top",
+            Action::MoveToBottom => "bottom",
+            Action::Select => "play",
+            Action::TogglePause => "play/pause",
+            Action::NextSong => "next",
+            Action::PreviousSong => "previous",
+            Action::FocusSearch => "search",
+            Action::SubmitSearch => "search",
+            Action::LeaveSearch => "cancel",
+            Action::DeleteChar => "delete",
+            Action::ShowSongs => "songs",
+            Action::ShowFolders => "folders",
+            Action::ShowPlaylists => "playlists",
+            Action::ShowSearch => "results",
+            Action::ShowLyrics => "lyrics",
+            Action::ShowLog => "log",
+            Action::ShowHistory => "history",
+            Action::ShowStats => "stats",
+            Action::CloseStats => "close",
+            Action::CycleStatsPeriod => "period",
+            Action::CycleLogLevel => "log level",
+            Action::EnqueueSelected => "enqueue",
+            Action::OpenArtist => "artist",
+            Action::Download => "download",
+            Action::AddToPlaylist => "add to playlist",
+            Action::NewPlaylist => "new playlist",
+            Action::RenamePlaylist => "rename",
+            Action::DeletePlaylist => "delete",
+            Action::RateUp => "rate up",
+            Action::RateDown => "rate down",
+            Action::ToggleFavorite => "favorite",
+            Action::GoToParent => "back",
+            Action::SeekToLine => "seek to line",
+            Action::ToggleRemainingTime => "remaining/total",
+            Action::ShowHelp => "help",
+            Action::CloseHelp => "close",
+            Action::OpenCommandLine => "command",
+            Action::RunCommand => "run",
+            Action::CloseCommandLine => "cancel",
+            Action::CompleteCommand => "complete",
+            Action::PreviousCommand => "older",
+            Action::NextCommand => "newer",
+            Action::Char(_) => "type",
+        }
+    }
+}
+
+/// An action with the count typed before it, like the 5 in `5j`.
+#[derive(Clone, Copy)]
+pub struct KeyPress {
+    pub action: Action,
+    pub count: Option<usize>,
+}
+
+impl KeyPress {
+    pub fn repeat(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+/// Turns key presses into actions using the keymap and the contexts of the focused pane.
+/// Keys that start a longer binding are held until the sequence completes or times out.
 pub struct KeyboardHandler {
-    event_tx: Sender<ApplicationEvent>,
+    keymap: Keymap,
+    pending: Vec<KeyCombo>,
+    count: Option<usize>,
+    last_key_at: Instant,
 }
 
 impl KeyboardHandler {
-    pub fn new(event_tx: Sender<ApplicationEvent>) {
-        tokio::spawn(async move {
-            KeyboardHandler { event_tx }.run().await;
-        });
+    pub fn new(keymap: Keymap) -> Self {
+        KeyboardHandler {
+            keymap,
+            pending: Vec::new(),
+            count: None,
+            last_key_at: Instant::now(),
+        }
     }
 
-    async fn run(&self) {
-        loop {
-            match crossterm::event::read().unwrap() {
-                crossterm::event::Event::Key(key_event) => {
-                    if key_event.modifiers.is_empty() {
-                        self.handle(key_event);
-                    } else {
-                        self.handle_with_modifier(key_event);
-                    }
-                }
-                _ => {}
-            }
+    pub fn resolve(&mut self, key_event: KeyEvent, contexts: &[KeyContext]) -> Option<KeyPress> {
+        self.resolve_key(KeyCombo::from(key_event), contexts)
+    }
+
+    /// Fires a held binding once no longer binding can follow in time. Called on every tick.
+    pub fn timeout(&mut self, contexts: &[KeyContext]) -> Option<KeyPress> {
+        if self.pending.is_empty() || self.last_key_at.elapsed() < SEQUENCE_TIMEOUT {
+            return None;
         }
+        let action = match self.keymap.lookup(contexts, &self.pending) {
+            SequenceMatch::ExactAndPrefix(action) => Some(action),
+            _ => None,
+        };
+        let count = self.count;
+        self.reset();
+        action.map(|action| KeyPress { action, count })
     }
 
-    fn handle_with_modifier(&self, key_event: crossterm::event::KeyEvent) {
-        if key_event
-            .modifiers
-            .contains(crossterm::event::KeyModifiers::CONTROL)
+    fn resolve_key(&mut self, key: KeyCombo, contexts: &[KeyContext]) -> Option<KeyPress> {
+        self.last_key_at = Instant::now();
+
+        // While typing, keys map one to one and there are no counts
+        if contexts
+            .first()
+            .is_some_and(|context| context.accepts_text())
         {
-            if let crossterm::event::KeyCode::Char('c') = key_event.code {
-                let _ = self.event_tx.send(ApplicationEvent::Action(Action::Quit));
-            }
+            self.reset();
+            // Plain characters only fall back to other contexts when the text one binds them
+            let lookup_contexts = match key.code {
+                KeyCode::Char(_) if key.modifiers.is_empty() => &contexts[..1],
+                _ => contexts,
+            };
+            return match self.keymap.lookup(lookup_contexts, &[key]) {
+                SequenceMatch::Exact(action) | SequenceMatch::ExactAndPrefix(action) => {
+                    Some(KeyPress {
+                        action,
+                        count: None,
+                    })
+                }
+                _ => match key.code {
+                    KeyCode::Char(char) if key.modifiers.is_empty() => Some(KeyPress {
+                        action: Action::Char(char),
+                        count: None,
+                    }),
+                    _ => None,
+                },
+            };
+        }
+
+        if key.code == KeyCode::Esc && (self.count.is_some() || !self.pending.is_empty()) {
+            self.reset();
+            return None;
         }
-        if key_event
-            .modifiers
-            .contains(crossterm::event::KeyModifiers::SHIFT)
+
+        // Digits build a count unless they're bound, a started count always takes them
+        if self.pending.is_empty()
+            && key.modifiers.is_empty()
+            && let KeyCode::Char(digit @ '0'..='9') = key.code
+            && (self.count.is_some()
+                || (digit != '0'
+                    && matches!(self.keymap.lookup(contexts, &[key]), SequenceMatch::None)))
         {
-            if let crossterm::event::KeyCode::Char(c) = key_event.code {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::Char(c)));
-            }
+            let digit = digit.to_digit(10).unwrap_or(0) as usize;
+            self.count = Some((self.count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+            return None;
         }
-    }
 
-    fn handle(&self, key_event: crossterm::event::KeyEvent) {
-        match key_event.code {
-            KeyCode::Up | KeyCode::PageUp => {
-                let _ = self.event_tx.send(ApplicationEvent::Action(Action::MoveUp));
-            }
-            KeyCode::Down | KeyCode::PageDown => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::MoveDown));
-            }
-            KeyCode::Enter => {
-                let _ = self.event_tx.send(ApplicationEvent::Action(Action::Select));
-            }
-            KeyCode::Char(' ') => {
-                let _ = self.event_tx.send(ApplicationEvent::Action(Action::Space));
-            }
-            KeyCode::Left => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::PreviousSong));
-            }
-            KeyCode::Right => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::NextSong));
+        self.pending.push(key);
+        match self.keymap.lookup(contexts, &self.pending) {
+            SequenceMatch::Exact(action) => {
+                let count = self.count;
+                self.reset();
+                Some(KeyPress { action, count })
             }
-            KeyCode::Tab => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::SwitchWindow));
+            SequenceMatch::Prefix | SequenceMatch::ExactAndPrefix(_) => None,
+            SequenceMatch::None if self.pending.len() > 1 => {
+                // The sequence broke off, the last key may still start something on its own
+                self.pending.clear();
+                self.resolve_key(key, contexts)
             }
-            KeyCode::Char(char) => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::Char(char)));
+            SequenceMatch::None => {
+                self.reset();
+                None
             }
-            KeyCode::Backspace => {
-                let _ = self
-                    .event_tx
-                    .send(ApplicationEvent::Action(Action::Backspace));
-            }
-            KeyCode::Esc => {
-                let _ = self.event_tx.send(ApplicationEvent::Action(Action::Esc));
-            }
-            _ => {}
         }
     }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.count = None;
+    }
+
+    /// The count and keys typed so far, shown while a sequence is incomplete.
+    pub fn pending_keys(&self) -> Option<String> {
+        if self.pending.is_empty() && self.count.is_none() {
+            return None;
+        }
+        let count = self
+            .count
+            .map(|count| count.to_string())
+            .unwrap_or_default();
+        Some(format!("{count}{}", KeySequence(self.pending.clone())))
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub fn hints(&self, contexts: &[KeyContext]) -> Vec<(KeySequence, Action)> {
+        self.keymap.hints(contexts)
+    }
 }
@@ -0,0 +1,653 @@
+use std::{collections::HashMap, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::events::keyboard::Action;
+
+/// Where a binding applies. Resolution tries the most specific context first.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Global,
+    Media,
+    Queue,
+    Search,
+    Songs,
+    Folders,
+    Playlists,
+    Browse,
+    Lyrics,
+    Log,
+    History,
+    Stats,
+    Help,
+    Command,
+}
+
+impl KeyContext {
+    pub const ALL: [KeyContext; 14] = [
+        KeyContext::Global,
+        KeyContext::Media,
+        KeyContext::Songs,
+        KeyContext::Folders,
+        KeyContext::Playlists,
+        KeyContext::Browse,
+        KeyContext::Lyrics,
+        KeyContext::Log,
+        KeyContext::History,
+        KeyContext::Queue,
+        KeyContext::Search,
+        KeyContext::Stats,
+        KeyContext::Help,
+        KeyContext::Command,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyContext::Global => "Global",
+            KeyContext::Media => "Media",
+            KeyContext::Queue => "Queue",
+            KeyContext::Search => "Search input",
+            KeyContext::Songs => "Songs",
+            KeyContext::Folders => "Folders",
+            KeyContext::Playlists => "Playlists",
+            KeyContext::Browse => "Search results",
+            KeyContext::Lyrics => "Lyrics",
+            KeyContext::Log => "Log",
+            KeyContext::History => "History",
+            KeyContext::Stats => "Stats",
+            KeyContext::Help => "Help",
+            KeyContext::Command => "Command line",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyContext::Global => "global",
+            KeyContext::Media => "media",
+            KeyContext::Queue => "queue",
+            KeyContext::Search => "search",
+            KeyContext::Songs => "songs",
+            KeyContext::Folders => "folders",
+            KeyContext::Playlists => "playlists",
+            KeyContext::Browse => "browse",
+            KeyContext::Lyrics => "lyrics",
+            KeyContext::Log => "log",
+            KeyContext::History => "history",
+            KeyContext::Stats => "stats",
+            KeyContext::Help => "help",
+            KeyContext::Command => "command",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyContext> {
+        KeyContext::ALL
+            .into_iter()
+            .find(|context| context.name() == name)
+    }
+
+    /// Contexts this one falls back to when a key isn't bound in it.
+    pub fn parents(&self) -> &'static [KeyContext] {
+        match self {
+            KeyContext::Global => &[],
+            KeyContext::Songs
+            | KeyContext::Folders
+            | KeyContext::Playlists
+            | KeyContext::Browse
+            | KeyContext::Lyrics
+            | KeyContext::Log
+            | KeyContext::History => &[KeyContext::Media, KeyContext::Global],
+            _ => &[KeyContext::Global],
+        }
+    }
+
+    /// Unbound characters typed in these contexts are text input.
+    pub fn accepts_text(&self) -> bool {
+        matches!(
+            self,
+            KeyContext::Search | KeyContext::Help | KeyContext::Command
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyCombo {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        KeyCombo { code, modifiers }
+    }
+
+    pub fn plain(code: KeyCode) -> Self {
+        KeyCombo::new(code, KeyModifiers::NONE)
+    }
+
+    pub fn char(char: char) -> Self {
+        KeyCombo::plain(KeyCode::Char(char))
+    }
+
+    pub fn ctrl(char: char) -> Self {
+        KeyCombo::new(KeyCode::Char(char), KeyModifiers::CONTROL)
+    }
+
+    /// Parses chords like `ctrl+c`, `shift+tab`, `enter` or `G`.
+    pub fn parse(chord: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = chord.split('+').collect();
+        // "+" itself and chords ending in it, like "ctrl++"
+        if chord.ends_with("++") || chord == "+" {
+            parts.truncate(parts.len().saturating_sub(2));
+            parts.push("+");
+        }
+        let (key, modifier_names) = parts.split_last()?;
+        for modifier in modifier_names {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" | "c" => modifiers |= KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => modifiers |= KeyModifiers::ALT,
+                "shift" | "s" => modifiers |= KeyModifiers::SHIFT,
+                _ => return None,
+            }
+        }
+        let code = match key.to_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" | "bksp" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            lower => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => KeyCode::Char(char),
+                    _ => KeyCode::F(lower.strip_prefix('f')?.parse().ok()?),
+                }
+            }
+        };
+        // Same normalization as key events, so "shift+g" and "G" are one chord
+        if let KeyCode::Char(char) = code
+            && modifiers.contains(KeyModifiers::SHIFT)
+        {
+            modifiers.remove(KeyModifiers::SHIFT);
+            return Some(KeyCombo::new(
+                KeyCode::Char(char.to_ascii_uppercase()),
+                modifiers,
+            ));
+        }
+        Some(KeyCombo::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for KeyCombo {
+    // Shift is already part of the character, 'A' arrives as Shift+'A'
+    fn from(key_event: KeyEvent) -> Self {
+        let mut modifiers = key_event.modifiers;
+        if let KeyCode::Char(_) = key_event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        KeyCombo::new(key_event.code, modifiers)
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(char) => write!(f, "{char}"),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Backspace => write!(f, "Bksp"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// One or more chords pressed one after another, like `g g`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<KeyCombo>);
+
+impl KeySequence {
+    /// Chords are separated by spaces. A run of plain characters like `gg` is split into
+    /// one chord per character.
+    pub fn parse(sequence: &str) -> Option<Self> {
+        let mut keys = Vec::new();
+        for part in sequence.split_whitespace() {
+            match KeyCombo::parse(part) {
+                Some(key) => keys.push(key),
+                None if !part.contains('+') => keys.extend(part.chars().map(KeyCombo::char)),
+                None => return None,
+            }
+        }
+        (!keys.is_empty()).then_some(KeySequence(keys))
+    }
+}
+
+impl From<KeyCombo> for KeySequence {
+    fn from(key: KeyCombo) -> Self {
+        KeySequence(vec![key])
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain_chars = self.0.iter().all(|key| {
+            key.modifiers.is_empty() && matches!(key.code, KeyCode::Char(char) if char != ' ')
+        });
+        let separator = if plain_chars { "" } else { " " };
+        let keys: Vec<String> = self.0.iter().map(|key| key.to_string()).collect();
+        write!(f, "{}", keys.join(separator))
+    }
+}
+
+pub enum SequenceMatch {
+    None,
+    /// Longer sequences start with these keys
+    Prefix,
+    Exact(Action),
+    /// Bound, but longer sequences start with the same keys
+    ExactAndPrefix(Action),
+}
+
+pub struct KeyBinding {
+    pub context: KeyContext,
+    pub keys: KeySequence,
+    pub action: Action,
+}
+
+pub struct Keymap {
+    bindings: Vec<KeyBinding>,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        Keymap {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Replaces an existing binding for the same keys in the same context.
+    pub fn bind(&mut self, context: KeyContext, keys: impl Into<KeySequence>, action: Action) {
+        let keys = keys.into();
+        self.unbind(context, keys.clone());
+        self.bindings.push(KeyBinding {
+            context,
+            keys,
+            action,
+        });
+    }
+
+    pub fn unbind(&mut self, context: KeyContext, keys: impl Into<KeySequence>) {
+        let keys = keys.into();
+        self.bindings
+            .retain(|binding| !(binding.context == context && binding.keys == keys));
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Keymap::default()),
+            "vim" => Some(Keymap::vim()),
+            _ => None,
+        }
+    }
+
+    /// The default bindings plus hl and a few other vim habits. Views move from the number keys
+    /// to `g` sequences so that every digit starts a count.
+    pub fn vim() -> Self {
+        let mut keymap = Keymap::default();
+        for char in ['1', '2', '3', '4', '5', '6', '7'] {
+            keymap.unbind(KeyContext::Media, KeyCombo::char(char));
+        }
+        let sequences = [
+            ("gs", Action::ShowSongs),
+            ("gf", Action::ShowFolders),
+            ("gp", Action::ShowPlaylists),
+            ("gr", Action::ShowSearch),
+            ("gl", Action::ShowLyrics),
+            ("gL", Action::ShowLog),
+            ("gh", Action::ShowHistory),
+        ];
+        for (keys, action) in sequences {
+            let keys = KeySequence::parse(keys).expect("Valid key sequence");
+            keymap.bind(KeyContext::Media, keys, action);
+        }
+        let bindings = [
+            (KeyContext::Global, KeyCombo::char('/'), Action::FocusSearch),
+            (KeyContext::Global, KeyCombo::char('q'), Action::Quit),
+            (KeyContext::Global, KeyCombo::char('n'), Action::NextSong),
+            (
+                KeyContext::Global,
+                KeyCombo::char('p'),
+                Action::PreviousSong,
+            ),
+            (KeyContext::Media, KeyCombo::char('l'), Action::Select),
+            (KeyContext::Queue, KeyCombo::char('l'), Action::Select),
+            (KeyContext::Folders, KeyCombo::char('h'), Action::GoToParent),
+            (KeyContext::Browse, KeyCombo::char('h'), Action::GoToParent),
+            (
+                KeyContext::Playlists,
+                KeyCombo::char('h'),
+                Action::GoToParent,
+            ),
+            (KeyContext::Lyrics, KeyCombo::char('l'), Action::SeekToLine),
+        ];
+        for (context, key, action) in bindings {
+            keymap.bind(context, key, action);
+        }
+        keymap
+    }
+
+    pub fn bindings(&self) -> &[KeyBinding] {
+        &self.bindings
+    }
+
+    /// Matches keys pressed so far. The first context with any match decides.
+    pub fn lookup(&self, contexts: &[KeyContext], keys: &[KeyCombo]) -> SequenceMatch {
+        for context in contexts {
+            let mut exact = None;
+            let mut prefix = false;
+            for binding in self
+                .bindings
+                .iter()
+                .filter(|binding| binding.context == *context)
+            {
+                if binding.keys.0 == keys {
+                    exact = Some(binding.action);
+                } else if binding.keys.0.starts_with(keys) {
+                    prefix = true;
+                }
+            }
+            match (exact, prefix) {
+                (Some(action), true) => return SequenceMatch::ExactAndPrefix(action),
+                (Some(action), false) => return SequenceMatch::Exact(action),
+                (None, true) => return SequenceMatch::Prefix,
+                (None, false) => {}
+            }
+        }
+        SequenceMatch::None
+    }
+
+    /// One binding per action, most specific context first. Keys shadowed by a more specific
+    /// context are left out.
+    pub fn hints(&self, contexts: &[KeyContext]) -> Vec<(KeySequence, Action)> {
+        let mut hints: Vec<(KeySequence, Action)> = Vec::new();
+        for context in contexts {
+            for binding in self
+                .bindings
+                .iter()
+                .filter(|binding| binding.context == *context)
+            {
+                let shadowed = hints.iter().any(|(keys, _)| *keys == binding.keys);
+                let duplicate = hints.iter().any(|(_, action)| *action == binding.action);
+                if !shadowed && !duplicate {
+                    hints.push((binding.keys.clone(), binding.action));
+                }
+            }
+        }
+        hints
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap::new();
+        let global = [
+            (KeyCombo::ctrl('c'), Action::Quit),
+            (KeyCombo::char(' '), Action::TogglePause),
+            (KeyCombo::plain(KeyCode::Left), Action::PreviousSong),
+            (KeyCombo::plain(KeyCode::Right), Action::NextSong),
+            (KeyCombo::plain(KeyCode::Up), Action::MoveUp),
+            (KeyCombo::char('k'), Action::MoveUp),
+            (KeyCombo::plain(KeyCode::Down), Action::MoveDown),
+            (KeyCombo::char('j'), Action::MoveDown),
+            (KeyCombo::plain(KeyCode::PageUp), Action::PageUp),
+            (KeyCombo::plain(KeyCode::PageDown), Action::PageDown),
+            (KeyCombo::ctrl('u'), Action::HalfPageUp),
+            (KeyCombo::ctrl('d'), Action::HalfPageDown),
+            (KeyCombo::plain(KeyCode::Home), Action::MoveToTop),
+            (KeyCombo::plain(KeyCode::End), Action::MoveToBottom),
+            (KeyCombo::char('G'), Action::MoveToBottom),
+            (KeyCombo::plain(KeyCode::Tab), Action::SwitchWindow),
+            (KeyCombo::char('f'), Action::FocusSearch),
+            (KeyCombo::char('t'), Action::ToggleRemainingTime),
+            (KeyCombo::char('?'), Action::ShowHelp),
+            (KeyCombo::char(':'), Action::OpenCommandLine),
+        ];
+        let media = [
+            (KeyCombo::plain(KeyCode::Enter), Action::Select),
+            (KeyCombo::char('1'), Action::ShowSongs),
+            (KeyCombo::char('2'), Action::ShowFolders),
+            (KeyCombo::char('3'), Action::ShowSearch),
+            (KeyCombo::char('4'), Action::ShowLyrics),
+            (KeyCombo::char('5'), Action::ShowLog),
+            (KeyCombo::char('6'), Action::ShowPlaylists),
+            (KeyCombo::char('7'), Action::ShowHistory),
+            (KeyCombo::char('S'), Action::ShowStats),
+            (KeyCombo::char('P'), Action::AddToPlaylist),
+            (KeyCombo::char('+'), Action::RateUp),
+            (KeyCombo::char('-'), Action::RateDown),
+            (KeyCombo::char('*'), Action::ToggleFavorite),
+        ];
+        let queue = [
+            (KeyCombo::plain(KeyCode::Enter), Action::Select),
+            (KeyCombo::char('P'), Action::AddToPlaylist),
+            (KeyCombo::char('+'), Action::RateUp),
+            (KeyCombo::char('-'), Action::RateDown),
+            (KeyCombo::char('*'), Action::ToggleFavorite),
+        ];
+        let search = [
+            (KeyCombo::plain(KeyCode::Enter), Action::SubmitSearch),
+            (KeyCombo::plain(KeyCode::Backspace), Action::DeleteChar),
+            (KeyCombo::plain(KeyCode::Esc), Action::LeaveSearch),
+        ];
+        let folders = [
+            (KeyCombo::char('a'), Action::EnqueueSelected),
+            (KeyCombo::plain(KeyCode::Backspace), Action::GoToParent),
+        ];
+        let playlists = [
+            (KeyCombo::char('a'), Action::EnqueueSelected),
+            (KeyCombo::char('n'), Action::NewPlaylist),
+            (KeyCombo::char('r'), Action::RenamePlaylist),
+            (KeyCombo::char('D'), Action::DeletePlaylist),
+            (KeyCombo::plain(KeyCode::Backspace), Action::GoToParent),
+        ];
+        let browse = [
+            (KeyCombo::char('a'), Action::EnqueueSelected),
+            (KeyCombo::char('o'), Action::OpenArtist),
+            (KeyCombo::char('d'), Action::Download),
+            (KeyCombo::plain(KeyCode::Backspace), Action::GoToParent),
+        ];
+        let lyrics = [(KeyCombo::plain(KeyCode::Enter), Action::SeekToLine)];
+        let log = [(KeyCombo::char('v'), Action::CycleLogLevel)];
+        let history = [(KeyCombo::char('a'), Action::EnqueueSelected)];
+        let stats = [
+            (KeyCombo::plain(KeyCode::Esc), Action::CloseStats),
+            (KeyCombo::char('S'), Action::CloseStats),
+            (KeyCombo::plain(KeyCode::Tab), Action::CycleStatsPeriod),
+            (KeyCombo::char('p'), Action::CycleStatsPeriod),
+        ];
+        let help = [
+            (KeyCombo::plain(KeyCode::Esc), Action::CloseHelp),
+            (KeyCombo::char('?'), Action::CloseHelp),
+            (KeyCombo::plain(KeyCode::Up), Action::MoveUp),
+            (KeyCombo::plain(KeyCode::Down), Action::MoveDown),
+            (KeyCombo::plain(KeyCode::PageUp), Action::PageUp),
+            (KeyCombo::plain(KeyCode::PageDown), Action::PageDown),
+            (KeyCombo::plain(KeyCode::Backspace), Action::DeleteChar),
+        ];
+        let command = [
+            (KeyCombo::plain(KeyCode::Enter), Action::RunCommand),
+            (KeyCombo::plain(KeyCode::Esc), Action::CloseCommandLine),
+            (KeyCombo::plain(KeyCode::Tab), Action::CompleteCommand),
+            (KeyCombo::plain(KeyCode::Up), Action::PreviousCommand),
+            (KeyCombo::plain(KeyCode::Down), Action::NextCommand),
+            (KeyCombo::plain(KeyCode::Backspace), Action::DeleteChar),
+        ];
+
+        for (context, bindings) in [
+            (KeyContext::Global, &global[..]),
+            (KeyContext::Media, &media[..]),
+            (KeyContext::Queue, &queue[..]),
+            (KeyContext::Search, &search[..]),
+            (KeyContext::Folders, &folders[..]),
+            (KeyContext::Playlists, &playlists[..]),
+            (KeyContext::Browse, &browse[..]),
+            (KeyContext::Lyrics, &lyrics[..]),
+            (KeyContext::Log, &log[..]),
+            (KeyContext::History, &history[..]),
+            (KeyContext::Stats, &stats[..]),
+            (KeyContext::Help, &help[..]),
+            (KeyContext::Command, &command[..]),
+        ] {
+            for (key, action) in bindings {
+                keymap.bind(context, *key, *action);
+            }
+        }
+        keymap.bind(
+            KeyContext::Global,
+            KeySequence(vec![KeyCombo::char('g'), KeyCombo::char('g')]),
+            Action::MoveToTop,
+        );
+        keymap
+    }
+}
+
+/// The `[keymap]` section of the config file.
+#[derive(Deserialize, Default)]
+pub struct KeymapConfig {
+    preset: Option<String>,
+    /// Context name to chord to action name
+    #[serde(flatten)]
+    contexts: HashMap<String, HashMap<String, String>>,
+}
+
+impl KeymapConfig {
+    /// The preset with the user's bindings on top, and problems worth showing to the user,
+    /// like unknown actions or conflicting chords.
+    pub fn build(&self) -> (Keymap, Vec<String>) {
+        let mut problems = Vec::new();
+        let preset = self.preset.as_deref().unwrap_or("default");
+        let mut keymap = Keymap::preset(preset).unwrap_or_else(|| {
+            problems.push(format!("Unknown keymap preset {preset:?}, using default"));
+            Keymap::default()
+        });
+
+        let mut user_bindings: Vec<(KeyContext, KeySequence, String, Option<Action>)> = Vec::new();
+        let mut contexts: Vec<(&String, &HashMap<String, String>)> = self.contexts.iter().collect();
+        contexts.sort_by(|a, b| a.0.cmp(b.0));
+        for (context_name, bindings) in contexts {
+            let Some(context) = KeyContext::from_name(context_name) else {
+                problems.push(format!("Unknown keymap context [keymap.{context_name}]"));
+                continue;
+            };
+            let mut bindings: Vec<(String, String)> = bindings
+                .iter()
+                .map(|(chord, action_name)| (chord.clone(), action_name.clone()))
+                .collect();
+            bindings.sort();
+            for (chord, action_name) in bindings {
+                let Some(keys) = KeySequence::parse(&chord) else {
+                    problems.push(format!("Invalid key {chord:?} in [keymap.{context_name}]"));
+                    continue;
+                };
+                let action = match action_name.as_str() {
+                    "none" => None,
+                    name => match Action::from_name(name) {
+                        Some(action) => Some(action),
+                        None => {
+                            problems.push(format!(
+                                "Unknown action {action_name:?} for {chord:?} in [keymap.{context_name}]"
+                            ));
+                            continue;
+                        }
+                    },
+                };
+                if let Some((_, _, other_chord, _)) =
+                    user_bindings
+                        .iter()
+                        .find(|(other_context, other_keys, _, _)| {
+                            *other_context == context && *other_keys == keys
+                        })
+                {
+                    problems.push(format!(
+                        "{chord:?} and {other_chord:?} are the same key in [keymap.{context_name}]"
+                    ));
+                    continue;
+                }
+                if context.accepts_text()
+                    && let KeyCode::Char(_) = keys.0[0].code
+                    && keys.0[0].modifiers.is_empty()
+                {
+                    problems.push(format!(
+                        "{chord:?} in [keymap.{context_name}] can no longer be typed"
+                    ));
+                }
+                user_bindings.push((context, keys, chord, action));
+            }
+        }
+
+        for (context, keys, _, action) in &user_bindings {
+            match action {
+                Some(action) => keymap.bind(*context, keys.clone(), *action),
+                None => keymap.unbind(*context, keys.clone()),
+            }
+        }
+
+        for (context, keys, chord, action) in &user_bindings {
+            let Some(action) = action else {
+                continue;
+            };
+            for binding in keymap.bindings() {
+                // A key that starts a longer sequence only fires after the sequence timeout
+                if binding.context == *context
+                    && binding.keys.0.len() > keys.0.len()
+                    && binding.keys.0.starts_with(&keys.0)
+                {
+                    problems.push(format!(
+                        "{chord:?} ({}) in [keymap.{}] waits for a timeout because {} starts with it",
+                        action.name(),
+                        context.name(),
+                        binding.keys
+                    ));
+                }
+                // A binding is hidden wherever a more specific context binds the same keys
+                if binding.keys == *keys
+                    && binding.action != *action
+                    && binding.context.parents().contains(context)
+                {
+                    problems.push(format!(
+                        "{chord:?} ({}) in [keymap.{}] is overridden by {} in [keymap.{}]",
+                        action.name(),
+                        context.name(),
+                        binding.action.name(),
+                        binding.context.name()
+                    ));
+                }
+            }
+        }
+        (keymap, problems)
+    }
+}
@@ -1,14 +1,25 @@
 use crate::{
-    events::{keyboard::Action, musicplayer::PlayerSendEvent},
+    error::AppError,
+    events::musicplayer::PlayerSendEvent,
+    ipc::{IpcReply, Request},
+    playlist_file::PlaylistFile,
     song::Song,
 };
 
 pub mod keyboard;
+pub mod keymap;
 pub mod musicplayer;
 
 pub enum ApplicationEvent {
-    Action(Action),
     PlayerEvent(PlayerSendEvent),
+    /// Songs and playlist files found by the library scan
+    LibraryScanned(Vec<Song>, Vec<PlaylistFile>),
+    /// Shared state changed on a background task
+    Redraw,
+    /// A background task failed, shown as a notification
+    Error(AppError),
+    /// From a connection on the IPC socket, answered through the reply
+    IpcRequest(Request, IpcReply),
 }
 
 pub fn format_ms_to_duration_string(ms: u64) -> String {
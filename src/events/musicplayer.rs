@@ -1,19 +1,28 @@
 use std::{
+    path::PathBuf,
     sync::mpsc::{Receiver, Sender, channel},
     thread,
-    time::Duration,
 };
 
+use log::{error, warn};
+use rand::seq::SliceRandom;
 use souvlaki::{
     MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
 };
+use tokio::sync::mpsc::UnboundedSender;
 use vlc::{Event, EventType, Instance, Media, MediaPlayer, MediaPlayerAudioEx, State};
 
 use crate::{
+    artwork,
+    config::Config,
+    error::AppError,
     events::ApplicationEvent,
     song::{Song, SongType},
 };
 
+/// Longer steps between time updates are seeks, not listening
+const MAX_TIME_STEP: u64 = 5000;
+
 #[derive(Default)]
 pub enum PlayerStatus {
     Playing(Song),
@@ -27,12 +36,21 @@ pub enum PlayerReceiveEvent {
     SetAndPlaySong(usize),
     CreateQueueAndPlay(Vec<Song>),
     AddSongsToQueueAndPlay(Vec<Song>),
+    /// Puts a song before the start of the queue and plays it
+    PrependAndPlay(Box<Song>),
     Play,
     Next,
     Previous,
     Pause,
     TogglePause,
     Update,
+    SetCoverArt {
+        release: String,
+        path: PathBuf,
+    },
+    Seek(u64),
+    SetVolume(i32),
+    SetShuffle(bool),
 }
 
 pub enum PlayerSendEvent {
@@ -41,13 +59,21 @@ pub enum PlayerSendEvent {
     TimeChanged(u64),
     Pause(usize),
     Play(usize),
-    PlayerInformation(PlayerInformation),
+    PlayerInformation(Box<PlayerInformation>),
     QueueUpdate(Vec<Song>),
+    /// A song stopped playing after `listened` ms of its `length`
+    Listened {
+        song: Box<Song>,
+        listened: u64,
+        length: u64,
+    },
+    Error(AppError),
 }
 
 enum PlayerBackendEvent {
     VLCEvent(Event),
     MediaControls(MediaControlEvent),
+    App(PlayerReceiveEvent),
 }
 
 #[derive(Default)]
@@ -57,22 +83,50 @@ pub struct PlayerInformation {
     pub passed_time: u64,
     pub status: PlayerStatus,
     pub volume: i32,
+    pub shuffle: bool,
 }
 
 pub struct Player {
     queue: Vec<Song>,
     playing_index: Option<usize>,
+    shuffle: bool,
+    /// Time heard of the current song, seeks don't count
+    listened: u64,
+    last_time: Option<u64>,
     vlc_instance: Instance,
     media_player: MediaPlayer,
-    event_tx: Sender<ApplicationEvent>,
-    player_rx: Receiver<PlayerReceiveEvent>,
-    media_controls: MediaControls,
+    event_tx: UnboundedSender<ApplicationEvent>,
+    backend_tx: Sender<PlayerBackendEvent>,
+    backend_rx: Receiver<PlayerBackendEvent>,
+    media_controls: Option<MediaControls>,
 }
 
 impl Player {
-    pub fn new(event_tx: Sender<ApplicationEvent>, player_rx: Receiver<PlayerReceiveEvent>) {
+    pub fn new(
+        event_tx: UnboundedSender<ApplicationEvent>,
+        player_rx: Receiver<PlayerReceiveEvent>,
+    ) {
+        // Everything the player reacts to arrives on one channel, so it can block on it
+        let (backend_tx, backend_rx) = channel::<PlayerBackendEvent>();
+        let app_tx = backend_tx.clone();
+        thread::spawn(move || {
+            for event in player_rx {
+                if app_tx.send(PlayerBackendEvent::App(event)).is_err() {
+                    break;
+                }
+            }
+        });
         thread::spawn(move || {
-            let instance = Instance::new().unwrap();
+            let Some((instance, media_player)) = Instance::new().and_then(|instance| {
+                let media_player = MediaPlayer::new(&instance)?;
+                Some((instance, media_player))
+            }) else {
+                error!("Failed to start libvlc");
+                let _ = event_tx.send(ApplicationEvent::PlayerEvent(PlayerSendEvent::Error(
+                    AppError::VlcUnavailable,
+                )));
+                return;
+            };
             #[cfg(not(target_os = "windows"))]
             let hwnd = None;
 
@@ -91,99 +145,125 @@ impl Player {
                 display_name: "Musictui",
                 hwnd,
             };
+            // Playback works without them, e.g. when there is no D-Bus session
+            let media_controls = match Config::get().mpris.then(|| MediaControls::new(config)) {
+                None => None,
+                Some(Ok(media_controls)) => Some(media_controls),
+                Some(Err(err)) => {
+                    warn!("Failed to create media controls: {err}");
+                    let _ = event_tx.send(ApplicationEvent::PlayerEvent(PlayerSendEvent::Error(
+                        AppError::MediaControls(err.to_string()),
+                    )));
+                    None
+                }
+            };
             Player {
                 queue: Vec::new(),
                 playing_index: None,
-                media_player: MediaPlayer::new(&instance).unwrap(),
+                shuffle: false,
+                listened: 0,
+                last_time: None,
+                media_player,
                 vlc_instance: instance,
                 event_tx,
-                player_rx,
-                media_controls: MediaControls::new(config).unwrap(),
+                backend_tx,
+                backend_rx,
+                media_controls,
             }
             .run()
         });
     }
 
     pub fn run(&mut self) {
-        let (vlc_tx, vlc_rx) = channel::<PlayerBackendEvent>();
-        self.create_event_thread(vlc_tx);
-        loop {
-            if let Ok(event) = vlc_rx.try_recv() {
-                match event {
-                    PlayerBackendEvent::VLCEvent(event) => match event {
-                        Event::MediaPlayerTimeChanged => {
-                            let passed_time = self.media_player.get_time().unwrap_or(0) as u64;
-
-                            self.event_tx
-                                .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::TimeChanged(
-                                    passed_time,
-                                )))
-                                .expect("Error sending TimeChanged event");
-                        }
-                        Event::MediaPlayerStopped => {
-                            self.next_song();
-                        }
-                        _ => {}
-                    },
-                    PlayerBackendEvent::MediaControls(event) => match event {
-                        MediaControlEvent::Pause => {
-                            self.pause();
-                        }
-                        MediaControlEvent::Next => {
-                            self.next_song();
-                        }
-                        MediaControlEvent::Previous => {
-                            self.prev_song();
-                        }
-                        MediaControlEvent::Play => {
-                            self.play();
-                        }
-                        MediaControlEvent::Toggle => {
-                            self.toggle_pause();
-                        }
-                        _ => {}
-                    },
-                }
-            }
-            if let Ok(event) = self.player_rx.try_recv() {
-                match event {
-                    PlayerReceiveEvent::SetSong(index) => {
-                        self.set_song(index);
-                    }
-                    PlayerReceiveEvent::AddSongsToQueueAndPlay(songs) => {
-                        self.add_songs_to_queue(songs);
+        self.create_event_thread(self.backend_tx.clone());
+        while let Ok(event) = self.backend_rx.recv() {
+            match event {
+                PlayerBackendEvent::VLCEvent(event) => match event {
+                    Event::MediaPlayerTimeChanged => {
+                        let passed_time = self.media_player.get_time().unwrap_or(0) as u64;
+                        self.track_listened(passed_time);
+                        self.send(PlayerSendEvent::TimeChanged(passed_time));
                     }
-                    PlayerReceiveEvent::CreateQueueAndPlay(songs) => {
-                        self.create_queue_and_play(songs);
-                    }
-                    PlayerReceiveEvent::SetAndPlaySong(index) => {
-                        self.set_and_play_song(index);
+                    Event::MediaPlayerStopped => {
+                        self.next_song();
                     }
-                    PlayerReceiveEvent::Play => {
-                        self.play();
+                    Event::MediaPlayerEncounteredError => {
+                        self.skip_unplayable();
                     }
-                    PlayerReceiveEvent::Pause => {
+                    _ => {}
+                },
+                PlayerBackendEvent::MediaControls(event) => match event {
+                    MediaControlEvent::Pause => {
                         self.pause();
                     }
-                    PlayerReceiveEvent::TogglePause => {
-                        self.toggle_pause();
-                    }
-                    PlayerReceiveEvent::Update => {
-                        self.event_tx
-                            .send(ApplicationEvent::PlayerEvent(
-                                PlayerSendEvent::PlayerInformation(self.get_player_information()),
-                            ))
-                            .expect("Error sending Update event");
+                    MediaControlEvent::Next => {
+                        self.next_song();
                     }
-                    PlayerReceiveEvent::Previous => {
+                    MediaControlEvent::Previous => {
                         self.prev_song();
                     }
-                    PlayerReceiveEvent::Next => {
-                        self.next_song();
+                    MediaControlEvent::Play => {
+                        self.play();
                     }
-                }
+                    MediaControlEvent::Toggle => {
+                        self.toggle_pause();
+                    }
+                    _ => {}
+                },
+                PlayerBackendEvent::App(event) => self.handle_app_event(event),
+            }
+        }
+    }
+
+    fn handle_app_event(&mut self, event: PlayerReceiveEvent) {
+        match event {
+            PlayerReceiveEvent::SetSong(index) => {
+                self.set_song(index);
+            }
+            PlayerReceiveEvent::AddSongsToQueueAndPlay(songs) => {
+                self.add_songs_to_queue(songs);
+            }
+            PlayerReceiveEvent::CreateQueueAndPlay(songs) => {
+                self.create_queue_and_play(songs);
+            }
+            PlayerReceiveEvent::PrependAndPlay(song) => {
+                self.prepend_and_play(*song);
+            }
+            PlayerReceiveEvent::SetAndPlaySong(index) => {
+                self.set_and_play_song(index);
+            }
+            PlayerReceiveEvent::Play => {
+                self.play();
+            }
+            PlayerReceiveEvent::Pause => {
+                self.pause();
+            }
+            PlayerReceiveEvent::TogglePause => {
+                self.toggle_pause();
+            }
+            PlayerReceiveEvent::Update => {
+                self.send(PlayerSendEvent::PlayerInformation(Box::new(
+                    self.get_player_information(),
+                )));
+            }
+            PlayerReceiveEvent::Previous => {
+                self.prev_song();
+            }
+            PlayerReceiveEvent::Next => {
+                self.next_song();
+            }
+            PlayerReceiveEvent::SetCoverArt { release, path } => {
+                self.set_cover_art(&release, path);
+            }
+            PlayerReceiveEvent::Seek(time) => {
+                self.seek(time);
+            }
+            PlayerReceiveEvent::SetVolume(volume) => {
+                let _ = self.media_player.set_volume(volume.clamp(0, 100));
+            }
+            PlayerReceiveEvent::SetShuffle(shuffle) => {
+                self.set_shuffle(shuffle);
             }
-            thread::sleep(Duration::from_millis(50));
         }
     }
 
@@ -192,26 +272,86 @@ impl Player {
             if index == 0 {
                 return;
             }
-            self.set_song(index - 1);
-            self.play();
+            if self.set_song(index - 1) {
+                self.play();
+            }
         }
     }
 
     fn next_song(&mut self) {
         if let Some(index) = self.playing_index {
             if index + 1 >= self.queue.iter().len() {
+                self.finish_song();
                 self.playing_index = None;
-                self.event_tx
-                    .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::PlayerEnded))
-                    .expect("Failed to send player ended event");
+                self.send(PlayerSendEvent::PlayerEnded);
+                return;
+            }
+            self.send(PlayerSendEvent::NextSong);
+            self.play_from(index + 1);
+        }
+    }
+
+    /// Plays the first song from `index` on that can be played, warning about the others.
+    fn play_from(&mut self, index: usize) {
+        for index in index..self.queue.len() {
+            if self.set_song(index) {
+                self.play();
                 return;
             }
-            self.set_song(index + 1);
-            self.event_tx
-                .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::NextSong))
-                .expect("Failed to send next song event");
-            self.play();
         }
+        self.playing_index = None;
+        self.send(PlayerSendEvent::PlayerEnded);
+    }
+
+    /// Adds the time since the last update, unless it jumped like after a seek.
+    fn track_listened(&mut self, time: u64) {
+        if let Some(last_time) = self.last_time
+            && time > last_time
+            && time - last_time <= MAX_TIME_STEP
+        {
+            self.listened += time - last_time;
+        }
+        self.last_time = Some(time);
+    }
+
+    /// Reports how much of the current song was heard, before another one is loaded.
+    fn finish_song(&mut self) {
+        let listened = std::mem::take(&mut self.listened);
+        self.last_time = None;
+        if listened == 0 {
+            return;
+        }
+        if let Some((song, _)) = self.get_current_song() {
+            let length = match song.total_time {
+                0 => self
+                    .media_player
+                    .get_media()
+                    .and_then(|media| media.duration())
+                    .unwrap_or(0) as u64,
+                total_time => total_time as u64,
+            };
+            let song = Box::new(song.clone());
+            self.send(PlayerSendEvent::Listened {
+                song,
+                listened,
+                length,
+            });
+        }
+    }
+
+    /// Called when libvlc fails on the current song after it started.
+    fn skip_unplayable(&mut self) {
+        if let Some((song, index)) = self.get_current_song() {
+            warn!("Failed to play {}", song.title);
+            let title = song.title.clone();
+            self.send(PlayerSendEvent::Error(AppError::Unplayable { title }));
+            self.play_from(index + 1);
+        }
+    }
+
+    fn send(&self, event: PlayerSendEvent) {
+        // Only fails while the app shuts down
+        let _ = self.event_tx.send(ApplicationEvent::PlayerEvent(event));
     }
 
     fn get_player_information(&self) -> PlayerInformation {
@@ -222,6 +362,7 @@ impl Player {
             passed_time: passed_time,
             status: self.get_player_status(),
             volume: self.media_player.get_volume(),
+            shuffle: self.shuffle,
         }
     }
 
@@ -249,7 +390,11 @@ impl Player {
 
     fn toggle_pause(&mut self) {
         if self.playing_index.is_some() {
-            self.pause();
+            if self.media_player.is_playing() {
+                self.pause();
+            } else {
+                self.play();
+            }
         } else {
             if !self.queue.is_empty() {
                 self.set_and_play_song(0);
@@ -259,88 +404,153 @@ impl Player {
 
     fn add_song_to_queue(&mut self, song: Song) {
         self.queue.push(song);
-        self.event_tx
-            .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::QueueUpdate(
-                self.queue.clone(),
-            )))
-            .expect("Error sending queue update event");
+        self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
     }
 
     fn set_and_play_song(&mut self, index: usize) {
-        self.set_song(index);
-        self.play();
+        self.play_from(index);
     }
 
     fn create_queue_and_play(&mut self, songs: Vec<Song>) {
+        self.finish_song();
         self.queue = songs;
-        self.event_tx
-            .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::QueueUpdate(
-                self.queue.clone(),
-            )))
-            .expect("Error sending queue update event");
+        self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
         if !self.queue.is_empty() {
-            self.set_song(0);
-            self.play();
+            self.play_from(0);
         }
     }
 
+    fn prepend_and_play(&mut self, song: Song) {
+        // Before the indexes move, so the listened time goes to the right song
+        self.finish_song();
+        self.queue.insert(0, song);
+        self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
+        self.play_from(0);
+    }
+
     fn add_songs_to_queue(&mut self, songs: Vec<Song>) {
         for song in songs {
             self.queue.push(song);
         }
-        self.event_tx
-            .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::QueueUpdate(
-                self.queue.clone(),
-            )))
-            .expect("Error sending queue update event");
+        if self.shuffle {
+            self.shuffle_upcoming();
+        }
+        self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
+    }
+
+    /// Turning shuffle on shuffles the songs after the current one, songs added while it is on
+    /// are mixed in with them.
+    fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        if shuffle {
+            self.shuffle_upcoming();
+            self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
+        }
+    }
+
+    fn shuffle_upcoming(&mut self) {
+        let start = self.playing_index.map(|index| index + 1).unwrap_or(0);
+        if let Some(upcoming) = self.queue.get_mut(start..) {
+            upcoming.shuffle(&mut rand::rng());
+        }
     }
 
     fn add_to_queue_and_play_song(&mut self, song: Song) {
         self.add_song_to_queue(song);
-        self.set_song(self.queue.len() - 1);
-        self.play();
+        self.play_from(self.queue.len() - 1);
     }
 
+    /// Does nothing while already playing, unlike the toggle.
     fn play(&mut self) {
+        if self.media_player.is_playing() {
+            return;
+        }
         if let Some((_song, index)) = self.get_current_song() {
-            self.media_player.play().expect("Failed to play media");
-            self.event_tx
-                .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::Play(index)))
-                .expect("Error sending Play event");
-            self.media_controls
-                .set_playback(MediaPlayback::Playing { progress: None })
-                .unwrap();
+            if self.media_player.play().is_err() {
+                self.skip_unplayable();
+                return;
+            }
+            self.send(PlayerSendEvent::Play(index));
+            self.set_playback(MediaPlayback::Playing { progress: None });
         }
     }
 
     fn pause(&mut self) {
         if let Some((_song, index)) = self.get_current_song() {
-            self.media_player.pause();
-            self.event_tx
-                .send(ApplicationEvent::PlayerEvent(PlayerSendEvent::Pause(index)))
-                .expect("Error sending Play event");
-            self.media_controls
-                .set_playback(MediaPlayback::Paused { progress: None })
-                .unwrap();
+            // `pause` toggles, a second pause request would resume
+            self.media_player.set_pause(true);
+            self.send(PlayerSendEvent::Pause(index));
+            self.set_playback(MediaPlayback::Paused { progress: None });
+        }
+    }
+
+    fn set_playback(&mut self, playback: MediaPlayback) {
+        if let Some(media_controls) = &mut self.media_controls
+            && let Err(err) = media_controls.set_playback(playback)
+        {
+            warn!("Failed to update media controls: {err}");
+        }
+    }
+
+    fn seek(&mut self, time: u64) {
+        if self.playing_index.is_some() {
+            self.media_player.set_time(time as i64);
+            self.last_time = Some(time);
+            self.send(PlayerSendEvent::TimeChanged(time));
         }
     }
 
-    fn set_song(&mut self, index: usize) {
-        if let Some(song) = self.queue.get(index) {
-            if let SongType::Local { path } | SongType::OnlineDownloaded { path, .. } =
-                &song.song_type
-            {
-                let media = Media::new_path(&self.vlc_instance, path).unwrap();
-                self.playing_index = Some(index);
-                self.media_player.set_media(&media);
-                self.media_controls
-                    .set_metadata(MediaMetadata {
-                        title: Some(&song.title),
-                        artist: song.artist.as_deref(),
-                        album: song.album.as_deref(),
-                        ..Default::default()
-                    })
-                    .unwrap();
+    /// Returns false and warns if the song can't be loaded.
+    fn set_song(&mut self, index: usize) -> bool {
+        self.finish_song();
+        let Some(song) = self.queue.get(index) else {
+            return false;
+        };
+        let media = match &song.song_type {
+            SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => {
+                Media::new_path(&self.vlc_instance, path)
+            }
+            SongType::Online { url } => Media::new_location(&self.vlc_instance, url),
+            SongType::OnlineWithoutUrl => None,
+        };
+        let Some(media) = media else {
+            warn!("Failed to load {}", song.title);
+            let title = song.title.clone();
+            self.send(PlayerSendEvent::Error(AppError::Unplayable { title }));
+            return false;
+        };
+        self.playing_index = Some(index);
+        self.media_player.set_media(&media);
+        self.update_metadata(index);
+        true
+    }
+
+    fn set_cover_art(&mut self, release: &str, path: PathBuf) {
+        for song in &mut self.queue {
+            if song.musicbrainz.release.as_deref() == Some(release) && song.cover_art.is_none() {
+                song.cover_art = Some(path.clone());
+            }
+        }
+        self.send(PlayerSendEvent::QueueUpdate(self.queue.clone()));
+        if let Some(index) = self.playing_index {
+            self.update_metadata(index);
+        }
+    }
+
+    fn update_metadata(&mut self, index: usize) {
+        if let Some(song) = self.queue.get(index)
+            && let Some(media_controls) = &mut self.media_controls
+        {
+            let cover_url = song.cover_art.as_deref().map(artwork::cover_url);
+            let metadata = MediaMetadata {
+                title: Some(&song.title),
+                artist: song.artist.as_deref(),
+                album: song.album.as_deref(),
+                cover_url: cover_url.as_deref(),
+                ..Default::default()
+            };
+            if let Err(err) = media_controls.set_metadata(metadata) {
+                warn!("Failed to update media controls: {err}");
             }
         }
     }
@@ -365,11 +575,21 @@ impl Player {
         let _ = event_manager.attach(EventType::MediaPlayerStopped, move |event, _| {
             let _ = event_tx.send(PlayerBackendEvent::VLCEvent(Event::MediaPlayerStopped));
         });
+        let event_tx = player_backend_event_tx.clone();
+        let _ = event_manager.attach(EventType::MediaPlayerEncounteredError, move |_, _| {
+            let _ = event_tx.send(PlayerBackendEvent::VLCEvent(
+                Event::MediaPlayerEncounteredError,
+            ));
+        });
 
         // MediaControlls
         let event_tx = player_backend_event_tx.clone();
-        let _ = self.media_controls.attach(move |event: MediaControlEvent| {
-            let _ = event_tx.send(PlayerBackendEvent::MediaControls(event));
-        });
+        if let Some(media_controls) = &mut self.media_controls
+            && let Err(err) = media_controls.attach(move |event: MediaControlEvent| {
+                let _ = event_tx.send(PlayerBackendEvent::MediaControls(event));
+            })
+        {
+            warn!("Failed to attach media controls: {err}");
+        }
     }
 }
@@ -0,0 +1,82 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    fetched_at: u64,
+    body: String,
+}
+
+/// Stores response bodies on disk, one file per key.
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        ResponseCache { dir, ttl }
+    }
+
+    /// Returns the cached body if it is younger than the TTL.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entry = self.read_entry(key)?;
+        if now_secs().saturating_sub(entry.fetched_at) > self.ttl.as_secs() {
+            return None;
+        }
+        Some(entry.body)
+    }
+
+    /// Returns the cached body regardless of its age. Used when the network is unavailable.
+    pub fn get_stale(&self, key: &str) -> Option<String> {
+        self.read_entry(key).map(|entry| entry.body)
+    }
+
+    pub fn insert(&self, key: &str, body: &str) {
+        let entry = CacheEntry {
+            key: key.to_string(),
+            fetched_at: now_secs(),
+            body: body.to_string(),
+        };
+        let result = fs::create_dir_all(&self.dir).and_then(|_| {
+            let json = serde_json::to_string(&entry)?;
+            fs::write(self.path_for(key), json)
+        });
+        if let Err(err) = result {
+            warn!("Failed to write cache entry for {key}: {err}");
+        }
+    }
+
+    fn read_entry(&self, key: &str) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.path_for(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        // Guard against hash collisions
+        (entry.key == key).then_some(entry)
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+}
+
+// Stable across builds, unlike std's DefaultHasher
+pub fn fnv1a(input: &[u8]) -> u64 {
+    input.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,33 @@
+use std::{fs, path::PathBuf};
+
+use reqwest::{StatusCode, header};
+
+use crate::{artwork::cover_dir, config::Config, fetch::musicbrainz::FetchError};
+
+const COVER_ART_ARCHIVE_URL: &str = "https://coverartarchive.org";
+
+/// Downloads the front cover of a release into the cover cache, or returns the cached file.
+pub async fn fetch_release_cover(release_id: &str) -> Result<PathBuf, FetchError> {
+    let dir = cover_dir();
+    let path = dir.join(format!("{release_id}.jpg"));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let config = Config::get();
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{COVER_ART_ARCHIVE_URL}/release/{release_id}/front-500"
+        ))
+        .header(header::USER_AGENT, config.musicbrainz_user_agent.as_str())
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(format!("No cover art for release {release_id}").into());
+    }
+    let bytes = response.error_for_status()?.bytes().await?;
+
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, &bytes)?;
+    Ok(path)
+}
@@ -0,0 +1,221 @@
+use musicbrainz_rs::entity::{
+    release::Release,
+    release_group::{ReleaseGroup, ReleaseGroupPrimaryType},
+};
+
+use crate::{
+    fetch::{
+        matcher::normalize,
+        musicbrainz::{FetchError, MusicBrainzClient},
+        search::resolve_online_sources,
+    },
+    song::{MusicBrainzIds, Song, SongType},
+    tags,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReleaseKind {
+    Album,
+    Ep,
+    Single,
+    Other,
+}
+
+impl ReleaseKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReleaseKind::Album => "Albums",
+            ReleaseKind::Ep => "EPs",
+            ReleaseKind::Single => "Singles",
+            ReleaseKind::Other => "Other",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReleaseGroupSummary {
+    pub id: String,
+    pub title: String,
+    pub kind: ReleaseKind,
+    pub year: Option<String>,
+}
+
+pub struct Album {
+    pub title: String,
+    pub tracks: Vec<Song>,
+}
+
+/// Release groups per browse request, the most MusicBrainz allows
+const PAGE_SIZE: usize = 100;
+
+/// Release groups of an artist, sorted by type and then by release date. Pages through all of
+/// them, prolific artists have more than fit in one request.
+pub async fn fetch_discography(
+    client: &MusicBrainzClient,
+    artist_id: &str,
+) -> Result<Vec<ReleaseGroupSummary>, FetchError> {
+    let mut entities: Vec<ReleaseGroup> = Vec::new();
+    loop {
+        let offset = entities.len().to_string();
+        let limit = PAGE_SIZE.to_string();
+        let page = client
+            .browse::<ReleaseGroup>(
+                "release-group",
+                &[
+                    ("artist", artist_id),
+                    ("type", "album|ep|single"),
+                    ("limit", &limit),
+                    ("offset", &offset),
+                ],
+            )
+            .await?;
+        let page_len = page.entities.len();
+        entities.extend(page.entities);
+        if page_len == 0 || entities.len() >= page.count.max(0) as usize {
+            break;
+        }
+    }
+    let mut release_groups: Vec<ReleaseGroupSummary> = entities
+        .into_iter()
+        .map(|release_group| ReleaseGroupSummary {
+            kind: match release_group.primary_type {
+                Some(ReleaseGroupPrimaryType::Album) => ReleaseKind::Album,
+                Some(ReleaseGroupPrimaryType::Ep) => ReleaseKind::Ep,
+                Some(ReleaseGroupPrimaryType::Single) => ReleaseKind::Single,
+                _ => ReleaseKind::Other,
+            },
+            year: release_group
+                .first_release_date
+                .map(|date| date.0.chars().take(4).collect::<String>())
+                .filter(|year| !year.is_empty()),
+            id: release_group.id,
+            title: release_group.title,
+        })
+        .collect();
+    release_groups.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.year.cmp(&b.year)));
+    Ok(release_groups)
+}
+
+/// Tracklist of the earliest official release in a release group. Tracks that exist in `library`
+/// point to the local file, all others get an online source.
+pub async fn fetch_album(
+    client: &MusicBrainzClient,
+    release_group_id: &str,
+    library: Vec<Song>,
+) -> Result<Album, FetchError> {
+    let releases = client
+        .browse::<Release>(
+            "release",
+            &[
+                ("release-group", release_group_id),
+                ("status", "official"),
+                ("limit", "25"),
+            ],
+        )
+        .await?
+        .entities;
+    let release = releases
+        .iter()
+        .min_by_key(|release| {
+            release
+                .date
+                .as_ref()
+                .map(|date| date.0.clone())
+                .filter(|date| !date.is_empty())
+                .unwrap_or_else(|| "9999".to_string())
+        })
+        .ok_or("Release group has no official releases")?;
+    let release_ids: Vec<String> = releases.iter().map(|release| release.id.clone()).collect();
+
+    let release: Release = client
+        .lookup("release", &release.id, &["recordings", "artist-credits"])
+        .await?;
+    let release_artist = release.artist_credit.as_ref().and_then(|c| c.first());
+    let release_artist_name = release_artist.map(|credit| credit.artist.name.clone());
+
+    let mut tracks: Vec<Song> = release
+        .media
+        .iter()
+        .flatten()
+        .flat_map(|media| media.tracks.iter().flatten())
+        .map(|track| {
+            let artist_credit = track
+                .artist_credit
+                .as_ref()
+                .and_then(|c| c.first())
+                .or(release_artist);
+            Song {
+                title: track.title.clone(),
+                artist: artist_credit.map(|credit| credit.artist.name.clone()),
+                album: Some(release.title.clone()),
+                genre: None,
+                year: None,
+                total_time: track.length.unwrap_or(0),
+                song_type: SongType::OnlineWithoutUrl,
+                match_confidence: None,
+                musicbrainz: MusicBrainzIds {
+                    recording: track.recording.as_ref().map(|r| r.id.clone()),
+                    artist: artist_credit.map(|credit| credit.artist.id.clone()),
+                    release: Some(release.id.clone()),
+                },
+                cover_art: None,
+            }
+        })
+        .collect();
+
+    // Matching may read tags from disk
+    let mut tracks = tokio::task::spawn_blocking(move || {
+        for song in &mut tracks {
+            if let Some(owned) =
+                find_owned(&library, song, &release_ids, release_artist_name.as_deref())
+            {
+                song.song_type = owned.song_type.clone();
+                song.cover_art = owned.cover_art.clone();
+            }
+        }
+        tracks
+    })
+    .await?;
+
+    resolve_online_sources(&mut tracks).await?;
+    Ok(Album {
+        title: release.title,
+        tracks,
+    })
+}
+
+/// The library copy of a track: same title, and tagged with a release of the group or by the
+/// same artist. Otherwise the title only counts when the album artist tag of the file is the
+/// release's artist, so generic titles like "Live" don't match other artists' songs.
+fn find_owned<'a>(
+    library: &'a [Song],
+    song: &Song,
+    release_ids: &[String],
+    release_artist: Option<&str>,
+) -> Option<&'a Song> {
+    let title = normalize(&song.title);
+    let artist = song.artist.as_deref().map(normalize);
+    let release_artist = release_artist.map(normalize);
+    library.iter().find(|local| {
+        if !local.is_local() || normalize(&local.title) != title {
+            return false;
+        }
+        if let Some(release) = &local.musicbrainz.release
+            && release_ids.contains(release)
+        {
+            return true;
+        }
+        let local_artist = local.artist.as_deref().map(normalize);
+        if artist.is_some() && local_artist == artist {
+            return true;
+        }
+        let SongType::Local { path } = &local.song_type else {
+            return false;
+        };
+        // Read only for the few songs that get this far, the library doesn't keep it
+        let local_album_artist = tags::read(path)
+            .and_then(|tags| tags.album_artist)
+            .map(|album_artist| normalize(&album_artist));
+        release_artist.is_some() && local_album_artist == release_artist
+    })
+}
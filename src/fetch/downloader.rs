@@ -10,9 +10,9 @@ pub struct Downloader {
 
 impl Downloader {
     pub async fn new() -> Result<Self, Box<dyn Error>> {
-        let config = Config::new();
-        let executables_dir = PathBuf::from(config.ytdl_libs.clone());
-        let output_dir = PathBuf::from(config.ytdl_libs);
+        let config = Config::get();
+        let executables_dir = config.ytdl_libs.clone();
+        let output_dir = config.download_dir.clone();
 
         let fetcher = Youtube::with_new_binaries(executables_dir, output_dir).await?;
         Ok(Downloader { fetcher })
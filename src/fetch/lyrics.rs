@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use reqwest::{StatusCode, Url, header};
+use serde::Deserialize;
+
+use crate::{
+    config::Config,
+    fetch::{cache::ResponseCache, musicbrainz::FetchError},
+    lyrics::{Lyrics, LyricsSource},
+    song::Song,
+};
+
+const LRCLIB_URL: &str = "https://lrclib.net/api/get";
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 30);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibResponse {
+    plain_lyrics: Option<String>,
+    synced_lyrics: Option<String>,
+}
+
+/// Looks up lyrics on lrclib. Misses are cached as well so they aren't requested again.
+pub async fn fetch_lyrics(song: &Song) -> Result<Option<Lyrics>, FetchError> {
+    let Some(artist) = &song.artist else {
+        return Ok(None);
+    };
+    let config = Config::get();
+    let cache = ResponseCache::new(config.cache_dir.join("lyrics"), CACHE_TTL);
+
+    let mut params = vec![
+        ("track_name", song.title.clone()),
+        ("artist_name", artist.clone()),
+    ];
+    if let Some(album) = &song.album {
+        params.push(("album_name", album.clone()));
+    }
+    if song.total_time > 0 {
+        params.push(("duration", (song.total_time / 1000).to_string()));
+    }
+    let url = Url::parse_with_params(LRCLIB_URL, &params)?;
+
+    let body = match cache.get(url.as_str()) {
+        Some(body) => body,
+        None => {
+            let response = reqwest::Client::new()
+                .get(url.clone())
+                .header(header::USER_AGENT, config.musicbrainz_user_agent.as_str())
+                .send()
+                .await?;
+            let body = if response.status() == StatusCode::NOT_FOUND {
+                String::new()
+            } else {
+                response.error_for_status()?.text().await?
+            };
+            cache.insert(url.as_str(), &body);
+            body
+        }
+    };
+    if body.is_empty() {
+        return Ok(None);
+    }
+
+    let response: LrclibResponse = serde_json::from_str(&body)?;
+    Ok(response
+        .synced_lyrics
+        .or(response.plain_lyrics)
+        .filter(|text| !text.trim().is_empty())
+        .map(|text| Lyrics::parse(&text, LyricsSource::Provider)))
+}
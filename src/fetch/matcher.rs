@@ -0,0 +1,153 @@
+use rusty_ytdl::search::Video;
+
+const DURATION_WEIGHT: f32 = 0.45;
+const TITLE_WEIGHT: f32 = 0.4;
+const CHANNEL_WEIGHT: f32 = 0.15;
+
+// Words that usually mean the video is not the studio recording
+const UNWANTED_WORDS: [&str; 9] = [
+    "live",
+    "cover",
+    "remix",
+    "karaoke",
+    "instrumental",
+    "reaction",
+    "slowed",
+    "sped",
+    "nightcore",
+];
+
+pub struct RecordingInfo<'a> {
+    pub title: &'a str,
+    pub artist: Option<&'a str>,
+    pub length_ms: Option<u32>,
+}
+
+pub struct ScoredVideo {
+    pub url: String,
+    pub confidence: f32,
+}
+
+pub fn best_match(recording: &RecordingInfo, videos: Vec<Video>) -> Option<ScoredVideo> {
+    videos
+        .into_iter()
+        .map(|video| ScoredVideo {
+            confidence: score(recording, &video),
+            url: video.url,
+        })
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+pub fn score(recording: &RecordingInfo, video: &Video) -> f32 {
+    let score = DURATION_WEIGHT * duration_score(recording.length_ms, video.duration)
+        + TITLE_WEIGHT * title_score(recording, &video.title)
+        + CHANNEL_WEIGHT
+            * channel_score(
+                recording.artist,
+                &video.channel.name,
+                video.channel.verified,
+            )
+        - unwanted_penalty(recording.title, &video.title);
+    score.clamp(0.0, 1.0)
+}
+
+fn duration_score(length_ms: Option<u32>, video_ms: u64) -> f32 {
+    let Some(length_ms) = length_ms.filter(|length| *length > 0) else {
+        return 0.5;
+    };
+    if video_ms == 0 {
+        return 0.5;
+    }
+    let diff_secs = (length_ms as f32 - video_ms as f32).abs() / 1000.0;
+    if diff_secs <= 2.0 {
+        1.0
+    } else {
+        (1.0 - (diff_secs - 2.0) / 28.0).max(0.0)
+    }
+}
+
+fn title_score(recording: &RecordingInfo, video_title: &str) -> f32 {
+    let video_tokens = tokenize(video_title);
+    if video_tokens.is_empty() {
+        return 0.0;
+    }
+    let title_tokens = tokenize(recording.title);
+    if title_tokens.is_empty() {
+        return 0.0;
+    }
+    let title_hits = title_tokens
+        .iter()
+        .filter(|token| video_tokens.contains(token))
+        .count();
+    let title_ratio = title_hits as f32 / title_tokens.len() as f32;
+
+    let artist_ratio = match recording.artist.map(tokenize) {
+        Some(artist_tokens) if !artist_tokens.is_empty() => {
+            let hits = artist_tokens
+                .iter()
+                .filter(|token| video_tokens.contains(token))
+                .count();
+            hits as f32 / artist_tokens.len() as f32
+        }
+        _ => 0.5,
+    };
+
+    // Extra words in the video title (e.g. "feat. someone", "1 hour") lower the score slightly
+    let expected = title_tokens.len()
+        + recording
+            .artist
+            .map(|artist| tokenize(artist).len())
+            .unwrap_or(0);
+    let extra = video_tokens.len().saturating_sub(expected) as f32;
+    let noise = (extra * 0.03).min(0.2);
+
+    (0.7 * title_ratio + 0.3 * artist_ratio - noise).max(0.0)
+}
+
+fn channel_score(artist: Option<&str>, channel: &str, verified: bool) -> f32 {
+    let channel_lower = channel.to_lowercase();
+    if channel_lower.ends_with("- topic") {
+        return 1.0;
+    }
+    let artist_channel = artist
+        .map(|artist| normalize(channel).contains(&normalize(artist)))
+        .unwrap_or(false);
+    if channel_lower.contains("vevo") || (artist_channel && verified) {
+        0.9
+    } else if artist_channel || channel_lower.contains("official") {
+        0.7
+    } else if verified {
+        0.3
+    } else {
+        0.0
+    }
+}
+
+fn unwanted_penalty(recording_title: &str, video_title: &str) -> f32 {
+    let recording_tokens = tokenize(recording_title);
+    let video_tokens = tokenize(video_title);
+    let unwanted = UNWANTED_WORDS
+        .iter()
+        .filter(|word| {
+            video_tokens.iter().any(|token| token == *word)
+                && !recording_tokens.iter().any(|token| token == *word)
+        })
+        .count();
+    unwanted as f32 * 0.25
+}
+
+pub fn normalize(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+fn tokenize(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
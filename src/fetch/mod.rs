@@ -1,2 +1,9 @@
+pub mod cache;
+pub mod coverart;
+pub mod discography;
 pub mod downloader;
+pub mod lyrics;
+pub mod matcher;
+pub mod musicbrainz;
+pub mod scrobble;
 pub mod search;
@@ -0,0 +1,208 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use musicbrainz_rs::entity::{
+    Browsable, BrowseResult,
+    search::{SearchResult, Searchable},
+};
+use reqwest::{StatusCode, Url, header};
+use serde::de::DeserializeOwned;
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::{config::Config, fetch::cache::ResponseCache};
+
+const BASE_URL: &str = "https://musicbrainz.org/ws/2";
+
+pub type FetchError = Box<dyn Error + Send + Sync>;
+
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(refill_per_second: f64, capacity: f64) -> Self {
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long to wait for the next one.
+    fn try_take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(missing / self.refill_per_second))
+        }
+    }
+}
+
+/// Shared MusicBrainz client. All requests go through one rate limiter and the response cache.
+pub struct MusicBrainzClient {
+    http: reqwest::Client,
+    limiter: Mutex<TokenBucket>,
+    cache: ResponseCache,
+    max_retries: u32,
+}
+
+impl MusicBrainzClient {
+    pub fn new(config: &Config) -> Result<Self, FetchError> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_str(&config.musicbrainz_user_agent)?,
+        );
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+        Ok(MusicBrainzClient {
+            http,
+            limiter: Mutex::new(TokenBucket::new(
+                config.musicbrainz_requests_per_second,
+                1.0,
+            )),
+            cache: ResponseCache::new(
+                config.cache_dir.join("musicbrainz"),
+                config.musicbrainz_cache_ttl,
+            ),
+            max_retries: config.musicbrainz_max_retries,
+        })
+    }
+
+    pub async fn search<T>(
+        &self,
+        entity: &str,
+        query: &str,
+        limit: u8,
+    ) -> Result<SearchResult<T>, FetchError>
+    where
+        T: DeserializeOwned + Searchable,
+    {
+        let body = self
+            .get(entity, &[("query", query), ("limit", &limit.to_string())])
+            .await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub async fn browse<T>(
+        &self,
+        entity: &str,
+        params: &[(&str, &str)],
+    ) -> Result<BrowseResult<T>, FetchError>
+    where
+        T: DeserializeOwned + Browsable,
+    {
+        let body = self.get(entity, params).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub async fn lookup<T>(
+        &self,
+        entity: &str,
+        id: &str,
+        includes: &[&str],
+    ) -> Result<T, FetchError>
+    where
+        T: DeserializeOwned,
+    {
+        let path = format!("{entity}/{id}");
+        let includes = includes.join("+");
+        let params: &[(&str, &str)] = if includes.is_empty() {
+            &[]
+        } else {
+            &[("inc", &includes)]
+        };
+        let body = self.get(&path, params).await?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    async fn get(&self, path: &str, params: &[(&str, &str)]) -> Result<String, FetchError> {
+        let mut url = Url::parse(&format!("{BASE_URL}/{path}"))?;
+        url.query_pairs_mut()
+            .extend_pairs(params)
+            .append_pair("fmt", "json");
+        let key = url.to_string();
+
+        if let Some(body) = self.cache.get(&key) {
+            return Ok(body);
+        }
+
+        let mut attempt = 0;
+        loop {
+            self.wait_for_token().await;
+            let response = match self.http.get(url.clone()).send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    if let Some(body) = self.cache.get_stale(&key) {
+                        warn!("MusicBrainz unreachable ({err}), using cached response");
+                        return Ok(body);
+                    }
+                    return Err(err.into());
+                }
+            };
+
+            let status = response.status();
+            let retryable = matches!(
+                status,
+                StatusCode::SERVICE_UNAVAILABLE | StatusCode::TOO_MANY_REQUESTS
+            );
+            if retryable && attempt < self.max_retries {
+                let backoff = retry_after(&response)
+                    .unwrap_or_else(|| Duration::from_millis(500 * 2u64.pow(attempt)));
+                info!("MusicBrainz returned {status}, retrying in {backoff:?}");
+                sleep(backoff).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = match response.error_for_status() {
+                Ok(response) => response,
+                Err(err) => {
+                    if let Some(body) = self.cache.get_stale(&key) {
+                        warn!("MusicBrainz request failed ({err}), using cached response");
+                        return Ok(body);
+                    }
+                    return Err(err.into());
+                }
+            };
+            let body = response.text().await?;
+            self.cache.insert(&key, &body);
+            return Ok(body);
+        }
+    }
+
+    async fn wait_for_token(&self) {
+        loop {
+            let wait = match self.limiter.lock().await.try_take() {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+            sleep(wait).await;
+        }
+    }
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
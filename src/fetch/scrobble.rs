@@ -0,0 +1,286 @@
+use std::{collections::BTreeMap, fmt};
+
+use md5::{Digest, Md5};
+use reqwest::{StatusCode, header};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    config::{Config, LastfmConfig, ListenBrainzConfig},
+    song::Song,
+};
+
+/// Last.fm errors worth retrying: invalid session key, so scrobbles wait for a fixed config,
+/// then service offline, temporarily unavailable and rate limited.
+const LASTFM_RETRY_CODES: [u32; 4] = [9, 11, 16, 29];
+
+/// What gets submitted for a play, kept in the queue until a service has it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    /// ms
+    pub length: u64,
+    /// Unix seconds
+    pub started: u64,
+    pub recording_mbid: Option<String>,
+}
+
+impl Listen {
+    /// `None` without an artist, which both services require.
+    pub fn new(song: &Song, started: u64, length: u64) -> Option<Self> {
+        Some(Listen {
+            artist: song.artist.clone()?,
+            title: song.title.clone(),
+            album: song.album.clone(),
+            length,
+            started,
+            recording_mbid: song.musicbrainz.recording.clone(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Service {
+    ListenBrainz,
+    Lastfm,
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Service::ListenBrainz => write!(f, "ListenBrainz"),
+            Service::Lastfm => write!(f, "Last.fm"),
+        }
+    }
+}
+
+pub enum SubmitError {
+    /// Worth sending again later, like no connection or a server error
+    Retry(String),
+    /// The service won't take it, sending again wouldn't help
+    Rejected(String),
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::Retry(message) | SubmitError::Rejected(message) => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for SubmitError {
+    fn from(err: reqwest::Error) -> Self {
+        SubmitError::Retry(err.to_string())
+    }
+}
+
+impl Service {
+    /// The services with credentials in the config.
+    pub fn configured(config: &Config) -> Vec<Service> {
+        let mut services = Vec::new();
+        if config.listenbrainz.is_some() {
+            services.push(Service::ListenBrainz);
+        }
+        if config.lastfm.is_some() {
+            services.push(Service::Lastfm);
+        }
+        services
+    }
+
+    pub async fn now_playing(self, config: &Config, listen: &Listen) -> Result<(), SubmitError> {
+        self.submit(config, listen, false).await
+    }
+
+    pub async fn scrobble(self, config: &Config, listen: &Listen) -> Result<(), SubmitError> {
+        self.submit(config, listen, true).await
+    }
+
+    async fn submit(
+        self,
+        config: &Config,
+        listen: &Listen,
+        scrobble: bool,
+    ) -> Result<(), SubmitError> {
+        let user_agent = &config.musicbrainz_user_agent;
+        let not_configured = || SubmitError::Rejected(format!("{self} isn't configured"));
+        match self {
+            Service::ListenBrainz => {
+                let listenbrainz = config.listenbrainz.as_ref().ok_or_else(not_configured)?;
+                submit_listenbrainz(listenbrainz, user_agent, listen, scrobble).await
+            }
+            Service::Lastfm => {
+                let lastfm = config.lastfm.as_ref().ok_or_else(not_configured)?;
+                submit_lastfm(lastfm, user_agent, listen, scrobble).await
+            }
+        }
+    }
+}
+
+async fn submit_listenbrainz(
+    listenbrainz: &ListenBrainzConfig,
+    user_agent: &str,
+    listen: &Listen,
+    scrobble: bool,
+) -> Result<(), SubmitError> {
+    let mut track = json!({
+        "artist_name": listen.artist,
+        "track_name": listen.title,
+        "additional_info": {
+            "duration_ms": listen.length,
+            "submission_client": "music-ratatui",
+            "submission_client_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    if let Some(album) = &listen.album {
+        track["release_name"] = json!(album);
+    }
+    if let Some(mbid) = &listen.recording_mbid {
+        track["additional_info"]["recording_mbid"] = json!(mbid);
+    }
+    let body = if scrobble {
+        json!({
+            "listen_type": "single",
+            "payload": [{ "listened_at": listen.started, "track_metadata": track }],
+        })
+    } else {
+        json!({
+            "listen_type": "playing_now",
+            "payload": [{ "track_metadata": track }],
+        })
+    };
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/1/submit-listens", listenbrainz.url))
+        .header(header::USER_AGENT, user_agent)
+        .header(
+            header::AUTHORIZATION,
+            format!("Token {}", listenbrainz.token),
+        )
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let message = format!("ListenBrainz answered {status}: {}", response.text().await?);
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        Err(SubmitError::Retry(message))
+    } else {
+        Err(SubmitError::Rejected(message))
+    }
+}
+
+#[derive(Deserialize)]
+struct LastfmError {
+    error: u32,
+    message: String,
+}
+
+async fn submit_lastfm(
+    lastfm: &LastfmConfig,
+    user_agent: &str,
+    listen: &Listen,
+    scrobble: bool,
+) -> Result<(), SubmitError> {
+    let method = if scrobble {
+        "track.scrobble"
+    } else {
+        "track.updateNowPlaying"
+    };
+    let mut params = BTreeMap::from([
+        ("method", method.to_string()),
+        ("api_key", lastfm.api_key.clone()),
+        ("sk", lastfm.session_key.clone()),
+        ("artist", listen.artist.clone()),
+        ("track", listen.title.clone()),
+        ("duration", (listen.length / 1000).to_string()),
+    ]);
+    if scrobble {
+        params.insert("timestamp", listen.started.to_string());
+    }
+    if let Some(album) = &listen.album {
+        params.insert("album", album.clone());
+    }
+    if let Some(mbid) = &listen.recording_mbid {
+        params.insert("mbid", mbid.clone());
+    }
+    params.insert("api_sig", lastfm_signature(&params, &lastfm.api_secret));
+    params.insert("format", "json".to_string());
+
+    let response = reqwest::Client::new()
+        .post(&lastfm.url)
+        .header(header::USER_AGENT, user_agent)
+        .form(&params)
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    // Errors come as JSON with their own code, often with a 4xx status
+    if let Ok(error) = serde_json::from_str::<LastfmError>(&body) {
+        let message = format!("Last.fm error {}: {}", error.error, error.message);
+        return Err(if LASTFM_RETRY_CODES.contains(&error.error) {
+            SubmitError::Retry(message)
+        } else {
+            SubmitError::Rejected(message)
+        });
+    }
+    if status.is_success() {
+        Ok(())
+    } else if status.is_server_error() {
+        Err(SubmitError::Retry(format!("Last.fm answered {status}")))
+    } else {
+        Err(SubmitError::Rejected(format!("Last.fm answered {status}")))
+    }
+}
+
+/// md5 of the parameters sorted by name, each name followed by its value, then the secret.
+fn lastfm_signature(params: &BTreeMap<&str, String>, secret: &str) -> String {
+    let mut hasher = Md5::new();
+    for (name, value) in params {
+        hasher.update(name.as_bytes());
+        hasher.update(value.as_bytes());
+    }
+    hasher.update(secret.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lastfm_signature_is_md5() {
+        // RFC 1321's "abc"
+        let params = BTreeMap::from([("a", "b".to_string())]);
+        assert_eq!(
+            lastfm_signature(&params, "c"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+    }
+
+    #[test]
+    fn lastfm_signature_sorts_parameters() {
+        // md5 of "api_keykeymethodtrack.scrobblesksessionsecret"
+        let params = BTreeMap::from([
+            ("sk", "session".to_string()),
+            ("method", "track.scrobble".to_string()),
+            ("api_key", "key".to_string()),
+        ]);
+        assert_eq!(
+            lastfm_signature(&params, "secret"),
+            "258e32db13d7112c91bf57a0b025de31"
+        );
+    }
+}
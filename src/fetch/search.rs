@@ -1,38 +1,23 @@
 use std::fmt::format;
 
 use log::info;
-use musicbrainz_rs::{
-    entity::{
-        artist::{Artist, ArtistSearchQuery},
-        recording::{Recording, RecordingSearchQuery, RecordingSearchQueryLuceneQueryBuilder},
-    },
-    prelude::*,
-};
-use reqwest::Error;
+use musicbrainz_rs::entity::recording::Recording;
 use rusty_ytdl::search::{SearchOptions, SearchResult, YouTube};
+use tokio::task::JoinSet;
 
-use crate::song::{Song, SongType};
+use crate::{
+    fetch::{
+        matcher::{RecordingInfo, ScoredVideo, best_match},
+        musicbrainz::{FetchError, MusicBrainzClient},
+    },
+    song::{MusicBrainzIds, Song, SongType},
+};
 
-pub async fn fetch_artists_manual() -> Result<(), Error> {
-    let client = reqwest::Client::new();
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.append(reqwest::header::USER_AGENT, "anonymous".parse().unwrap());
-    let res = client
-        .get("http://musicbrainz.org/ws/2/artist/?query=artist:giant_rooks")
-        .headers(headers)
-        .send()
-        .await?;
-    println!("Status: {}", res.status());
-    println!("Headers:\n{:#?}", res.headers());
+const YT_CANDIDATES: u64 = 5;
 
-    let body = res.text().await?;
-    println!("Body:\n{}", body);
-    Ok(())
-}
-
-fn create_query(input: &str, max_results: u32) -> String {
+fn create_query(input: &str) -> String {
     let parts: Vec<&str> = input.split_whitespace().collect();
-    let mut base_string = format!("query=recording:\"{}\" OR artist:\"{}\"", input, input);
+    let mut base_string = format!("recording:\"{}\" OR artist:\"{}\"", input, input);
     if parts.is_empty() {
         return "".to_string();
     }
@@ -59,61 +44,122 @@ fn create_query(input: &str, max_results: u32) -> String {
             );
         }
     }
-    return format!("{}&limit={}", base_string, max_results);
+    base_string
 }
 
-pub async fn fetch_recording(query: &str) -> Result<Vec<Song>, Box<dyn std::error::Error>> {
-    let search_string = create_query(query, 10);
+pub async fn fetch_recording(
+    client: &MusicBrainzClient,
+    query: &str,
+) -> Result<Vec<Song>, FetchError> {
+    let search_string = create_query(query);
+    if search_string.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    let youtube = YouTube::new().unwrap();
-    let mut query_result: Vec<Song> = Recording::search(search_string)
-        .execute()
+    let mut songs: Vec<Song> = client
+        .search::<Recording>("recording", &search_string, 10)
         .await?
         .entities
         .iter()
         .map(|recording| {
             info!("Recording: {:?}", recording.title);
-            let artist = recording
-                .artist_credit
-                .as_ref()
-                .and_then(|r| r.first())
+            let artist_credit = recording.artist_credit.as_ref().and_then(|r| r.first());
+            let artist = artist_credit
                 .map(|r| Some(r.artist.name.clone()))
                 .unwrap_or(None);
-            let album = recording
-                .releases
-                .as_ref()
-                .and_then(|rels| rels.get(0))
+            let release = recording.releases.as_ref().and_then(|rels| rels.get(0));
+            let album = release
                 .and_then(|release| release.release_group.as_ref())
                 .map(|rg| Some(rg.title.clone()))
                 .unwrap_or(None);
             let title = recording.title.clone();
-            let total_time = recording.length.unwrap_or(0) / 1000;
+            let total_time = recording.length.unwrap_or(0);
 
             Song {
                 album,
                 artist,
                 title,
+                genre: None,
+                year: None,
                 total_time,
                 song_type: SongType::OnlineWithoutUrl,
+                match_confidence: None,
+                musicbrainz: MusicBrainzIds {
+                    recording: Some(recording.id.clone()),
+                    artist: artist_credit.map(|credit| credit.artist.id.clone()),
+                    release: release.map(|release| release.id.clone()),
+                },
+                cover_art: None,
             }
         })
         .collect();
 
-    for song in &mut query_result {
-        let yt_search_result = youtube
-            .search_one(
-                format!("{}", song.title),
-                Some(&SearchOptions {
-                    search_type: rusty_ytdl::search::SearchType::Video,
-                    ..Default::default()
-                }),
-            )
-            .await;
-        if let Ok(Some(SearchResult::Video(video))) = yt_search_result {
-            info!("YT search result: {:?}", video.title);
-            song.song_type = SongType::Online { url: video.url }
+    resolve_online_sources(&mut songs).await?;
+    Ok(songs)
+}
+
+/// Looks up a YouTube source for every song that has none yet, all searches running concurrently.
+pub async fn resolve_online_sources(songs: &mut [Song]) -> Result<(), FetchError> {
+    let youtube = YouTube::new()?;
+    let mut lookups = JoinSet::new();
+    for (index, song) in songs.iter().enumerate() {
+        if !matches!(song.song_type, SongType::OnlineWithoutUrl) {
+            continue;
         }
+        let youtube = youtube.clone();
+        let title = song.title.clone();
+        let artist = song.artist.clone();
+        let length_ms = (song.total_time > 0).then_some(song.total_time);
+        lookups.spawn(async move {
+            let recording = RecordingInfo {
+                title: &title,
+                artist: artist.as_deref(),
+                length_ms,
+            };
+            (index, find_youtube_match(&youtube, &recording).await)
+        });
     }
 
-    Ok(query_result)
+    while let Some(result) = lookups.join_next().await {
+        if let Ok((index, Some(matched))) = result
+            && let Some(song) = songs.get_mut(index)
+        {
+            info!(
+                "YT match for {:?}: {} ({:.2})",
+                song.title, matched.url, matched.confidence
+            );
+            song.song_type = SongType::Online { url: matched.url };
+            song.match_confidence = Some(matched.confidence);
+        }
+    }
+    Ok(())
+}
+
+async fn find_youtube_match(
+    youtube: &YouTube,
+    recording: &RecordingInfo<'_>,
+) -> Option<ScoredVideo> {
+    let search_query = match recording.artist {
+        Some(artist) => format!("{} {}", artist, recording.title),
+        None => recording.title.to_string(),
+    };
+    let results = youtube
+        .search(
+            search_query,
+            Some(&SearchOptions {
+                limit: YT_CANDIDATES,
+                search_type: rusty_ytdl::search::SearchType::Video,
+                ..Default::default()
+            }),
+        )
+        .await
+        .ok()?;
+    let videos = results
+        .into_iter()
+        .filter_map(|result| match result {
+            SearchResult::Video(video) => Some(video),
+            _ => None,
+        })
+        .collect();
+    best_match(recording, videos)
 }
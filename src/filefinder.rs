@@ -1,24 +1,33 @@
-use std::{fs::read_dir, path::PathBuf, str::FromStr};
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use id3::{Tag, TagLike};
 use log::info;
 use vlc::{Instance, Media};
 
 use crate::{
+    artwork,
     display_handlers::folder_handler::Folder,
-    song::{Song, SongType},
+    error::AppError,
+    playlist_file::PlaylistFormat,
+    song::{MusicBrainzIds, Song, SongType},
+    tags,
 };
 
+#[derive(Clone)]
 pub struct FileFinder {
-    extensions: [String; 3],
+    extensions: Vec<String>,
     search_path: PathBuf,
     depth: u32,
     found_paths: Vec<PathBuf>,
     pub songs: Vec<Song>,
+    pub playlist_paths: Vec<PathBuf>,
 }
 
 impl FileFinder {
-    pub fn new(extensions: [String; 3], search_path: String, depth: Option<u32>) -> Self {
+    pub fn new(extensions: Vec<String>, search_path: String, depth: Option<u32>) -> Self {
         let search_path = PathBuf::from(search_path);
         FileFinder {
             extensions,
@@ -26,11 +35,22 @@ impl FileFinder {
             search_path: search_path.clone(),
             depth: depth.unwrap_or(3),
             songs: Vec::new(),
+            playlist_paths: Vec::new(),
         }
     }
 
-    pub fn find_paths(&mut self, path: Option<&PathBuf>, depth: Option<u32>) {
-        let vlc_instance = Instance::new().unwrap();
+    /// Song lengths come from libvlc, without it nothing is scanned.
+    pub fn find_paths(
+        &mut self,
+        path: Option<&PathBuf>,
+        depth: Option<u32>,
+    ) -> Result<(), AppError> {
+        let vlc_instance = Instance::new().ok_or(AppError::VlcUnavailable)?;
+        self.scan(&vlc_instance, path, depth);
+        Ok(())
+    }
+
+    fn scan(&mut self, vlc_instance: &Instance, path: Option<&PathBuf>, depth: Option<u32>) {
         let path = if let Some(path) = path {
             path
         } else {
@@ -46,6 +66,9 @@ impl FileFinder {
                 if let Ok(entry) = entry {
                     if let Ok(file_type) = entry.file_type() {
                         if file_type.is_file() {
+                            if PlaylistFormat::from_path(&entry.path()).is_some() {
+                                self.playlist_paths.push(entry.path());
+                            }
                             for extension in &self.extensions {
                                 if entry
                                     .file_name()
@@ -53,8 +76,7 @@ impl FileFinder {
                                     .unwrap_or_else(|_| "".to_string())
                                     .ends_with(extension)
                                 {
-                                    let song =
-                                        FileFinder::create_song(&vlc_instance, &entry.path());
+                                    let song = FileFinder::create_song(vlc_instance, &entry.path());
                                     if let Some(song) = song {
                                         self.songs.push(song);
                                     }
@@ -65,7 +87,7 @@ impl FileFinder {
                             let path = entry.path();
                             let file_name = entry.file_name().to_string_lossy().to_string();
                             if depth > 0 && !file_name.starts_with(".") {
-                                self.find_paths(Some(&path), Some(depth - 1));
+                                self.scan(vlc_instance, Some(&path), Some(depth - 1));
                             }
                         }
                     }
@@ -74,43 +96,42 @@ impl FileFinder {
         }
     }
     pub fn create_song(vlc_instance: &Instance, path: &PathBuf) -> Option<Song> {
-        if let Ok(tag) = Tag::read_from_path(path) {
-            let media = Media::new_path(vlc_instance, path).expect("Media should be created");
-            media.parse();
-            let song = Song {
-                artist: tag.artist().map(|s| s.to_string()),
-                title: tag.title().unwrap_or("Not defiended").to_string(),
-                total_time: media.duration().unwrap_or(5) as u32,
-                album: tag.album().map(|s| s.to_string()),
-                song_type: SongType::Local {
-                    path: path.to_owned(),
-                },
-            };
-            return Some(song);
-        }
-        return None;
+        let tags = tags::read(path)?;
+        let media = Media::new_path(vlc_instance, path)?;
+        media.parse();
+        let song = Song {
+            artist: tags.artist,
+            title: tags.title.unwrap_or("Not defiended".to_string()),
+            total_time: media.duration().unwrap_or(5) as u32,
+            album: tags.album,
+            genre: tags.genre,
+            year: tags.year,
+            song_type: SongType::Local {
+                path: path.to_owned(),
+            },
+            match_confidence: None,
+            musicbrainz: MusicBrainzIds {
+                release: tags.musicbrainz_release_id,
+                ..Default::default()
+            },
+            cover_art: artwork::find_local_cover(path, tags.picture.as_ref()),
+        };
+        Some(song)
     }
-    pub fn create_songs(&mut self) -> &Vec<Song> {
-        let mut vector = Vec::new();
-        let vlc_instance = Instance::new().unwrap();
-        for path in &self.found_paths {
-            if let Ok(tag) = Tag::read_from_path(path) {
-                if let Some(media) = Media::new_path(&vlc_instance, path) {
-                    media.parse();
-                    let song = Song {
-                        artist: tag.artist().map(|s| s.to_string()),
-                        title: tag.title().unwrap_or("Not defiended").to_string(),
-                        total_time: media.duration().unwrap_or(5) as u32,
-                        album: tag.album().map(|s| s.to_string()),
-                        song_type: SongType::Local {
-                            path: path.to_owned(),
-                        },
-                    };
-                    vector.push(song);
-                };
-            }
+    /// Songs in a file or folder outside the library, found like the library itself.
+    pub fn find_songs(&self, path: &Path) -> Result<Vec<Song>, AppError> {
+        if path.is_file() {
+            let vlc_instance = Instance::new().ok_or(AppError::VlcUnavailable)?;
+            return Ok(FileFinder::create_song(&vlc_instance, &path.to_path_buf())
+                .into_iter()
+                .collect());
         }
-        self.songs = vector;
-        &self.songs
+        let mut file_finder = FileFinder::new(
+            self.extensions.clone(),
+            path.to_string_lossy().to_string(),
+            Some(self.depth),
+        );
+        file_finder.find_paths(None, None)?;
+        Ok(file_finder.songs)
     }
 }
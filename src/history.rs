@@ -0,0 +1,129 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    library_db::now,
+    song::{MusicBrainzIds, Song, SongType},
+};
+
+/// One play of a song, whether it was heard to the end or skipped. Times are unix seconds,
+/// durations ms.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub started: u64,
+    pub listened: u64,
+    pub length: u64,
+    /// Heard past the play threshold
+    pub completed: bool,
+    /// View or pane the song was queued from
+    pub source: String,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Path or url
+    pub location: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(song: &Song, listened: u64, length: u64, completed: bool, source: String) -> Self {
+        HistoryEntry {
+            started: now().saturating_sub(listened / 1000),
+            listened,
+            length,
+            completed,
+            source,
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            location: song.location(),
+        }
+    }
+
+    pub fn local_time(&self) -> DateTime<Local> {
+        DateTime::from_timestamp(self.started as i64, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local)
+    }
+
+    /// The song from the library if it is still there, otherwise rebuilt from the entry.
+    pub fn song(&self, library: &[Song]) -> Option<Song> {
+        let location = self.location.as_ref()?;
+        if let Some(song) = library
+            .iter()
+            .find(|song| song.location().as_ref() == Some(location))
+        {
+            return Some(song.clone());
+        }
+        let song_type = if location.starts_with("http://") || location.starts_with("https://") {
+            SongType::Online {
+                url: location.clone(),
+            }
+        } else {
+            SongType::Local {
+                path: PathBuf::from(location),
+            }
+        };
+        Some(Song {
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            album: self.album.clone(),
+            genre: None,
+            year: None,
+            total_time: self.length as u32,
+            song_type,
+            match_confidence: None,
+            musicbrainz: MusicBrainzIds::default(),
+            cover_art: None,
+        })
+    }
+}
+
+/// Every play, oldest first, appended to a JSON lines file in the data dir.
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Lines that can't be read are logged and skipped.
+    pub fn load() -> Self {
+        let path = Config::get().data_dir.join("history.jsonl");
+        let entries = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                serde_json::from_str(line)
+                    .inspect_err(|err| warn!("Skipping history entry in {}: {err}", path.display()))
+                    .ok()
+            })
+            .collect();
+        History { path, entries }
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Keeps the entry in memory even if it can't be written.
+    pub fn add(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        self.entries.push(entry);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    fs, io,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::UnboundedSender,
+        oneshot,
+    },
+};
+
+use crate::{
+    config::{Config, CtlRequest},
+    events::{ApplicationEvent, musicplayer::PlayerSendEvent},
+    song::Song,
+};
+
+/// Events kept for a subscriber that reads slower than the player sends
+const EVENT_BUFFER: usize = 256;
+
+/// Requests sent to the app, answered through the sender
+pub type IpcReply = oneshot::Sender<Result<Value, String>>;
+
+/// A JSON line on the socket, tagged by `cmd`. Each gets one response line,
+/// `{"ok":true,"data":...}` or `{"ok":false,"error":"..."}`, except `subscribe`, after which the
+/// connection gets an event line for every player event. Player requests mirror
+/// `PlayerReceiveEvent` with songs given as paths to files or folders.
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Request {
+    SetSong {
+        index: usize,
+    },
+    SetAndPlaySong {
+        index: usize,
+    },
+    CreateQueueAndPlay {
+        paths: Vec<PathBuf>,
+    },
+    AddSongsToQueueAndPlay {
+        paths: Vec<PathBuf>,
+    },
+    PrependAndPlay {
+        path: PathBuf,
+    },
+    Play,
+    Next,
+    Previous,
+    Pause,
+    TogglePause,
+    Update,
+    SetCoverArt {
+        release: String,
+        path: PathBuf,
+    },
+    /// ms
+    Seek {
+        position: u64,
+    },
+    SetVolume {
+        volume: i32,
+    },
+    SetShuffle {
+        shuffle: bool,
+    },
+    Status,
+    Queue,
+    /// Songs in the library with every word of the query in the title, artist or album
+    Search {
+        query: String,
+    },
+    Subscribe,
+}
+
+#[derive(Serialize)]
+pub struct SongInfo {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// ms
+    pub length: u32,
+    pub location: Option<String>,
+}
+
+impl From<&Song> for SongInfo {
+    fn from(song: &Song) -> Self {
+        SongInfo {
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            length: song.total_time,
+            location: song.location(),
+        }
+    }
+}
+
+/// The event line for a player event, `None` for the ones that are only internal.
+pub fn event_line(event: &PlayerSendEvent, queue: &[Song]) -> Option<String> {
+    let event = match event {
+        PlayerSendEvent::Play(index) => json!({
+            "event": "play",
+            "index": index,
+            "song": queue.get(*index).map(SongInfo::from),
+        }),
+        PlayerSendEvent::Pause(index) => json!({ "event": "pause", "index": index }),
+        PlayerSendEvent::NextSong => json!({ "event": "next-song" }),
+        PlayerSendEvent::PlayerEnded => json!({ "event": "ended" }),
+        PlayerSendEvent::TimeChanged(position) => {
+            json!({ "event": "time", "position": position })
+        }
+        PlayerSendEvent::QueueUpdate(songs) => json!({
+            "event": "queue",
+            "songs": songs.iter().map(SongInfo::from).collect::<Vec<SongInfo>>(),
+        }),
+        PlayerSendEvent::Listened {
+            song,
+            listened,
+            length,
+        } => json!({
+            "event": "listened",
+            "song": SongInfo::from(song.as_ref()),
+            "listened": listened,
+            "length": length,
+        }),
+        PlayerSendEvent::Error(err) => json!({ "event": "error", "message": err.to_string() }),
+        PlayerSendEvent::PlayerInformation(_) => return None,
+    };
+    Some(event.to_string())
+}
+
+/// A socket left behind by an instance that is gone is replaced, one that still answers is an
+/// error. Only the user can connect: the socket is created under a umask that leaves others
+/// out, in a directory private to the user if it has to be made.
+pub async fn bind(path: &Path) -> io::Result<UnixListener> {
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("another instance is listening on {}", path.display()),
+        ));
+    }
+    let _ = fs::remove_file(path);
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    // Process wide, other threads creating files meanwhile only end up more private
+    let umask = unsafe { libc::umask(0o077) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    let listener = listener?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+pub fn spawn_server(
+    listener: UnixListener,
+    event_tx: UnboundedSender<ApplicationEvent>,
+    events: broadcast::Sender<String>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, event_tx.clone(), events.clone()));
+                }
+                Err(err) => warn!("IPC connection failed: {err}"),
+            }
+        }
+    });
+}
+
+pub fn event_channel() -> broadcast::Sender<String> {
+    broadcast::channel(EVENT_BUFFER).0
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    event_tx: UnboundedSender<ApplicationEvent>,
+    events: broadcast::Sender<String>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Subscribe) => {
+                let mut receiver = events.subscribe();
+                if write_line(&mut writer, &response(Ok(Value::Null)))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                loop {
+                    tokio::select! {
+                        event = receiver.recv() => match event {
+                            Ok(event) => {
+                                if write_line(&mut writer, &event).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Err(RecvError::Lagged(skipped)) => {
+                                info!("IPC subscriber missed {skipped} events");
+                            }
+                            Err(RecvError::Closed) => return,
+                        },
+                        // Anything sent after subscribing is ignored, only the end matters
+                        line = lines.next_line() => {
+                            if !matches!(line, Ok(Some(_))) {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(request) => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                if event_tx
+                    .send(ApplicationEvent::IpcRequest(request, reply_tx))
+                    .is_err()
+                {
+                    return;
+                }
+                match reply_rx.await {
+                    Ok(result) => result,
+                    Err(_) => return,
+                }
+            }
+            Err(err) => Err(format!("Invalid request: {err}")),
+        };
+        if write_line(&mut writer, &response(result)).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn response(result: Result<Value, String>) -> String {
+    match result {
+        Ok(data) => json!({ "ok": true, "data": data }),
+        Err(err) => json!({ "ok": false, "error": err }),
+    }
+    .to_string()
+}
+
+async fn write_line<W: AsyncWriteExt + Unpin>(writer: &mut W, line: &str) -> io::Result<()> {
+    writer.write_all(format!("{line}\n").as_bytes()).await
+}
+
+/// Sends one request to the running instance, the `ctl` subcommand. Returns what to print.
+pub async fn ctl(request: &CtlRequest) -> Result<String, String> {
+    let config = Config::get();
+    if !config.ipc_enabled {
+        return Err("The IPC socket is disabled in the config".to_string());
+    }
+    let request = match request {
+        CtlRequest::Play => json!({ "cmd": "play" }),
+        CtlRequest::Pause => json!({ "cmd": "pause" }),
+        CtlRequest::Next => json!({ "cmd": "next" }),
+        CtlRequest::Add { path } => {
+            // The instance has its own working directory
+            let path = path
+                .canonicalize()
+                .map_err(|err| format!("{}: {err}", path.display()))?;
+            json!({ "cmd": "add-songs-to-queue-and-play", "paths": [path] })
+        }
+        CtlRequest::Status => json!({ "cmd": "status" }),
+    };
+
+    let path = &config.ipc_socket;
+    let stream = UnixStream::connect(path)
+        .await
+        .map_err(|err| format!("No instance listening on {}: {err}", path.display()))?;
+    let (reader, mut writer) = stream.into_split();
+    write_line(&mut writer, &request.to_string())
+        .await
+        .map_err(|err| err.to_string())?;
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|err| err.to_string())?
+        .ok_or("The instance closed the connection")?;
+
+    let response: Value =
+        serde_json::from_str(&line).map_err(|err| format!("Invalid response: {err}"))?;
+    if response["ok"] != Value::Bool(true) {
+        return Err(response["error"]
+            .as_str()
+            .unwrap_or("Request failed")
+            .to_string());
+    }
+    Ok(match &response["data"] {
+        Value::Null => String::new(),
+        data => serde_json::to_string_pretty(data).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    sync::{OnceLock, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    song::{Song, SongType},
+    utils::atomic_file,
+};
+
+static LIBRARY_DB: OnceLock<RwLock<LibraryDb>> = OnceLock::new();
+
+/// What the library knows about a song beyond its tags. Times are unix seconds.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongStats {
+    pub added: u64,
+    pub play_count: u32,
+    pub skip_count: u32,
+    pub last_played: Option<u64>,
+    /// 0 to 5 stars, `None` if unrated
+    pub rating: Option<u8>,
+    pub favorite: bool,
+}
+
+/// Per-song stats, keyed by file path and stored as JSON in the data dir.
+pub struct LibraryDb {
+    path: PathBuf,
+    songs: HashMap<String, SongStats>,
+}
+
+impl LibraryDb {
+    /// The shared database, loaded on first use. Don't hold the guard across another `get`.
+    pub fn get() -> RwLockReadGuard<'static, LibraryDb> {
+        global().read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn get_mut() -> RwLockWriteGuard<'static, LibraryDb> {
+        global().write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// An unreadable file is moved aside and the database starts empty, the next save doesn't
+    /// overwrite it.
+    fn load() -> Self {
+        let path = Config::get().data_dir.join("library.json");
+        let songs = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                match atomic_file::set_aside(&path) {
+                    Ok(backup) => warn!(
+                        "Moved broken {} to {}: {err}",
+                        path.display(),
+                        backup.display()
+                    ),
+                    Err(move_err) => warn!(
+                        "Ignoring broken {}: {err}, can't move it: {move_err}",
+                        path.display()
+                    ),
+                }
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        LibraryDb { path, songs }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let content = serde_json::to_string(&self.songs).map_err(io::Error::other)?;
+        atomic_file::write(&self.path, content.as_bytes())
+    }
+
+    pub fn stats(&self, song: &Song) -> Option<&SongStats> {
+        self.songs.get(&key(song)?)
+    }
+
+    /// Remembers songs seen for the first time, as added when their file was last modified so
+    /// that a library found on the first scan doesn't all count as new. Returns whether any were
+    /// new.
+    pub fn register(&mut self, songs: &[Song]) -> bool {
+        let now = now();
+        let mut changed = false;
+        for song in songs {
+            let Some(key) = key(song) else {
+                continue;
+            };
+            self.songs.entry(key).or_insert_with(|| {
+                changed = true;
+                SongStats {
+                    added: modified(song).unwrap_or(now).min(now),
+                    ..SongStats::default()
+                }
+            });
+        }
+        changed
+    }
+
+    /// Counts a play if at least `threshold` of the song was heard, a skip otherwise.
+    /// Returns whether it was a play.
+    pub fn record_listen(
+        &mut self,
+        song: &Song,
+        listened: u64,
+        length: u64,
+        threshold: f64,
+    ) -> bool {
+        let Some(stats) = self.entry(song) else {
+            return false;
+        };
+        let played = listened as f64 >= length as f64 * threshold;
+        if played {
+            stats.play_count += 1;
+            stats.last_played = Some(now());
+        } else {
+            stats.skip_count += 1;
+        }
+        played
+    }
+
+    /// Returns the stats the song ends up with, `None` if it isn't a file.
+    pub fn set_rating(&mut self, song: &Song, rating: Option<u8>) -> Option<SongStats> {
+        let stats = self.entry(song)?;
+        stats.rating = rating.map(|rating| rating.min(5));
+        Some(stats.clone())
+    }
+
+    pub fn toggle_favorite(&mut self, song: &Song) -> Option<bool> {
+        let stats = self.entry(song)?;
+        stats.favorite = !stats.favorite;
+        Some(stats.favorite)
+    }
+
+    /// Songs played before they were scanned, e.g. from a playlist, are added on the spot.
+    fn entry(&mut self, song: &Song) -> Option<&mut SongStats> {
+        Some(self.songs.entry(key(song)?).or_insert_with(|| SongStats {
+            added: now(),
+            ..SongStats::default()
+        }))
+    }
+}
+
+/// Writes the shared database. Failures are logged, the stats stay in memory either way.
+pub fn save() {
+    let db = LibraryDb::get();
+    if let Err(err) = db.save() {
+        warn!("Failed to save {}: {err}", db.path.display());
+    }
+}
+
+fn global() -> &'static RwLock<LibraryDb> {
+    LIBRARY_DB.get_or_init(|| RwLock::new(LibraryDb::load()))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn modified(song: &Song) -> Option<u64> {
+    let (SongType::Local { path } | SongType::OnlineDownloaded { path, .. }) = &song.song_type
+    else {
+        return None;
+    };
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Only songs on disk have stats.
+fn key(song: &Song) -> Option<String> {
+    match &song.song_type {
+        SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => {
+            Some(path.to_string_lossy().to_string())
+        }
+        _ => None,
+    }
+}
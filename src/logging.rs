@@ -0,0 +1,168 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use log::{Level, LevelFilter, Record};
+use log4rs::{
+    append::{Append, file::FileAppender},
+    config::{Appender, Logger, Root},
+    encode::{Encode, pattern::PatternEncoder, writer::simple::SimpleWriter},
+};
+use ratatui::{
+    style::Stylize,
+    text::{Line, Span},
+    widgets::ListItem,
+};
+
+use crate::{config::Config, error::AppError, utils::selecthandler::SelectHandlerItem};
+
+const FILE_PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S)} {l:<5} {t} - {m}{n}";
+const PANE_PATTERN: &str = "{d(%H:%M:%S)} {t} - {m}";
+const MAX_LOG_LINES: usize = 1000;
+
+/// Levels for log targets and everything below them, like `music_ratatui::fetch`
+pub type TargetLevels = Vec<(String, LevelFilter)>;
+
+#[derive(Clone, Debug)]
+pub struct LogLine {
+    pub level: Level,
+    pub text: String,
+}
+
+impl SelectHandlerItem for LogLine {
+    fn list_item(&self) -> ListItem<'_> {
+        let theme = &Config::get().theme;
+        let level = Span::raw(format!("{:<5} ", self.level));
+        let level = match self.level {
+            Level::Error => level.fg(theme.error),
+            Level::Warn => level.fg(theme.warning),
+            _ => level,
+        };
+        ListItem::new(Line::from(vec![level, Span::raw(self.text.as_str())]))
+    }
+}
+
+/// The most recent log records, kept for the Log pane.
+#[derive(Clone, Default, Debug)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<LogBufferInner>>,
+}
+
+#[derive(Default, Debug)]
+struct LogBufferInner {
+    lines: VecDeque<LogLine>,
+    /// Counts every line ever pushed, so readers can tell whether anything changed
+    generation: u64,
+}
+
+impl LogBuffer {
+    fn push(&self, line: LogLine) {
+        if let Ok(inner) = &mut self.inner.lock() {
+            inner.lines.push_back(line);
+            inner.generation += 1;
+            while inner.lines.len() > MAX_LOG_LINES {
+                inner.lines.pop_front();
+            }
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.inner.lock().map(|inner| inner.generation).unwrap_or(0)
+    }
+
+    /// Lines at or above `level`, oldest first.
+    pub fn lines(&self, level: LevelFilter) -> Vec<LogLine> {
+        self.inner
+            .lock()
+            .map(|inner| {
+                inner
+                    .lines
+                    .iter()
+                    .filter(|line| line.level <= level)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+struct BufferAppender {
+    encoder: PatternEncoder,
+    buffer: LogBuffer,
+}
+
+impl Append for BufferAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let mut writer = SimpleWriter(Vec::new());
+        self.encoder.encode(&mut writer, record)?;
+        self.buffer.push(LogLine {
+            level: record.level(),
+            text: String::from_utf8_lossy(&writer.0).into_owned(),
+        });
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+/// Logs to the configured file and to `buffer`. If the file can't be opened, logging still
+/// reaches the buffer and the error is returned.
+pub fn init(config: &Config, buffer: LogBuffer) -> Result<(), AppError> {
+    let buffer_appender = BufferAppender {
+        encoder: PatternEncoder::new(PANE_PATTERN),
+        buffer,
+    };
+    let file_appender = FileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(FILE_PATTERN)))
+        .build(&config.log_file);
+
+    let mut builder = log4rs::Config::builder()
+        .appender(Appender::builder().build("buffer", Box::new(buffer_appender)));
+    let mut root = Root::builder().appender("buffer");
+    let file_error = match file_appender {
+        Ok(file_appender) => {
+            builder = builder.appender(Appender::builder().build("file", Box::new(file_appender)));
+            root = root.appender("file");
+            None
+        }
+        Err(err) => Some(AppError::Logging(format!(
+            "can't write {}: {err}",
+            config.log_file.display()
+        ))),
+    };
+    for (target, level) in &config.log_filters {
+        builder = builder.logger(Logger::builder().build(target, *level));
+    }
+    let log_config = builder
+        .build(root.build(config.log_level))
+        .map_err(|err| AppError::Logging(err.to_string()))?;
+    log4rs::init_config(log_config).map_err(|err| AppError::Logging(err.to_string()))?;
+    match file_error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Reads `RUST_LOG` style filters: comma separated `level` or `target=level` directives, a bare
+/// target means everything from it.
+pub fn parse_filters(spec: &str) -> Result<(Option<LevelFilter>, TargetLevels), String> {
+    let mut default = None;
+    let mut filters = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => {
+                let level = level
+                    .parse()
+                    .map_err(|_| format!("unknown level {level:?} in {directive:?}"))?;
+                filters.push((target.trim().replace('-', "_"), level));
+            }
+            None => match directive.parse() {
+                Ok(level) => default = Some(level),
+                Err(_) => filters.push((directive.replace('-', "_"), LevelFilter::Trace)),
+            },
+        }
+    }
+    Ok((default, filters))
+}
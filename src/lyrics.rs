@@ -0,0 +1,161 @@
+use std::{fs, path::Path};
+
+use ratatui::widgets::ListItem;
+
+use crate::{
+    tags::{self, EmbeddedLyrics},
+    utils::selecthandler::SelectHandlerItem,
+};
+
+const SIDECAR_EXTENSIONS: [&str; 2] = ["lrc", "txt"];
+
+#[derive(Clone)]
+pub struct LyricLine {
+    /// Start of the line in ms, only set for synced lyrics
+    pub time: Option<u64>,
+    pub text: String,
+}
+
+impl SelectHandlerItem for LyricLine {
+    fn list_item(&self) -> ListItem<'_> {
+        ListItem::new(self.text.clone())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LyricsSource {
+    Sidecar,
+    Embedded,
+    Provider,
+}
+
+impl LyricsSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LyricsSource::Sidecar => "file",
+            LyricsSource::Embedded => "tags",
+            LyricsSource::Provider => "lrclib",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Lyrics {
+    pub lines: Vec<LyricLine>,
+    pub source: LyricsSource,
+}
+
+impl Lyrics {
+    /// Parses LRC if the text contains any timestamps, otherwise treats it as plain text.
+    pub fn parse(text: &str, source: LyricsSource) -> Self {
+        let mut offset: i64 = 0;
+        let mut synced = Vec::new();
+        for line in text.lines() {
+            let (times, rest) = parse_timestamps(line);
+            if times.is_empty() {
+                if let Some(value) = line
+                    .trim()
+                    .strip_prefix("[offset:")
+                    .and_then(|value| value.strip_suffix(']'))
+                {
+                    offset = value.trim().parse().unwrap_or(0);
+                }
+                continue;
+            }
+            for time in times {
+                synced.push(LyricLine {
+                    // A positive offset makes lines appear sooner
+                    time: Some((time as i64 - offset).max(0) as u64),
+                    text: rest.trim().to_string(),
+                });
+            }
+        }
+
+        if synced.is_empty() {
+            let lines = text
+                .lines()
+                .map(|line| LyricLine {
+                    time: None,
+                    text: line.trim_end().to_string(),
+                })
+                .collect();
+            return Lyrics { lines, source };
+        }
+        synced.sort_by_key(|line| line.time);
+        Lyrics {
+            lines: synced,
+            source,
+        }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.lines.iter().any(|line| line.time.is_some())
+    }
+
+    /// Index of the line being sung at `position` ms.
+    pub fn current_line(&self, position: u64) -> Option<usize> {
+        if !self.is_synced() {
+            return None;
+        }
+        self.lines
+            .iter()
+            .rposition(|line| line.time.is_some_and(|time| time <= position))
+    }
+}
+
+/// Lyrics stored with the track: a sidecar file next to it, then embedded tags.
+pub fn load_local(song_path: &Path) -> Option<Lyrics> {
+    for extension in SIDECAR_EXTENSIONS {
+        if let Ok(text) = fs::read_to_string(song_path.with_extension(extension))
+            && !text.trim().is_empty()
+        {
+            return Some(Lyrics::parse(&text, LyricsSource::Sidecar));
+        }
+    }
+    match tags::read_lyrics(song_path)? {
+        EmbeddedLyrics::Synced(content) => Some(Lyrics {
+            lines: content
+                .into_iter()
+                .map(|(time, text)| LyricLine {
+                    time: Some(time),
+                    text: text.trim().to_string(),
+                })
+                .collect(),
+            source: LyricsSource::Embedded,
+        }),
+        EmbeddedLyrics::Text(text) => Some(Lyrics::parse(&text, LyricsSource::Embedded)),
+    }
+}
+
+// Splits "[01:02.34][01:40.00]text" into its timestamps in ms and the text
+fn parse_timestamps(line: &str) -> (Vec<u64>, &str) {
+    let mut times = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(tag) = rest.strip_prefix('[') {
+        let Some(end) = tag.find(']') else {
+            break;
+        };
+        let Some(time) = parse_timestamp(&tag[..end]) else {
+            break;
+        };
+        times.push(time);
+        rest = &tag[end + 1..];
+    }
+    (times, rest)
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (minutes, seconds) = timestamp.split_once(':')?;
+    let minutes: u64 = minutes.parse().ok()?;
+    let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, "0"));
+    let seconds: u64 = seconds.parse().ok()?;
+    // Fractions are hundredths in most files, but some use tenths or milliseconds, so `.5` is
+    // 500 ms. Some leave it empty, `[01:02.]`.
+    let fraction_ms = if fraction.is_empty() {
+        0
+    } else {
+        let digits = fraction.get(..3).unwrap_or(fraction);
+        format!("{digits:0<3}").parse().ok()?
+    };
+    Some(minutes * 60_000 + seconds * 1000 + fraction_ms)
+}
@@ -1,51 +1,156 @@
 use crate::{
-    display_handlers::folder_handler::{Folder, FolderHandler, Node},
+    commands::{Command, CommandLine, Rating, SeekPosition, Volume, read_rc_file},
+    config::{Cli, CliCommand, Config},
+    display_handlers::{
+        folder_handler::{Folder, FolderHandler, Node},
+        help_handler::HelpHandler,
+        history_handler::HistoryHandler,
+        log_handler::LogHandler,
+        lyrics_handler::LyricsHandler,
+        notification_handler::{NotificationHandler, NotificationLevel},
+        playlist_handler::PlaylistHandler,
+    },
+    error::AppError,
     events::{
-        ApplicationEvent,
-        keyboard::{Action, KeyboardHandler},
+        ApplicationEvent, format_ms_to_duration_string,
+        keyboard::{Action, KeyPress, KeyboardHandler},
+        keymap::KeyContext,
         musicplayer::{
             Player, PlayerInformation, PlayerReceiveEvent, PlayerSendEvent, PlayerStatus,
         },
     },
+    fetch::{
+        coverart::fetch_release_cover, lyrics::fetch_lyrics, musicbrainz::MusicBrainzClient,
+        scrobble::Listen,
+    },
     filefinder::FileFinder,
+    history::HistoryEntry,
+    ipc::{Request, SongInfo},
+    library_db::{LibraryDb, SongStats, now},
+    logging::LogBuffer,
+    lyrics::load_local,
+    playlist_file::PlaylistFile,
+    playlists::playlist_path,
+    scrobbler::{ScrobbleEvent, should_scrobble},
     searchhandler::SearchHandler,
-    song::Song,
-    utils::selecthandler::SelectHandler,
+    song::{Song, SongType, stars},
+    songs::sort_songs,
+    stats::{Period, Stats},
+    terminal_image::{GraphicsProtocol, TerminalImage},
+    utils::selecthandler::{Movement, SelectHandler},
+};
+use clap::Parser;
+use crossterm::{
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyEvent, KeyEventKind,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
 };
+use futures::StreamExt;
 use log::{debug, error, info, trace, warn};
+use ratatui::{
+    DefaultTerminal,
+    layout::{Position, Rect},
+};
+use serde_json::{Value, json};
 use std::{
-    collections::HashMap,
-    env,
+    collections::{HashMap, HashSet},
+    fs,
+    io::stdout,
+    panic,
     path::{Path, PathBuf},
     process,
-    sync::mpsc::{Receiver, Sender, channel},
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, Sender, channel},
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    },
+    time::MissedTickBehavior,
+};
+mod artwork;
+mod commands;
 mod config;
 mod display_handlers;
+mod error;
 mod events;
 mod fetch;
 mod filefinder;
+mod history;
+mod ipc;
+mod library_db;
+mod logging;
+mod lyrics;
+mod playlist_file;
+mod playlists;
+mod scrobbler;
 mod searchhandler;
+mod smart_playlists;
 mod song;
 mod songs;
+mod stats;
+mod tags;
+mod terminal_image;
 mod ui;
 mod utils;
 
+/// Redraw interval while playing, also how often key sequences are checked for timeouts
+const TICK_RATE: Duration = Duration::from_millis(250);
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+const MOUSE_SCROLL_LINES: usize = 3;
+
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
-    log4rs::init_file("log4rs.yaml", Default::default()).unwrap();
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(2);
+        }
+    };
+    let config_warnings = config.warnings();
+    Config::init(config);
+
+    if let Some(CliCommand::Ctl { request }) = &cli.command {
+        match ipc::ctl(request).await {
+            Ok(output) => {
+                if !output.is_empty() {
+                    println!("{output}");
+                }
+                process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    let log_buffer = LogBuffer::default();
+    let logging_error = logging::init(Config::get(), log_buffer.clone()).err();
 
     info!("booting up");
-    let args: Vec<String> = env::args().collect();
-    let path = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        std::env::var("HOME").unwrap_or(".".to_string())
-    };
-    let mut app = App::new(path);
+    let mut app = App::new(log_buffer);
+    if let Some(err) = logging_error {
+        app.notify_error(&err);
+    }
+    for warning in config_warnings {
+        warn!("{warning}");
+        app.notification_handler
+            .push(NotificationLevel::Warning, warning);
+    }
     let res = app.run().await;
+    if let Some(socket) = &app.ipc_socket {
+        let _ = fs::remove_file(socket);
+    }
     if res.is_err() {
         error!("Error: {}", res.err().unwrap());
         process::exit(1);
@@ -56,6 +161,12 @@ async fn main() -> Result<(), std::io::Error> {
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum CurrentScreen {
     Main(FocusedWindowMain),
+    /// Help popup on top of the main screen with the given focus
+    Help(FocusedWindowMain),
+    /// `:` prompt in the controls bar, the main screen keeps the given focus
+    Command(FocusedWindowMain),
+    /// Listening stats popup on top of the main screen with the given focus
+    Stats(FocusedWindowMain),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -69,6 +180,38 @@ pub enum FocusedWindowMain {
 pub enum MediaDisplayType {
     Songs,
     Folders,
+    Playlists,
+    Search,
+    Lyrics,
+    Log,
+    History,
+}
+
+impl MediaDisplayType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "songs" => Some(MediaDisplayType::Songs),
+            "folders" => Some(MediaDisplayType::Folders),
+            "playlists" => Some(MediaDisplayType::Playlists),
+            "search" => Some(MediaDisplayType::Search),
+            "lyrics" => Some(MediaDisplayType::Lyrics),
+            "log" => Some(MediaDisplayType::Log),
+            "history" => Some(MediaDisplayType::History),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MediaDisplayType::Songs => "Songs",
+            MediaDisplayType::Folders => "Folders",
+            MediaDisplayType::Playlists => "Playlists",
+            MediaDisplayType::Search => "Search",
+            MediaDisplayType::Lyrics => "Lyrics",
+            MediaDisplayType::Log => "Log",
+            MediaDisplayType::History => "History",
+        }
+    }
 }
 
 struct App {
@@ -76,6 +219,7 @@ struct App {
     songs: HashMap<String, Song>,
     queue_shown: bool,
     folder_handler: FolderHandler,
+    playlist_handler: PlaylistHandler,
     select_handler: SelectHandler<Song>,
     queue_select_handler: SelectHandler<Song>,
     file_finder: FileFinder,
@@ -83,128 +227,367 @@ struct App {
     current_screen: CurrentScreen,
     selected_media_display_type: MediaDisplayType,
     search_handler: SearchHandler,
+    keyboard_handler: KeyboardHandler,
+    /// Shown in place of the key hints until the next key press
+    status_message: Option<String>,
+    notification_handler: NotificationHandler,
+    help_handler: HelpHandler,
+    command_line: CommandLine,
+    lyrics_handler: Arc<Mutex<LyricsHandler>>,
+    log_handler: LogHandler,
+    history_handler: HistoryHandler,
+    /// Where songs were queued from by location, for the history
+    song_sources: HashMap<String, &'static str>,
+    /// Start of the history entry Previous last went back to
+    history_rewind: Option<u64>,
+    stats_period: Period,
+    /// Computed when the Stats screen opens or changes period
+    stats: Option<Stats>,
+    /// `None` when no scrobbling service is configured
+    scrobbler: Option<UnboundedSender<ScrobbleEvent>>,
+    /// Location of the song last sent as now playing, until it finishes
+    now_playing: Option<String>,
+    /// Player events as JSON lines for IPC subscribers
+    ipc_events: broadcast::Sender<String>,
+    /// Set while this instance listens on the socket, removed on exit
+    ipc_socket: Option<PathBuf>,
+    cover_art_requested: HashSet<String>,
+    album_art: Option<TerminalImage>,
+    show_remaining_time: bool,
+    progress_bar_rect: Option<Rect>,
+    seeking_with_mouse: bool,
+    /// Where the panes were last drawn, for mouse clicks and paging
+    search_rect: Rect,
+    media_list_rect: Rect,
+    media_list_offset: usize,
+    media_list_len: usize,
+    queue_list_rect: Rect,
+    last_click: Option<(Instant, FocusedWindowMain, usize)>,
     player_tx: Sender<PlayerReceiveEvent>,
-    event_rx: Receiver<ApplicationEvent>,
+    event_tx: UnboundedSender<ApplicationEvent>,
+    event_rx: UnboundedReceiver<ApplicationEvent>,
 }
 
 impl App {
-    fn new(path: String) -> Self {
+    fn new(log_buffer: LogBuffer) -> Self {
+        let config = Config::get();
         let (player_tx, player_rx) = channel::<PlayerReceiveEvent>();
-        let (event_tx, event_rx) = channel::<ApplicationEvent>();
+        let (event_tx, event_rx) = unbounded_channel::<ApplicationEvent>();
+        let (keymap, keymap_problems) = config.keymap.build();
+        let mut notification_handler = NotificationHandler::new();
+        for problem in &keymap_problems {
+            warn!("Keymap: {problem}");
+            notification_handler.push(NotificationLevel::Warning, format!("Keymap: {problem}"));
+        }
+        let musicbrainz_client = match MusicBrainzClient::new(config) {
+            Ok(client) => Some(Arc::new(client)),
+            Err(err) => {
+                let err = AppError::MusicBrainzUnavailable(err.to_string());
+                error!("{err}");
+                notification_handler.push(NotificationLevel::Error, err.to_string());
+                None
+            }
+        };
         App::create_threads(event_tx.clone(), player_rx);
 
-        let mut file_finder = FileFinder::new(
-            [".mp3".to_string(), ".ogg".to_string(), ".wav".to_string()],
-            path,
-            Some(2),
+        let file_finder = FileFinder::new(
+            config.extensions.clone(),
+            config
+                .library_roots
+                .first()
+                .map(|root| root.to_string_lossy().to_string())
+                .unwrap_or_else(|| ".".to_string()),
+            Some(config.scan_depth),
         );
-
-        file_finder.find_paths(None, None);
-        let mut folder_handler =
+        let mut player_information = PlayerInformation::default();
+        if let Some(volume) = config.volume {
+            player_information.volume = volume;
+            let _ = player_tx.send(PlayerReceiveEvent::SetVolume(volume));
+        }
+        let folder_handler =
             FolderHandler::new(Folder::new("root".to_string(), PathBuf::from("root")));
-        folder_handler.insert_songs(file_finder.songs.to_owned());
+        let mut playlist_handler = PlaylistHandler::new();
+        playlist_handler.load(&[]);
         App {
             exit: false,
             songs: HashMap::new(),
             queue_shown: true,
             folder_handler: folder_handler,
+            playlist_handler,
             select_handler: SelectHandler::new(),
             queue_select_handler: SelectHandler::new(),
             file_finder: file_finder,
-            player_information: PlayerInformation::default(),
+            player_information,
             current_screen: CurrentScreen::Main(FocusedWindowMain::Media),
-            selected_media_display_type: MediaDisplayType::Folders,
-            search_handler: SearchHandler::new(),
+            selected_media_display_type: config.default_view,
+            search_handler: SearchHandler::new(musicbrainz_client, event_tx.clone()),
+            keyboard_handler: KeyboardHandler::new(keymap),
+            status_message: None,
+            notification_handler,
+            help_handler: HelpHandler::new(),
+            command_line: CommandLine::new(),
+            lyrics_handler: Arc::new(Mutex::new(LyricsHandler::new())),
+            log_handler: LogHandler::new(log_buffer),
+            history_handler: HistoryHandler::new(),
+            song_sources: HashMap::new(),
+            history_rewind: None,
+            stats_period: Period::default(),
+            stats: None,
+            scrobbler: scrobbler::spawn(),
+            now_playing: None,
+            ipc_events: ipc::event_channel(),
+            ipc_socket: None,
+            cover_art_requested: HashSet::new(),
+            album_art: config
+                .album_art
+                .then(|| TerminalImage::new(GraphicsProtocol::detect())),
+            show_remaining_time: false,
+            progress_bar_rect: None,
+            seeking_with_mouse: false,
+            search_rect: Rect::default(),
+            media_list_rect: Rect::default(),
+            media_list_offset: 0,
+            media_list_len: 0,
+            queue_list_rect: Rect::default(),
+            last_click: None,
             player_tx,
+            event_tx,
             event_rx,
         }
     }
 
     async fn run(&mut self) -> Result<(), std::io::Error> {
         let mut terminal = ratatui::init();
-        {
-            let songs_vec = self.file_finder.create_songs();
-            for song in songs_vec {
-                self.songs.insert(
-                    format!(
-                        "{}-{}",
-                        song.title.clone(),
-                        song.artist.clone().unwrap_or("".to_string())
-                    ),
-                    song.clone(),
-                );
+        install_panic_hook();
+        let result = self.event_loop(&mut terminal).await;
+        let _ = execute!(stdout(), DisableMouseCapture);
+        ratatui::restore();
+        result
+    }
+
+    async fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<(), std::io::Error> {
+        execute!(stdout(), EnableMouseCapture)?;
+        self.scan_library();
+        self.start_ipc().await;
+        let mut input = EventStream::new();
+        let mut tick = tokio::time::interval(TICK_RATE);
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut redraw = true;
+        while !self.exit {
+            if redraw {
+                let _ = terminal.draw(|frame| {
+                    ui::render(frame, self);
+                });
+                if let Some(album_art) = &mut self.album_art
+                    && let Err(err) = album_art.flush()
+                {
+                    warn!("Failed to draw album art: {err}");
+                }
             }
+            redraw = tokio::select! {
+                Some(event) = input.next() => match event {
+                    Ok(Event::Key(key_event)) if key_event.kind != KeyEventKind::Release => {
+                        self.key_event(key_event).await;
+                        true
+                    }
+                    Ok(Event::Mouse(mouse_event)) => {
+                        self.mouse_event(mouse_event).await;
+                        true
+                    }
+                    Ok(Event::Resize(_, _)) => true,
+                    Ok(_) => false,
+                    Err(err) => return Err(err),
+                },
+                Some(event) = self.event_rx.recv() => {
+                    self.application_event(event);
+                    true
+                }
+                _ = tick.tick() => {
+                    let contexts = self.key_contexts();
+                    let timed_out = self.keyboard_handler.timeout(&contexts);
+                    if let Some(key_press) = timed_out {
+                        self.key_press(key_press).await;
+                    }
+                    timed_out.is_some()
+                        || matches!(self.player_information.status, PlayerStatus::Playing(_))
+                        || !self.notification_handler.is_empty()
+                        || (self.selected_media_display_type == MediaDisplayType::Log
+                            && self.log_handler.refresh())
+                }
+            };
         }
-        self.select_handler
-            .set_items(self.songs.values().cloned().collect());
-        let _ = terminal.draw(|frame| {
-            ui::render(frame, self);
-        });
-        loop {
-            if self.exit {
-                break;
-            }
-            let _ = terminal.draw(|frame| {
-                ui::render(frame, self);
-            });
-            if let Ok(event) = self.event_rx.try_recv() {
-                match event {
-                    ApplicationEvent::Action(action) => match action {
-                        Action::Quit => self.exit = true,
-
-                        _ => match &self.current_screen {
-                            CurrentScreen::Main(_) => self.main_screen_events(action).await,
-                        },
-                    },
-                    ApplicationEvent::PlayerEvent(event) => match event {
-                        PlayerSendEvent::Play(playing_index) => {
-                            self.player_information.playing_index = Some(playing_index);
-                            if let Some(song) = self.get_current_song() {
-                                self.player_information.status =
-                                    PlayerStatus::Playing(song.clone());
-                            }
-                        }
-                        PlayerSendEvent::Pause(playing_index) => {
-                            self.player_information.playing_index = Some(playing_index);
-                            if let Some(song) = self.get_current_song() {
-                                self.player_information.status =
-                                    PlayerStatus::Playing(song.clone());
-                            }
-                        }
-                        PlayerSendEvent::TimeChanged(passed_time) => {
-                            self.player_information.passed_time = passed_time;
-                        }
-                        PlayerSendEvent::QueueUpdate(queue) => {
-                            self.player_information.queue = queue.clone();
-                            self.queue_select_handler.set_items(queue);
-                        }
-                        PlayerSendEvent::NextSong => {
-                            self.player_information.playing_index =
-                                self.player_information.playing_index.map(|index| index + 1);
-                            if let Some(song) = self.get_current_song() {
-                                self.player_information.status =
-                                    PlayerStatus::Playing(song.clone());
-                            }
-                        }
-                        PlayerSendEvent::PlayerEnded => {
-                            self.player_information.playing_index = None;
-                            self.player_information.status = PlayerStatus::NoAudioSelected;
-                        }
-                        PlayerSendEvent::PlayerInformation(player_information) => {
-                            self.player_information = player_information;
-                        }
-                    },
+        Ok(())
+    }
+
+    async fn key_event(&mut self, key_event: KeyEvent) {
+        self.status_message = None;
+        let contexts = self.key_contexts();
+        if let Some(key_press) = self.keyboard_handler.resolve(key_event, &contexts) {
+            self.key_press(key_press).await;
+        }
+    }
+
+    fn application_event(&mut self, event: ApplicationEvent) {
+        if let ApplicationEvent::PlayerEvent(event) = &event {
+            self.publish_player_event(event);
+        }
+        match event {
+            ApplicationEvent::PlayerEvent(event) => match event {
+                PlayerSendEvent::Play(playing_index) => {
+                    self.player_information.playing_index = Some(playing_index);
+                    if let Some(song) = self.get_current_song() {
+                        self.player_information.status = PlayerStatus::Playing(song.clone());
+                    }
+                    self.request_cover_art();
+                    self.request_lyrics();
+                    self.update_now_playing();
+                }
+                PlayerSendEvent::Pause(playing_index) => {
+                    self.player_information.playing_index = Some(playing_index);
+                    if let Some(song) = self.get_current_song() {
+                        self.player_information.status = PlayerStatus::Paused(song.clone());
+                    }
+                }
+                PlayerSendEvent::TimeChanged(passed_time) => {
+                    self.player_information.passed_time = passed_time;
+                    if let Ok(lyrics_handler) = &mut self.lyrics_handler.lock() {
+                        lyrics_handler.update_position(passed_time);
+                    }
+                }
+                PlayerSendEvent::QueueUpdate(queue) => {
+                    self.player_information.queue = queue.clone();
+                    self.queue_select_handler.set_items(queue);
+                }
+                PlayerSendEvent::NextSong => {
+                    self.player_information.playing_index =
+                        self.player_information.playing_index.map(|index| index + 1);
+                    if let Some(song) = self.get_current_song() {
+                        self.player_information.status = PlayerStatus::Playing(song.clone());
+                    }
+                    self.update_now_playing();
                 }
+                PlayerSendEvent::PlayerEnded => {
+                    self.player_information.playing_index = None;
+                    self.player_information.status = PlayerStatus::NoAudioSelected;
+                    if let Ok(lyrics_handler) = &mut self.lyrics_handler.lock() {
+                        lyrics_handler.clear();
+                    }
+                }
+                PlayerSendEvent::PlayerInformation(player_information) => {
+                    self.player_information = *player_information;
+                }
+                PlayerSendEvent::Listened {
+                    song,
+                    listened,
+                    length,
+                } => {
+                    let threshold = Config::get().play_threshold;
+                    let played =
+                        LibraryDb::get_mut().record_listen(&song, listened, length, threshold);
+                    library_db::save();
+                    let source = song
+                        .location()
+                        .and_then(|location| self.song_sources.get(&location).copied())
+                        .unwrap_or("Queue");
+                    let entry =
+                        HistoryEntry::new(&song, listened, length, played, source.to_string());
+                    self.now_playing = None;
+                    if should_scrobble(listened, length)
+                        && let Some(listen) = Listen::new(&song, entry.started, length)
+                    {
+                        self.send_to_scrobbler(ScrobbleEvent::Scrobble(listen));
+                    }
+                    if let Err(err) = self.history_handler.add(entry) {
+                        warn!("{err}");
+                    }
+                    if played && let Some(stats) = LibraryDb::get().stats(&song).cloned() {
+                        write_stat_tags(vec![(*song, stats)]);
+                    }
+                    self.playlist_handler
+                        .refresh_smart(self.select_handler.items());
+                }
+                PlayerSendEvent::Error(err) => self.notify_error(&err),
+            },
+            ApplicationEvent::LibraryScanned(songs, playlist_files) => {
+                self.folder_handler
+                    .insert_songs(songs.clone(), playlist_files);
+                for song in songs {
+                    self.songs.insert(
+                        format!(
+                            "{}-{}",
+                            song.title.clone(),
+                            song.artist.clone().unwrap_or("".to_string())
+                        ),
+                        song,
+                    );
+                }
+                self.select_handler
+                    .set_items(self.songs.values().cloned().collect());
+                if LibraryDb::get_mut().register(self.select_handler.items()) {
+                    library_db::save();
+                }
+                self.playlist_handler.load(self.select_handler.items());
+                // Commands like sort need the library, so the rc file waits for it
+                self.run_rc_file();
+            }
+            ApplicationEvent::Redraw => {}
+            ApplicationEvent::Error(err) => self.notify_error(&err),
+            ApplicationEvent::IpcRequest(request, reply) => {
+                let _ = reply.send(self.ipc_request(request));
             }
-            thread::sleep(Duration::from_millis(5));
         }
-        ratatui::restore();
-        Ok(())
     }
 
-    async fn main_screen_events(&mut self, action: Action) {
+    /// Reads tags of the whole library on a blocking thread, the ui starts out empty.
+    fn scan_library(&self) {
+        let mut file_finder = self.file_finder.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            for root in &Config::get().library_roots {
+                if let Err(err) = file_finder.find_paths(Some(root), None) {
+                    let _ = event_tx.send(ApplicationEvent::Error(err));
+                    return;
+                }
+            }
+            let playlist_files = file_finder
+                .playlist_paths
+                .iter()
+                .filter_map(|path| {
+                    let songs = playlist_file::read(path, &file_finder.songs)
+                        .inspect_err(|err| warn!("{err}"))
+                        .ok()?;
+                    Some(PlaylistFile {
+                        path: path.clone(),
+                        songs,
+                    })
+                })
+                .collect();
+            let _ = event_tx.send(ApplicationEvent::LibraryScanned(
+                file_finder.songs,
+                playlist_files,
+            ));
+        });
+    }
+
+    async fn key_press(&mut self, key_press: KeyPress) {
+        match key_press.action {
+            Action::Quit => self.exit = true,
+            _ => match &self.current_screen {
+                CurrentScreen::Main(_) => self.main_screen_events(key_press).await,
+                CurrentScreen::Help(_) => self.help_screen_events(key_press),
+                CurrentScreen::Command(_) => self.command_line_events(key_press),
+                CurrentScreen::Stats(_) => self.stats_screen_events(key_press),
+            },
+        }
+    }
+
+    async fn main_screen_events(&mut self, key_press: KeyPress) {
+        let action = key_press.action;
         let focused_window = match &self.current_screen {
             CurrentScreen::Main(focused_window) => focused_window,
+            CurrentScreen::Help(_) | CurrentScreen::Command(_) | CurrentScreen::Stats(_) => {
+                return;
+            }
         };
         match action {
             Action::SwitchWindow => {
@@ -214,28 +597,32 @@ impl App {
                     _ => FocusedWindowMain::Media,
                 })
             }
-            Action::MoveUp => match focused_window {
-                FocusedWindowMain::Queue => self.queue_select_handler.up(),
-                FocusedWindowMain::Media => match self.selected_media_display_type {
-                    MediaDisplayType::Folders => self.folder_handler.select_handler_up(),
-                    MediaDisplayType::Songs => self.select_handler.up(),
-                },
-                _ => {}
-            },
-            Action::MoveDown => match focused_window {
-                FocusedWindowMain::Queue => self.queue_select_handler.down(),
-                FocusedWindowMain::Media => match self.selected_media_display_type {
-                    MediaDisplayType::Folders => self.folder_handler.select_handler_down(),
-                    MediaDisplayType::Songs => self.select_handler.down(),
-                },
-                _ => {}
-            },
+            Action::MoveUp => self.move_selection(Movement::Up(key_press.repeat())),
+            Action::MoveDown => self.move_selection(Movement::Down(key_press.repeat())),
+            Action::PageUp => {
+                self.move_selection(Movement::Up(self.page_size() * key_press.repeat()))
+            }
+            Action::PageDown => {
+                self.move_selection(Movement::Down(self.page_size() * key_press.repeat()))
+            }
+            Action::HalfPageUp => {
+                self.move_selection(Movement::Up(self.page_size() / 2 * key_press.repeat()))
+            }
+            Action::HalfPageDown => {
+                self.move_selection(Movement::Down(self.page_size() / 2 * key_press.repeat()))
+            }
+            // With a count both jump to that line, like in vim
+            Action::MoveToTop | Action::MoveToBottom => {
+                self.move_selection(match (key_press.count, action) {
+                    (Some(line), _) => Movement::To(line.saturating_sub(1)),
+                    (None, Action::MoveToTop) => Movement::First,
+                    _ => Movement::Last,
+                })
+            }
             Action::Select => match focused_window {
                 FocusedWindowMain::Queue => {
                     if let Some(index) = self.queue_select_handler.state().selected() {
-                        self.player_tx
-                            .send(PlayerReceiveEvent::SetAndPlaySong(index))
-                            .expect("Failed to set song");
+                        self.send_to_player(PlayerReceiveEvent::SetAndPlaySong(index));
                     }
                 }
                 FocusedWindowMain::Media => match self.selected_media_display_type {
@@ -244,125 +631,1028 @@ impl App {
                             let (queue1, queue2) = self.select_handler.items().split_at(index);
                             let mut queue2 = queue2.to_vec();
                             queue2.append(&mut queue1.to_vec());
-                            self.player_tx
-                                .send(PlayerReceiveEvent::CreateQueueAndPlay(queue2))
-                                .expect("Failed to send song to player");
+                            self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(queue2));
                         }
                     }
                     MediaDisplayType::Folders => {
                         let song = self.folder_handler.select_handler_select();
                         if let Some(song) = song {
-                            self.player_tx
-                                .send(PlayerReceiveEvent::CreateQueueAndPlay(vec![song]))
-                                .expect("Failed to send song to player");
+                            self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(vec![song]));
+                        }
+                    }
+                    MediaDisplayType::Playlists => {
+                        if let Some((songs, index)) = self.playlist_handler.open_selected() {
+                            let (queue1, queue2) = songs.split_at(index);
+                            let mut queue2 = queue2.to_vec();
+                            queue2.append(&mut queue1.to_vec());
+                            self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(queue2));
+                        }
+                    }
+                    MediaDisplayType::Search => {
+                        let library = self.songs.values().cloned().collect();
+                        if self.search_handler.open_selected(library).is_some()
+                            && let Some((songs, index)) = self.search_handler.songs_from_selected()
+                        {
+                            let (queue1, queue2) = songs.split_at(index);
+                            let mut queue2 = queue2.to_vec();
+                            queue2.append(&mut queue1.to_vec());
+                            self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(queue2));
                         }
                     }
+                    MediaDisplayType::History => {
+                        let songs = self
+                            .history_handler
+                            .selected_songs(self.select_handler.items());
+                        if !songs.is_empty() {
+                            self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(songs));
+                        }
+                    }
+                    MediaDisplayType::Lyrics | MediaDisplayType::Log => {}
                 },
-                FocusedWindowMain::Search => {
-                    let _ = self.search_handler.search();
-                    self.current_screen = CurrentScreen::Main(FocusedWindowMain::Media);
-                }
+                _ => {}
             },
+            Action::SeekToLine => {
+                let time = self
+                    .lyrics_handler
+                    .lock()
+                    .ok()
+                    .and_then(|mut lyrics_handler| lyrics_handler.select_handler_select());
+                if let Some(time) = time {
+                    self.send_to_player(PlayerReceiveEvent::Seek(time));
+                }
+            }
+            Action::FocusSearch => {
+                self.current_screen = CurrentScreen::Main(FocusedWindowMain::Search);
+            }
+            Action::SubmitSearch => {
+                let _ = self.search_handler.search();
+                self.selected_media_display_type = MediaDisplayType::Search;
+                self.current_screen = CurrentScreen::Main(FocusedWindowMain::Media);
+            }
+            Action::LeaveSearch => {
+                self.current_screen = CurrentScreen::Main(FocusedWindowMain::Media);
+            }
             Action::Char(char) => {
-                match focused_window {
-                    FocusedWindowMain::Search => {
-                        self.search_handler.add_char_to_query(char);
-                    }
-                    FocusedWindowMain::Media => {
-                        match char {
-                            '1' => self.selected_media_display_type = MediaDisplayType::Songs,
-                            '2' => self.selected_media_display_type = MediaDisplayType::Folders,
-                            _ => {}
-                        }
-                        match self.selected_media_display_type {
-                            MediaDisplayType::Folders => {
-                                if char == 'a' {
-                                    if let Some(song) =
-                                        self.folder_handler.select_handler_selected()
-                                    {
-                                        match song {
-                                            Node::Folder(folder) => {
-                                                let queue: Vec<Song> = folder
-                                                    .get_children()
-                                                    .iter()
-                                                    .filter_map(|child| match child {
-                                                        Node::Song(song) => Some(song.to_owned()),
-                                                        _ => None,
-                                                    })
-                                                    .collect();
-                                                info!("Queue created!");
-                                                self.player_tx
-                                                    .send(
-                                                        PlayerReceiveEvent::AddSongsToQueueAndPlay(
-                                                            queue,
-                                                        ),
-                                                    )
-                                                    .expect("Failed to send songs to player");
-                                                info!("Playing queue!");
-                                            }
-                                            Node::Song(song) => self
-                                                .player_tx
-                                                .send(PlayerReceiveEvent::AddSongsToQueueAndPlay(
-                                                    vec![song.to_owned()],
-                                                ))
-                                                .expect("Failed to send song to player"),
-                                        };
-                                    }
-                                }
+                self.search_handler.add_char_to_query(char);
+            }
+            Action::DeleteChar => {
+                self.search_handler.remove_last_char();
+            }
+            Action::ShowSongs => self.selected_media_display_type = MediaDisplayType::Songs,
+            Action::ShowFolders => self.selected_media_display_type = MediaDisplayType::Folders,
+            Action::ShowPlaylists => self.selected_media_display_type = MediaDisplayType::Playlists,
+            Action::ShowSearch => self.selected_media_display_type = MediaDisplayType::Search,
+            Action::ShowLyrics => self.selected_media_display_type = MediaDisplayType::Lyrics,
+            Action::ShowLog => {
+                self.log_handler.refresh();
+                self.selected_media_display_type = MediaDisplayType::Log;
+            }
+            Action::ShowHistory => self.selected_media_display_type = MediaDisplayType::History,
+            Action::CycleLogLevel => self.log_handler.cycle_level(),
+            Action::EnqueueSelected => match self.selected_media_display_type {
+                MediaDisplayType::Folders => {
+                    if let Some(song) = self.folder_handler.select_handler_selected() {
+                        match song {
+                            Node::Folder(folder) | Node::Playlist(folder) => {
+                                let queue: Vec<Song> = folder
+                                    .get_children()
+                                    .iter()
+                                    .filter_map(|child| match child {
+                                        Node::Song(song) => Some(song.to_owned()),
+                                        _ => None,
+                                    })
+                                    .collect();
+                                info!("Queue created!");
+                                self.send_to_player(PlayerReceiveEvent::AddSongsToQueueAndPlay(
+                                    queue,
+                                ));
+                                info!("Playing queue!");
                             }
-                            _ => {}
+                            Node::Song(song) => self.send_to_player(
+                                PlayerReceiveEvent::AddSongsToQueueAndPlay(vec![song.to_owned()]),
+                            ),
                         };
                     }
-                    _ => {}
                 }
-                if !matches!(focused_window, FocusedWindowMain::Search) {
-                    if char == 'f' {
-                        self.current_screen = CurrentScreen::Main(FocusedWindowMain::Search);
+                MediaDisplayType::Search => {
+                    let queue = self.search_handler.songs_to_enqueue();
+                    if !queue.is_empty() {
+                        self.send_to_player(PlayerReceiveEvent::AddSongsToQueueAndPlay(queue));
                     }
                 }
-            }
-            Action::Backspace => match focused_window {
-                FocusedWindowMain::Search => {
-                    self.search_handler.remove_last_char();
+                MediaDisplayType::Playlists => {
+                    let queue = self.playlist_handler.selected_songs();
+                    if !queue.is_empty() {
+                        self.send_to_player(PlayerReceiveEvent::AddSongsToQueueAndPlay(queue));
+                    }
                 }
-                FocusedWindowMain::Media => match self.selected_media_display_type {
-                    MediaDisplayType::Folders => {
-                        self.folder_handler.go_to_parent();
+                MediaDisplayType::History => {
+                    let queue = self
+                        .history_handler
+                        .selected_songs(self.select_handler.items());
+                    if !queue.is_empty() {
+                        self.send_to_player(PlayerReceiveEvent::AddSongsToQueueAndPlay(queue));
                     }
-                    _ => {}
-                },
-                _ => {}
-            },
-            Action::Esc => match focused_window {
-                FocusedWindowMain::Search => {
-                    self.current_screen = CurrentScreen::Main(FocusedWindowMain::Media);
                 }
                 _ => {}
             },
-            Action::Space => match focused_window {
-                FocusedWindowMain::Search => {
-                    self.search_handler.add_char_to_query(' ');
+            Action::OpenArtist => self.search_handler.open_artist(),
+            Action::Download => self.search_handler.download(),
+            Action::GoToParent => match self.selected_media_display_type {
+                MediaDisplayType::Folders => {
+                    self.folder_handler.go_to_parent();
                 }
-                _ => {
-                    self.player_tx
-                        .send(PlayerReceiveEvent::TogglePause)
-                        .expect("Failed to toggle pause");
+                MediaDisplayType::Search => {
+                    self.search_handler.go_to_parent();
                 }
+                MediaDisplayType::Playlists => self.playlist_handler.go_to_parent(),
+                _ => {}
             },
+            Action::AddToPlaylist => self.open_command_line_with("playlist-add "),
+            Action::RateUp => self.run_command_from_key(Command::Rate(Rating::Up)),
+            Action::RateDown => self.run_command_from_key(Command::Rate(Rating::Down)),
+            Action::ToggleFavorite => self.run_command_from_key(Command::Favorite),
+            Action::NewPlaylist => self.open_command_line_with("playlist-new "),
+            Action::RenamePlaylist => self.open_command_line_with("playlist-rename "),
+            Action::DeletePlaylist => {
+                if let Some(name) = self.playlist_handler.selected_name() {
+                    self.open_command_line_with(&format!("playlist-delete {name}"));
+                }
+            }
+            Action::ShowHelp => {
+                self.help_handler.reset();
+                self.current_screen = CurrentScreen::Help(*focused_window);
+            }
+            Action::ShowStats => {
+                self.current_screen = CurrentScreen::Stats(*focused_window);
+                self.update_stats();
+            }
+            Action::OpenCommandLine => {
+                self.command_line.open();
+                self.current_screen = CurrentScreen::Command(*focused_window);
+            }
+            Action::ToggleRemainingTime => {
+                self.show_remaining_time = !self.show_remaining_time;
+            }
+            Action::TogglePause => {
+                self.send_to_player(PlayerReceiveEvent::TogglePause);
+            }
             Action::PreviousSong => {
-                self.player_tx
-                    .send(PlayerReceiveEvent::Previous)
-                    .expect("Failed to send previous song to player");
+                if self.player_information.playing_index == Some(0)
+                    && Config::get().history_fallback
+                {
+                    self.previous_from_history();
+                    return;
+                }
+                for _ in 0..key_press.repeat() {
+                    self.send_to_player(PlayerReceiveEvent::Previous);
+                }
             }
             Action::NextSong => {
-                self.player_tx
-                    .send(PlayerReceiveEvent::Next)
-                    .expect("Failed to send next song to player");
+                for _ in 0..key_press.repeat() {
+                    self.send_to_player(PlayerReceiveEvent::Next);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn move_selection(&mut self, movement: Movement) {
+        if let CurrentScreen::Main(focused_window) = self.current_screen {
+            self.move_selection_in(focused_window, movement);
+        }
+    }
+
+    fn move_selection_in(&mut self, window: FocusedWindowMain, movement: Movement) {
+        match window {
+            FocusedWindowMain::Queue => self.queue_select_handler.move_selection(movement),
+            FocusedWindowMain::Media => match self.selected_media_display_type {
+                MediaDisplayType::Folders => self.folder_handler.select_handler_move(movement),
+                MediaDisplayType::Songs => self.select_handler.move_selection(movement),
+                MediaDisplayType::Playlists => self.playlist_handler.select_handler_move(movement),
+                MediaDisplayType::Search => self.search_handler.select_handler_move(movement),
+                MediaDisplayType::Lyrics => {
+                    if let Ok(lyrics_handler) = &mut self.lyrics_handler.lock() {
+                        lyrics_handler.select_handler_move(movement);
+                    }
+                }
+                MediaDisplayType::Log => self.log_handler.select_handler_move(movement),
+                MediaDisplayType::History => self.history_handler.select_handler_move(movement),
+            },
+            _ => {}
+        }
+    }
+
+    /// Rows visible in the focused list, as last drawn.
+    fn page_size(&self) -> usize {
+        let height = match &self.current_screen {
+            CurrentScreen::Main(FocusedWindowMain::Queue) => self.queue_list_rect.height,
+            _ => self.media_list_rect.height,
+        };
+        (height as usize).max(2)
+    }
+
+    fn help_screen_events(&mut self, key_press: KeyPress) {
+        let page_height = self.help_handler.page_height();
+        match key_press.action {
+            Action::CloseHelp => {
+                if let CurrentScreen::Help(focused_window) = self.current_screen {
+                    self.current_screen = CurrentScreen::Main(focused_window);
+                }
             }
+            Action::MoveUp => self.help_handler.scroll_up(key_press.repeat()),
+            Action::MoveDown => self.help_handler.scroll_down(key_press.repeat()),
+            Action::PageUp => self.help_handler.scroll_up(page_height),
+            Action::PageDown => self.help_handler.scroll_down(page_height),
+            Action::Char(char) => self.help_handler.add_char_to_query(char),
+            Action::DeleteChar => self.help_handler.remove_last_char(),
             _ => {}
         }
     }
 
+    fn stats_screen_events(&mut self, key_press: KeyPress) {
+        match key_press.action {
+            Action::CloseStats => {
+                if let CurrentScreen::Stats(focused_window) = self.current_screen {
+                    self.current_screen = CurrentScreen::Main(focused_window);
+                    self.stats = None;
+                }
+            }
+            Action::CycleStatsPeriod => {
+                self.stats_period = self.stats_period.next();
+                self.update_stats();
+            }
+            _ => {}
+        }
+    }
+
+    fn update_stats(&mut self) {
+        self.stats = Some(Stats::compute(
+            self.history_handler.entries(),
+            self.select_handler.items(),
+            self.stats_period,
+        ));
+    }
+
+    fn command_line_events(&mut self, key_press: KeyPress) {
+        match key_press.action {
+            Action::RunCommand => {
+                let input = self.command_line.submit();
+                self.close_command_line();
+                if input.trim().is_empty() {
+                    return;
+                }
+                self.status_message = Some(
+                    match Command::parse(&input).and_then(|command| self.run_command(command)) {
+                        Ok(message) => message,
+                        Err(err) => err,
+                    },
+                );
+            }
+            Action::CloseCommandLine => self.close_command_line(),
+            Action::CompleteCommand => self.command_line.complete(),
+            Action::PreviousCommand => self.command_line.history_previous(),
+            Action::NextCommand => self.command_line.history_next(),
+            Action::Char(char) => self.command_line.add_char(char),
+            Action::DeleteChar => {
+                // Deleting past the start leaves the prompt, like in vim
+                if self.command_line.input().is_empty() {
+                    self.close_command_line();
+                } else {
+                    self.command_line.remove_last_char();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open_command_line_with(&mut self, input: &str) {
+        if let CurrentScreen::Main(focused_window) = self.current_screen {
+            self.command_line.open_with(input);
+            self.current_screen = CurrentScreen::Command(focused_window);
+        }
+    }
+
+    /// Songs under the selection of the focused pane. A folder or playlist stands for its songs.
+    fn selected_songs(&self) -> Vec<Song> {
+        let focused_window = match self.current_screen {
+            CurrentScreen::Main(focused_window)
+            | CurrentScreen::Help(focused_window)
+            | CurrentScreen::Command(focused_window)
+            | CurrentScreen::Stats(focused_window) => focused_window,
+        };
+        match (focused_window, self.selected_media_display_type) {
+            (FocusedWindowMain::Queue, _) => self
+                .queue_select_handler
+                .select()
+                .cloned()
+                .into_iter()
+                .collect(),
+            (FocusedWindowMain::Media, MediaDisplayType::Songs) => {
+                self.select_handler.select().cloned().into_iter().collect()
+            }
+            (FocusedWindowMain::Media, MediaDisplayType::Folders) => {
+                match self.folder_handler.select_handler_selected() {
+                    Some(Node::Folder(folder) | Node::Playlist(folder)) => folder
+                        .get_children()
+                        .iter()
+                        .filter_map(|child| match child {
+                            Node::Song(song) => Some(song.to_owned()),
+                            _ => None,
+                        })
+                        .collect(),
+                    Some(Node::Song(song)) => vec![song.to_owned()],
+                    None => Vec::new(),
+                }
+            }
+            (FocusedWindowMain::Media, MediaDisplayType::Playlists) => {
+                self.playlist_handler.selected_songs()
+            }
+            (FocusedWindowMain::Media, MediaDisplayType::Search) => {
+                self.search_handler.songs_to_enqueue()
+            }
+            (FocusedWindowMain::Media, MediaDisplayType::History) => self
+                .history_handler
+                .selected_songs(self.select_handler.items()),
+            _ => Vec::new(),
+        }
+    }
+
+    /// For keys that do the same as a command, the result shows in the status line.
+    fn run_command_from_key(&mut self, command: Command) {
+        self.status_message = Some(match self.run_command(command) {
+            Ok(message) => message,
+            Err(err) => err,
+        });
+    }
+
+    fn close_command_line(&mut self) {
+        if let CurrentScreen::Command(focused_window) = self.current_screen {
+            self.current_screen = CurrentScreen::Main(focused_window);
+        }
+    }
+
+    /// Runs the commands in the rc file, problems are shown as notifications.
+    fn run_rc_file(&mut self) {
+        let (commands, mut problems) = read_rc_file(&Config::get().rc_file);
+        for command in commands {
+            if let Err(err) = self.run_command(command) {
+                problems.push(err);
+            }
+        }
+        for problem in problems {
+            warn!("rc: {problem}");
+            self.notification_handler
+                .push(NotificationLevel::Warning, format!("rc: {problem}"));
+        }
+    }
+
+    /// Returns a message for the controls bar.
+    fn run_command(&mut self, command: Command) -> Result<String, String> {
+        match command {
+            Command::Add(path) => {
+                if !path.exists() {
+                    return Err(format!("No such file or folder: {}", path.display()));
+                }
+                let songs = self.file_finder.find_songs(&path).map_err(|err| {
+                    self.notify_error(&err);
+                    err.to_string()
+                })?;
+                if songs.is_empty() {
+                    return Err(format!("No songs in {}", path.display()));
+                }
+                let message = format!("Added {} songs to the queue", songs.len());
+                self.send_to_player(PlayerReceiveEvent::AddSongsToQueueAndPlay(songs));
+                Ok(message)
+            }
+            Command::Seek(position) => {
+                let song = self.get_current_song().ok_or("Nothing is playing")?;
+                let passed_time = self.player_information.passed_time;
+                let time = match position {
+                    SeekPosition::Absolute(time) => time,
+                    SeekPosition::Forward(time) => passed_time + time,
+                    SeekPosition::Backward(time) => passed_time.saturating_sub(time),
+                }
+                .min(song.total_time as u64);
+                self.player_information.passed_time = time;
+                self.send_to_player(PlayerReceiveEvent::Seek(time));
+                Ok(format!("Seeked to {}", format_ms_to_duration_string(time)))
+            }
+            Command::Volume(volume) => {
+                // Unknown until the player reports it, libvlc itself says -1 before playback
+                let current = match self.player_information.volume {
+                    volume if volume < 0 => 100,
+                    volume => volume,
+                };
+                let volume = match volume {
+                    Volume::Absolute(volume) => volume,
+                    Volume::Relative(step) => current + step,
+                }
+                .clamp(0, 100);
+                self.player_information.volume = volume;
+                self.send_to_player(PlayerReceiveEvent::SetVolume(volume));
+                Ok(format!("Volume {volume}%"))
+            }
+            Command::SavePlaylist(name) => {
+                let songs = self.player_information.queue.clone();
+                let count = songs.len();
+                self.playlist_handler.replace(&name, songs)?;
+                Ok(format!(
+                    "Saved {count} songs to {}",
+                    playlist_path(&name).display()
+                ))
+            }
+            Command::PlaylistNew(name) => {
+                self.playlist_handler.create(&name)?;
+                Ok(format!("Created playlist {name}"))
+            }
+            Command::PlaylistAdd(name) => {
+                let songs = self.selected_songs();
+                if songs.is_empty() {
+                    return Err("Nothing selected to add".to_string());
+                }
+                let count = songs.len();
+                self.playlist_handler.append(&name, songs)?;
+                Ok(format!("Added {count} songs to {name}"))
+            }
+            Command::PlaylistRename(new_name) => {
+                let name = self
+                    .playlist_handler
+                    .selected_name()
+                    .ok_or("Select a playlist in the Playlists view first")?;
+                self.playlist_handler.rename(&name, &new_name)?;
+                Ok(format!("Renamed {name} to {new_name}"))
+            }
+            Command::PlaylistDelete(name) => {
+                self.playlist_handler.delete(&name)?;
+                Ok(format!("Deleted playlist {name}"))
+            }
+            Command::PlaylistLoad(name) => {
+                let songs = self
+                    .playlist_handler
+                    .songs(&name)
+                    .ok_or(format!("No playlist named {name}"))?;
+                if songs.is_empty() {
+                    return Err(format!("{name} is empty"));
+                }
+                let message = format!("Playing {name}");
+                self.send_to_player(PlayerReceiveEvent::CreateQueueAndPlay(songs));
+                Ok(message)
+            }
+            Command::PlaylistImport(path) => {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .ok_or(format!("Not a playlist file: {}", path.display()))?;
+                if self.playlist_handler.songs(&name).is_some() {
+                    return Err(format!("Playlist {name} already exists"));
+                }
+                let songs = playlist_file::read(&path, self.select_handler.items())?;
+                let count = songs.len();
+                self.playlist_handler.replace(&name, songs)?;
+                Ok(format!("Imported {count} songs as {name}"))
+            }
+            Command::PlaylistExport(path, relative) => {
+                let name = self
+                    .playlist_handler
+                    .selected_name()
+                    .ok_or("Select a playlist in the Playlists view first")?;
+                let songs = self.playlist_handler.songs(&name).unwrap_or_default();
+                playlist_file::write(&path, &songs, relative)?;
+                Ok(format!("Exported {name} to {}", path.display()))
+            }
+            Command::SmartPlaylistNew(name) => {
+                let path = self.playlist_handler.create_smart(&name)?;
+                self.playlist_handler.load(self.select_handler.items());
+                Ok(format!("Edit {} to set the rules", path.display()))
+            }
+            Command::Rate(rating) => {
+                let songs = self.selected_songs();
+                if songs.is_empty() {
+                    return Err("Nothing selected to rate".to_string());
+                }
+                let mut db = LibraryDb::get_mut();
+                let mut rated = Vec::new();
+                for song in &songs {
+                    let current = db.stats(song).and_then(|stats| stats.rating);
+                    let new_rating = match rating {
+                        Rating::Set(rating) => Some(rating),
+                        Rating::Up => Some(current.map_or(1, |rating| (rating + 1).min(5))),
+                        Rating::Down => current.map(|rating| rating.saturating_sub(1)),
+                    };
+                    if let Some(stats) = db.set_rating(song, new_rating) {
+                        rated.push((song.clone(), stats));
+                    }
+                }
+                drop(db);
+                if rated.is_empty() {
+                    return Err("Only songs on disk can be rated".to_string());
+                }
+                library_db::save();
+                let message = match rated.as_slice() {
+                    [(song, stats)] => format!(
+                        "{} {}",
+                        song.title,
+                        stats.rating.map(stars).unwrap_or("unrated".to_string())
+                    ),
+                    _ => format!("Rated {} songs", rated.len()),
+                };
+                write_stat_tags(rated);
+                self.playlist_handler
+                    .refresh_smart(self.select_handler.items());
+                Ok(message)
+            }
+            Command::Favorite => {
+                let songs = self.selected_songs();
+                let mut db = LibraryDb::get_mut();
+                let favorites: Vec<bool> = songs
+                    .iter()
+                    .filter_map(|song| db.toggle_favorite(song))
+                    .collect();
+                drop(db);
+                if favorites.is_empty() {
+                    return Err("Select songs on disk to mark as favorites".to_string());
+                }
+                library_db::save();
+                self.playlist_handler
+                    .refresh_smart(self.select_handler.items());
+                Ok(match favorites.as_slice() {
+                    [true] => "Added to favorites".to_string(),
+                    [false] => "Removed from favorites".to_string(),
+                    _ => format!("Toggled {} favorites", favorites.len()),
+                })
+            }
+            Command::StatsExport(path) => {
+                let stats = Stats::compute(
+                    self.history_handler.entries(),
+                    self.select_handler.items(),
+                    self.stats_period,
+                );
+                stats.export(&path)?;
+                Ok(format!(
+                    "Exported stats for the {} to {}",
+                    self.stats_period.label(),
+                    path.display()
+                ))
+            }
+            Command::Shuffle(shuffle) => {
+                let shuffle = shuffle.unwrap_or(!self.player_information.shuffle);
+                self.player_information.shuffle = shuffle;
+                self.send_to_player(PlayerReceiveEvent::SetShuffle(shuffle));
+                Ok(format!("Shuffle {}", if shuffle { "on" } else { "off" }))
+            }
+            Command::Sort(sort_by, sort_order) => {
+                let mut songs = self.select_handler.items().clone();
+                sort_songs(&mut songs, &sort_by, &sort_order);
+                self.select_handler.set_items(songs);
+                self.selected_media_display_type = MediaDisplayType::Songs;
+                Ok("Songs sorted".to_string())
+            }
+            Command::Quit => {
+                self.exit = true;
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// Keymap contexts of the focused pane, most specific first.
+    fn key_contexts(&self) -> Vec<KeyContext> {
+        match self.current_screen {
+            CurrentScreen::Help(_) => vec![KeyContext::Help, KeyContext::Global],
+            CurrentScreen::Command(_) => vec![KeyContext::Command, KeyContext::Global],
+            CurrentScreen::Stats(_) => vec![KeyContext::Stats, KeyContext::Global],
+            CurrentScreen::Main(FocusedWindowMain::Search) => {
+                vec![KeyContext::Search, KeyContext::Global]
+            }
+            CurrentScreen::Main(FocusedWindowMain::Queue) => {
+                vec![KeyContext::Queue, KeyContext::Global]
+            }
+            CurrentScreen::Main(FocusedWindowMain::Media) => {
+                let display_context = match self.selected_media_display_type {
+                    MediaDisplayType::Songs => KeyContext::Songs,
+                    MediaDisplayType::Folders => KeyContext::Folders,
+                    MediaDisplayType::Playlists => KeyContext::Playlists,
+                    MediaDisplayType::Search => KeyContext::Browse,
+                    MediaDisplayType::Lyrics => KeyContext::Lyrics,
+                    MediaDisplayType::Log => KeyContext::Log,
+                    MediaDisplayType::History => KeyContext::History,
+                };
+                vec![display_context, KeyContext::Media, KeyContext::Global]
+            }
+        }
+    }
+
+    /// Clicking a pane focuses it and a list row selects it, a second click on the same row
+    /// plays it. The wheel scrolls the list under the pointer.
+    async fn mouse_event(&mut self, event: MouseEvent) {
+        if self.seek_with_mouse(event) {
+            return;
+        }
+        if let CurrentScreen::Help(_) = self.current_screen {
+            match event.kind {
+                MouseEventKind::ScrollUp => self.help_handler.scroll_up(MOUSE_SCROLL_LINES),
+                MouseEventKind::ScrollDown => self.help_handler.scroll_down(MOUSE_SCROLL_LINES),
+                _ => {}
+            }
+            return;
+        }
+        if !matches!(self.current_screen, CurrentScreen::Main(_)) {
+            return;
+        }
+        let position = Position::new(event.column, event.row);
+        let window = [
+            (FocusedWindowMain::Search, self.search_rect),
+            (FocusedWindowMain::Media, self.media_list_rect),
+            (FocusedWindowMain::Queue, self.queue_list_rect),
+        ]
+        .into_iter()
+        .find(|(_, rect)| rect.contains(position))
+        .map(|(window, _)| window);
+        let Some(window) = window else {
+            return;
+        };
+        match event.kind {
+            MouseEventKind::ScrollUp if window != FocusedWindowMain::Search => {
+                self.move_selection_in(window, Movement::Up(MOUSE_SCROLL_LINES))
+            }
+            MouseEventKind::ScrollDown if window != FocusedWindowMain::Search => {
+                self.move_selection_in(window, Movement::Down(MOUSE_SCROLL_LINES))
+            }
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.current_screen = CurrentScreen::Main(window);
+                let (rect, offset, len) = match window {
+                    FocusedWindowMain::Search => return,
+                    FocusedWindowMain::Media => (
+                        self.media_list_rect,
+                        self.media_list_offset,
+                        self.media_list_len,
+                    ),
+                    FocusedWindowMain::Queue => (
+                        self.queue_list_rect,
+                        self.queue_select_handler.state().offset(),
+                        self.queue_select_handler.items().len(),
+                    ),
+                };
+                let index = offset + (event.row - rect.y) as usize;
+                if index >= len {
+                    return;
+                }
+                self.move_selection_in(window, Movement::To(index));
+                let double_click = self
+                    .last_click
+                    .is_some_and(|(at, last_window, last_index)| {
+                        at.elapsed() < DOUBLE_CLICK_TIME
+                            && last_window == window
+                            && last_index == index
+                    });
+                if double_click {
+                    self.last_click = None;
+                    let action = match (window, self.selected_media_display_type) {
+                        (FocusedWindowMain::Media, MediaDisplayType::Lyrics) => Action::SeekToLine,
+                        _ => Action::Select,
+                    };
+                    self.main_screen_events(KeyPress {
+                        action,
+                        count: None,
+                    })
+                    .await;
+                } else {
+                    self.last_click = Some((Instant::now(), window, index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Clicking or dragging on the progress bar seeks to that position. Returns whether the
+    /// event belonged to the progress bar.
+    fn seek_with_mouse(&mut self, event: MouseEvent) -> bool {
+        let Some(rect) = self.progress_bar_rect else {
+            return false;
+        };
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.seeking_with_mouse =
+                    event.row == rect.y && event.column >= rect.x && event.column < rect.right();
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.seeking_with_mouse => {}
+            MouseEventKind::Up(MouseButton::Left) if self.seeking_with_mouse => {
+                self.seeking_with_mouse = false;
+                return true;
+            }
+            _ => return false,
+        }
+        if !self.seeking_with_mouse {
+            return false;
+        }
+        let Some(song) = self.get_current_song() else {
+            return true;
+        };
+        let column = event.column.clamp(rect.x, rect.right() - 1) - rect.x;
+        let ratio = column as f64 / (rect.width.max(2) - 1) as f64;
+        let time = (song.total_time as f64 * ratio) as u64;
+        self.player_information.passed_time = time;
+        self.send_to_player(PlayerReceiveEvent::Seek(time));
+        true
+    }
+
+    /// Fetches cover art from the Cover Art Archive for the current song if it has none.
+    fn request_cover_art(&mut self) {
+        let Some(song) = self.get_current_song() else {
+            return;
+        };
+        if song.cover_art.is_some() {
+            return;
+        }
+        let Some(release) = song.musicbrainz.release.clone() else {
+            return;
+        };
+        if !self.cover_art_requested.insert(release.clone()) {
+            return;
+        }
+        let player_tx = self.player_tx.clone();
+        tokio::spawn(async move {
+            match fetch_release_cover(&release).await {
+                Ok(path) => {
+                    let _ = player_tx.send(PlayerReceiveEvent::SetCoverArt { release, path });
+                }
+                Err(err) => info!("No cover art for release {release}: {err}"),
+            }
+        });
+    }
+
+    /// Loads lyrics for the current song: files next to it and its tags first, then the provider.
+    fn request_lyrics(&mut self) {
+        let Some(song) = self.get_current_song().cloned() else {
+            return;
+        };
+        let local_path = match &song.song_type {
+            SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => {
+                Some(path.clone())
+            }
+            _ => None,
+        };
+        let song_key = match &local_path {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => format!(
+                "{}-{}",
+                song.title,
+                song.artist.clone().unwrap_or("".to_string())
+            ),
+        };
+        {
+            let mut lyrics_handler = self
+                .lyrics_handler
+                .lock()
+                .expect("Lyrics handler lock poisoned");
+            if !lyrics_handler.start_loading(&song_key) {
+                return;
+            }
+        }
+
+        let lyrics_handler_arc = self.lyrics_handler.clone();
+        let event_tx = self.event_tx.clone();
+        let use_provider = Config::get().lyrics_provider;
+        tokio::spawn(async move {
+            let mut lyrics = local_path.as_deref().and_then(load_local);
+            if lyrics.is_none() && use_provider {
+                lyrics = match fetch_lyrics(&song).await {
+                    Ok(lyrics) => lyrics,
+                    Err(err) => {
+                        warn!("Failed to fetch lyrics for {}: {err}", song.title);
+                        None
+                    }
+                };
+            }
+            if let Ok(lyrics_handler) = &mut lyrics_handler_arc.lock() {
+                lyrics_handler.set_lyrics(&song_key, lyrics);
+            }
+            let _ = event_tx.send(ApplicationEvent::Redraw);
+        });
+    }
+
+    /// Before the first song of the queue comes the last one played before it. Going back
+    /// again continues from there.
+    fn previous_from_history(&mut self) {
+        let before = self
+            .history_rewind
+            .unwrap_or_else(|| now().saturating_sub(self.player_information.passed_time / 1000));
+        let Some(entry) = self.history_handler.last_before(before) else {
+            self.status_message = Some("Nothing earlier in the history".to_string());
+            return;
+        };
+        let started = entry.started;
+        let Some(song) = entry.song(self.select_handler.items()) else {
+            return;
+        };
+        if let Some(location) = song.location() {
+            self.song_sources
+                .insert(location, MediaDisplayType::History.label());
+        }
+        self.history_rewind = Some(started);
+        self.send_to_player(PlayerReceiveEvent::PrependAndPlay(Box::new(song)));
+    }
+
+    /// Listens on the IPC socket if enabled. Failing to is a warning, the app works without it.
+    async fn start_ipc(&mut self) {
+        let config = Config::get();
+        if !config.ipc_enabled {
+            return;
+        }
+        match ipc::bind(&config.ipc_socket).await {
+            Ok(listener) => {
+                ipc::spawn_server(listener, self.event_tx.clone(), self.ipc_events.clone());
+                self.ipc_socket = Some(config.ipc_socket.clone());
+            }
+            Err(err) => {
+                let message = format!("Remote control is off: {err}");
+                warn!("{message}");
+                self.notification_handler
+                    .push(NotificationLevel::Warning, message);
+            }
+        }
+    }
+
+    fn publish_player_event(&self, event: &PlayerSendEvent) {
+        // Time changes come several times a second, not worth serializing for nobody
+        if self.ipc_events.receiver_count() == 0 {
+            return;
+        }
+        if let Some(line) = ipc::event_line(event, &self.player_information.queue) {
+            let _ = self.ipc_events.send(line);
+        }
+    }
+
+    /// Player requests go the same way as keys and commands, with "Remote" as the source in
+    /// the history.
+    fn ipc_request(&mut self, request: Request) -> Result<Value, String> {
+        let event = match request {
+            Request::SetSong { index } => PlayerReceiveEvent::SetSong(index),
+            Request::SetAndPlaySong { index } => PlayerReceiveEvent::SetAndPlaySong(index),
+            Request::CreateQueueAndPlay { paths } => {
+                PlayerReceiveEvent::CreateQueueAndPlay(self.songs_at(&paths)?)
+            }
+            Request::AddSongsToQueueAndPlay { paths } => {
+                PlayerReceiveEvent::AddSongsToQueueAndPlay(self.songs_at(&paths)?)
+            }
+            Request::PrependAndPlay { path } => {
+                let song = self.songs_at(&[path])?.remove(0);
+                PlayerReceiveEvent::PrependAndPlay(Box::new(song))
+            }
+            Request::Play => PlayerReceiveEvent::Play,
+            Request::Next => PlayerReceiveEvent::Next,
+            Request::Previous => PlayerReceiveEvent::Previous,
+            Request::Pause => PlayerReceiveEvent::Pause,
+            Request::TogglePause => PlayerReceiveEvent::TogglePause,
+            Request::Update => PlayerReceiveEvent::Update,
+            Request::SetCoverArt { release, path } => {
+                PlayerReceiveEvent::SetCoverArt { release, path }
+            }
+            Request::Seek { position } => {
+                self.player_information.passed_time = position;
+                PlayerReceiveEvent::Seek(position)
+            }
+            Request::SetVolume { volume } => {
+                let volume = volume.clamp(0, 100);
+                self.player_information.volume = volume;
+                PlayerReceiveEvent::SetVolume(volume)
+            }
+            Request::SetShuffle { shuffle } => {
+                self.player_information.shuffle = shuffle;
+                PlayerReceiveEvent::SetShuffle(shuffle)
+            }
+            Request::Status => {
+                let (state, song) = match &self.player_information.status {
+                    PlayerStatus::Playing(song) => ("playing", Some(song)),
+                    PlayerStatus::Paused(song) => ("paused", Some(song)),
+                    PlayerStatus::NoAudioSelected => ("stopped", None),
+                };
+                return Ok(json!({
+                    "state": state,
+                    "song": song.map(SongInfo::from),
+                    "index": self.player_information.playing_index,
+                    "position": self.player_information.passed_time,
+                    "volume": self.player_information.volume,
+                    "shuffle": self.player_information.shuffle,
+                }));
+            }
+            Request::Queue => {
+                return Ok(json!({
+                    "index": self.player_information.playing_index,
+                    "songs": self
+                        .player_information
+                        .queue
+                        .iter()
+                        .map(SongInfo::from)
+                        .collect::<Vec<SongInfo>>(),
+                }));
+            }
+            Request::Search { query } => {
+                let words: Vec<String> = query
+                    .to_lowercase()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect();
+                let songs: Vec<SongInfo> = self
+                    .select_handler
+                    .items()
+                    .iter()
+                    .filter(|song| {
+                        let text = [Some(&song.title), song.artist.as_ref(), song.album.as_ref()]
+                            .into_iter()
+                            .flatten()
+                            .map(|field| field.to_lowercase())
+                            .collect::<Vec<String>>()
+                            .join(" ");
+                        words.iter().all(|word| text.contains(word))
+                    })
+                    .map(SongInfo::from)
+                    .collect();
+                return Ok(json!(songs));
+            }
+            Request::Subscribe => return Err("Subscribe is handled by the connection".to_string()),
+        };
+        self.send_to_player_from(event, "Remote");
+        Ok(Value::Null)
+    }
+
+    /// Songs in files or folders, like the add command finds them.
+    fn songs_at(&mut self, paths: &[PathBuf]) -> Result<Vec<Song>, String> {
+        let mut songs = Vec::new();
+        for path in paths {
+            if !path.exists() {
+                return Err(format!("No such file or folder: {}", path.display()));
+            }
+            songs.extend(self.file_finder.find_songs(path).map_err(|err| {
+                self.notify_error(&err);
+                err.to_string()
+            })?);
+        }
+        if songs.is_empty() {
+            return Err("No songs found".to_string());
+        }
+        Ok(songs)
+    }
+
+    /// Sends the current song as now playing, once per play rather than on every resume.
+    fn update_now_playing(&mut self) {
+        let Some(song) = self.get_current_song().cloned() else {
+            return;
+        };
+        let location = song.location();
+        if self.now_playing.is_some() && self.now_playing == location {
+            return;
+        }
+        self.now_playing = location;
+        if let Some(listen) = Listen::new(&song, now(), song.total_time as u64) {
+            self.send_to_scrobbler(ScrobbleEvent::NowPlaying(listen));
+        }
+    }
+
+    fn send_to_scrobbler(&self, event: ScrobbleEvent) {
+        if let Some(scrobbler) = &self.scrobbler {
+            let _ = scrobbler.send(event);
+        }
+    }
+
+    /// Name of the focused list, recorded in the history as where songs were queued from.
+    fn source_label(&self) -> &'static str {
+        match self.current_screen {
+            CurrentScreen::Main(FocusedWindowMain::Queue)
+            | CurrentScreen::Help(FocusedWindowMain::Queue)
+            | CurrentScreen::Command(FocusedWindowMain::Queue)
+            | CurrentScreen::Stats(FocusedWindowMain::Queue) => "Queue",
+            _ => self.selected_media_display_type.label(),
+        }
+    }
+
+    fn send_to_player(&mut self, event: PlayerReceiveEvent) {
+        self.send_to_player_from(event, self.source_label());
+    }
+
+    /// `source` is recorded in the history for songs that start a queue or are added to it.
+    fn send_to_player_from(&mut self, event: PlayerReceiveEvent, source: &'static str) {
+        if let PlayerReceiveEvent::CreateQueueAndPlay(songs)
+        | PlayerReceiveEvent::AddSongsToQueueAndPlay(songs) = &event
+        {
+            for location in songs.iter().filter_map(Song::location) {
+                self.song_sources.insert(location, source);
+            }
+        }
+        if let PlayerReceiveEvent::CreateQueueAndPlay(_) = &event {
+            self.history_rewind = None;
+        }
+        if self.player_tx.send(event).is_err() {
+            self.notify_error(&AppError::PlayerStopped);
+        }
+    }
+
+    fn notify_error(&mut self, err: &AppError) {
+        error!("{err}");
+        self.notification_handler
+            .push(NotificationLevel::Error, err.to_string());
+    }
+
     fn get_current_song(&self) -> Option<&Song> {
         if let Some(index) = self.player_information.playing_index {
             self.player_information.queue.get(index)
@@ -371,10 +1661,44 @@ impl App {
         }
     }
 
-    fn create_threads(event_tx: Sender<ApplicationEvent>, player_rx: Receiver<PlayerReceiveEvent>) {
-        let keyboard_event_tx = event_tx.clone();
-        KeyboardHandler::new(keyboard_event_tx);
+    fn create_threads(
+        event_tx: UnboundedSender<ApplicationEvent>,
+        player_rx: Receiver<PlayerReceiveEvent>,
+    ) {
         let player_event_tx = event_tx.clone();
         Player::new(player_event_tx, player_rx);
     }
 }
+
+/// Writes ratings and play counts to the files if enabled, off the UI thread.
+fn write_stat_tags(songs: Vec<(Song, SongStats)>) {
+    if !Config::get().write_rating_tags {
+        return;
+    }
+    tokio::task::spawn_blocking(move || {
+        for (song, stats) in songs {
+            if let SongType::Local { path } | SongType::OnlineDownloaded { path, .. } =
+                &song.song_type
+                && let Err(err) = tags::write_stats(path, stats.rating, stats.play_count)
+            {
+                warn!("{err}");
+            }
+        }
+    });
+}
+
+/// Restores the terminal before the panic message is printed, otherwise it is lost in raw mode.
+/// Panics on other threads, like the player's or a fetch task's, are only logged so that they
+/// don't print over the ui.
+fn install_panic_hook() {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        error!("{info}");
+        if thread::current().name() != Some("main") {
+            return;
+        }
+        let _ = execute!(stdout(), DisableMouseCapture);
+        ratatui::restore();
+        hook(info);
+    }));
+}
@@ -0,0 +1,568 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+
+use crate::song::{MusicBrainzIds, Song, SongType};
+
+/// Characters escaped in XSPF locations. Path separators stay readable.
+const URI_PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// A playlist file found in the library, with its songs.
+#[derive(Clone)]
+pub struct PlaylistFile {
+    pub path: PathBuf,
+    pub songs: Vec<Song>,
+}
+
+/// One entry of a playlist file, before it is matched against the library.
+#[derive(Default)]
+struct Entry {
+    location: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    /// In ms
+    duration: Option<u32>,
+}
+
+/// Reads a playlist in any supported format. Relative paths are relative to the file, and songs
+/// found in `library` are taken from there.
+pub fn read(path: &Path, library: &[Song]) -> Result<Vec<Song>, String> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or(format!("{} isn't an m3u, pls or xspf file", path.display()))?;
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    let entries = match format {
+        PlaylistFormat::M3u => parse_m3u(&content),
+        PlaylistFormat::Pls => parse_pls(&content),
+        PlaylistFormat::Xspf => {
+            parse_xspf(&content).map_err(|err| format!("Invalid xspf {}: {err}", path.display()))?
+        }
+    };
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(entries
+        .into_iter()
+        .map(|entry| song_from_entry(entry, base, library))
+        .collect())
+}
+
+/// Writes `songs` in the format of the file extension. With `relative`, local paths are written
+/// relative to the playlist file.
+pub fn write(path: &Path, songs: &[Song], relative: bool) -> Result<(), String> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or(format!("{} isn't an m3u, pls or xspf file", path.display()))?;
+    let base = relative.then(|| path.parent().unwrap_or(Path::new(".")));
+    let content = match format {
+        PlaylistFormat::M3u => m3u(songs, base),
+        PlaylistFormat::Pls => pls(songs, base),
+        PlaylistFormat::Xspf => xspf(songs, base),
+    };
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Failed to create {}: {err}", dir.display()))?;
+    }
+    fs::write(path, content).map_err(|err| format!("Failed to write {}: {err}", path.display()))
+}
+
+/// Extended M3U with local paths and stream urls. Songs without either are left out.
+fn m3u(songs: &[Song], base: Option<&Path>) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for song in songs {
+        let Some(location) = location(song, base) else {
+            continue;
+        };
+        content.push_str(&format!(
+            "#EXTINF:{},{}\n{location}\n",
+            song.total_time / 1000,
+            display_title(song)
+        ));
+    }
+    content
+}
+
+fn parse_m3u(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut info = Entry::default();
+    for line in content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('\u{feff}'))
+    {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            if let Some((seconds, display)) = extinf.split_once(',') {
+                info.duration = seconds_to_ms(seconds);
+                (info.artist, info.title) = split_display_title(display.trim());
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(Entry {
+            location: line.to_string(),
+            ..std::mem::take(&mut info)
+        });
+    }
+    entries
+}
+
+fn pls(songs: &[Song], base: Option<&Path>) -> String {
+    let mut content = String::from("[playlist]\n");
+    let mut count = 0;
+    for song in songs {
+        let Some(location) = location(song, base) else {
+            continue;
+        };
+        count += 1;
+        let length = match song.song_type {
+            SongType::Online { .. } => -1,
+            _ => (song.total_time / 1000) as i64,
+        };
+        content.push_str(&format!(
+            "File{count}={location}\nTitle{count}={}\nLength{count}={length}\n",
+            display_title(song)
+        ));
+    }
+    content.push_str(&format!("NumberOfEntries={count}\nVersion=2\n"));
+    content
+}
+
+fn parse_pls(content: &str) -> Vec<Entry> {
+    let mut entries: BTreeMap<u32, Entry> = BTreeMap::new();
+    for line in content.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim();
+        let (field, number) = key.split_at(key.find(|c: char| c.is_ascii_digit()).unwrap_or(0));
+        let Ok(number) = number.parse::<u32>() else {
+            continue;
+        };
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" => (entry.artist, entry.title) = split_display_title(value),
+            "length" => entry.duration = seconds_to_ms(value),
+            _ => {}
+        }
+    }
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
+
+fn xspf(songs: &[Song], base: Option<&Path>) -> String {
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+    );
+    for song in songs {
+        let location = match (&song.song_type, base) {
+            (SongType::Online { url }, _) => url.clone(),
+            (_, Some(_)) => match location(song, base) {
+                Some(location) => utf8_percent_encode(&location, URI_PATH).to_string(),
+                None => continue,
+            },
+            (_, None) => match location(song, None) {
+                Some(location) => {
+                    format!("file://{}", utf8_percent_encode(&location, URI_PATH))
+                }
+                None => continue,
+            },
+        };
+        content.push_str("    <track>\n");
+        content.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape_xml(&location)
+        ));
+        content.push_str(&format!(
+            "      <title>{}</title>\n",
+            escape_xml(&song.title)
+        ));
+        if let Some(artist) = &song.artist {
+            content.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                escape_xml(artist)
+            ));
+        }
+        if let Some(album) = &song.album {
+            content.push_str(&format!("      <album>{}</album>\n", escape_xml(album)));
+        }
+        if song.total_time > 0 {
+            content.push_str(&format!("      <duration>{}</duration>\n", song.total_time));
+        }
+        content.push_str("    </track>\n");
+    }
+    content.push_str("  </trackList>\n</playlist>\n");
+    content
+}
+
+fn parse_xspf(content: &str) -> Result<Vec<Entry>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(content)?;
+    let entries = document
+        .descendants()
+        .filter(|node| node.has_tag_name("track"))
+        .filter_map(|track| {
+            let text = |name: &str| {
+                track
+                    .children()
+                    .find(|child| child.has_tag_name(name))
+                    .and_then(|child| child.text())
+                    .map(|text| text.trim().to_string())
+                    .filter(|text| !text.is_empty())
+            };
+            let location = text("location")?;
+            let location = match location.strip_prefix("file://") {
+                Some(path) => percent_decode_str(path).decode_utf8_lossy().to_string(),
+                None if location.contains("://") => location,
+                None => percent_decode_str(&location)
+                    .decode_utf8_lossy()
+                    .to_string(),
+            };
+            Some(Entry {
+                location,
+                title: text("title"),
+                artist: text("creator"),
+                album: text("album"),
+                duration: text("duration").and_then(|duration| duration.parse().ok()),
+            })
+        })
+        .collect();
+    Ok(entries)
+}
+
+fn song_from_entry(entry: Entry, base: &Path, library: &[Song]) -> Song {
+    let location = entry.location;
+    let song_type = if location.starts_with("http://") || location.starts_with("https://") {
+        SongType::Online {
+            url: location.clone(),
+        }
+    } else {
+        let path = location.strip_prefix("file://").unwrap_or(&location);
+        SongType::Local {
+            path: normalize(&base.join(path)),
+        }
+    };
+    if let SongType::Local { path } = &song_type
+        && let Some(song) = library.iter().find(|song| match &song.song_type {
+            SongType::Local { path: library_path }
+            | SongType::OnlineDownloaded {
+                path: library_path, ..
+            } => library_path == path,
+            _ => false,
+        })
+    {
+        return song.clone();
+    }
+    Song {
+        title: entry.title.unwrap_or_else(|| file_title(&location)),
+        artist: entry.artist,
+        album: entry.album,
+        genre: None,
+        year: None,
+        total_time: entry.duration.unwrap_or(0),
+        song_type,
+        match_confidence: None,
+        musicbrainz: MusicBrainzIds::default(),
+        cover_art: None,
+    }
+}
+
+/// Path or url of a song, local paths relative to `base` if given.
+fn location(song: &Song, base: Option<&Path>) -> Option<String> {
+    match &song.song_type {
+        SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => {
+            let path = match base {
+                Some(base) => relative_path(path, base),
+                None => path.clone(),
+            };
+            Some(path.to_string_lossy().to_string())
+        }
+        SongType::Online { url } => Some(url.clone()),
+        SongType::OnlineWithoutUrl => None,
+    }
+}
+
+fn display_title(song: &Song) -> String {
+    match &song.artist {
+        Some(artist) => format!("{artist} - {}", song.title),
+        None => song.title.clone(),
+    }
+}
+
+/// `Artist - Title` as written by most players, or just a title.
+fn split_display_title(display: &str) -> (Option<String>, Option<String>) {
+    if display.is_empty() {
+        return (None, None);
+    }
+    match display.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.to_string()), Some(title.to_string())),
+        None => (None, Some(display.to_string())),
+    }
+}
+
+/// A length in whole seconds as ms. `None` for -1, which means unknown, and for anything that
+/// doesn't fit.
+fn seconds_to_ms(seconds: &str) -> Option<u32> {
+    let seconds = seconds
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|seconds| *seconds > 0)?;
+    u32::try_from(seconds).ok()?.checked_mul(1000)
+}
+
+fn file_title(location: &str) -> String {
+    Path::new(location)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| location.to_string())
+}
+
+/// `path` from `base`, going up with `..` where needed. Both should be absolute.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize(path);
+    let base = normalize(base);
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek())
+        && a == b
+    {
+        path_components.next();
+        base_components.next();
+    }
+    let mut relative: PathBuf = base_components.map(|_| Component::ParentDir).collect();
+    relative.extend(path_components);
+    relative
+}
+
+/// Resolves `.` and `..` without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn song(path: &Path, artist: Option<&str>, title: &str, total_time: u32) -> Song {
+        Song {
+            title: title.to_string(),
+            artist: artist.map(String::from),
+            album: None,
+            genre: None,
+            year: None,
+            total_time,
+            song_type: SongType::Local {
+                path: path.to_path_buf(),
+            },
+            match_confidence: None,
+            musicbrainz: MusicBrainzIds::default(),
+            cover_art: None,
+        }
+    }
+
+    fn local_path(song: &Song) -> &Path {
+        match &song.song_type {
+            SongType::Local { path } => path,
+            _ => panic!("{} isn't local", song.title),
+        }
+    }
+
+    /// A scratch directory with `music` and `playlists` in it, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            TempDir(env::temp_dir().join(format!("music-ratatui-{}-{name}", process::id())))
+        }
+
+        fn songs(&self) -> Vec<Song> {
+            vec![
+                song(
+                    &self.0.join("music/Artist/Song #1 é.flac"),
+                    Some("Artist"),
+                    "Song - Live",
+                    183_456,
+                ),
+                song(&self.0.join("music/untitled.mp3"), None, "untitled", 0),
+            ]
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes and reads back the songs of `dir`, checking that nothing was lost.
+    fn round_trip(dir: &TempDir, file_name: &str, relative: bool) -> String {
+        let path = dir.0.join("playlists").join(file_name);
+        let songs = dir.songs();
+        write(&path, &songs, relative).unwrap();
+        let read = read(&path, &[]).unwrap();
+
+        assert_eq!(read.len(), songs.len());
+        for (read, song) in read.iter().zip(&songs) {
+            assert_eq!(local_path(read), local_path(song));
+            assert_eq!(read.artist, song.artist);
+            assert_eq!(read.title, song.title);
+        }
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn m3u_round_trip() {
+        let dir = TempDir::new("m3u");
+        let content = round_trip(&dir, "list.m3u", true);
+        assert!(
+            content.contains("#EXTINF:183,Artist - Song - Live\n../music/Artist/Song #1 é.flac\n")
+        );
+        let read = read(&dir.0.join("playlists/list.m3u"), &[]).unwrap();
+        assert_eq!(read[0].total_time, 183_000);
+        assert_eq!(read[1].total_time, 0);
+
+        let content = round_trip(&dir, "absolute.m3u8", false);
+        let location = dir.0.join("music/untitled.mp3");
+        assert!(content.contains(&format!("{}\n", location.display())));
+    }
+
+    #[test]
+    fn pls_round_trip() {
+        let dir = TempDir::new("pls");
+        let content = round_trip(&dir, "list.pls", true);
+        assert!(content.contains("File1=../music/Artist/Song #1 é.flac\n"));
+        assert!(content.contains("Length1=183\n"));
+        assert!(content.ends_with("NumberOfEntries=2\nVersion=2\n"));
+    }
+
+    #[test]
+    fn xspf_round_trip_percent_encodes() {
+        let dir = TempDir::new("xspf");
+        let content = round_trip(&dir, "relative.xspf", true);
+        assert!(content.contains("<location>../music/Artist/Song%20%231%20%C3%A9.flac</location>"));
+
+        let content = round_trip(&dir, "absolute.xspf", false);
+        let location = dir.0.join("music/Artist/Song%20%231%20%C3%A9.flac");
+        assert!(content.contains(&format!(
+            "<location>file://{}</location>",
+            location.display()
+        )));
+        assert!(content.contains("<duration>183456</duration>"));
+        assert!(content.contains("<creator>Artist</creator>"));
+    }
+
+    #[test]
+    fn reads_library_songs_from_the_library() {
+        let dir = TempDir::new("library");
+        let path = dir.0.join("playlists/list.m3u");
+        let mut library = dir.songs();
+        library[1].album = Some("Album".to_string());
+        write(&path, &library, true).unwrap();
+
+        let read = read(&path, &library).unwrap();
+        assert_eq!(read[1].album.as_deref(), Some("Album"));
+    }
+
+    #[test]
+    fn extinf_splits_artist_and_title() {
+        let entries = parse_m3u(
+            "\u{feff}#EXTM3U\n\
+             #EXTINF:10,Artist - Title - Remix\n/a.mp3\n\
+             #EXTINF:-1,Just a title\nhttp://example.com/stream\n\
+             #EXTINF:5,\n/b.mp3\n\
+             /c.mp3\n",
+        );
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].artist.as_deref(), Some("Artist"));
+        assert_eq!(entries[0].title.as_deref(), Some("Title - Remix"));
+        assert_eq!(entries[0].duration, Some(10_000));
+        assert_eq!(entries[1].artist, None);
+        assert_eq!(entries[1].title.as_deref(), Some("Just a title"));
+        assert_eq!(entries[1].duration, None);
+        assert_eq!(entries[2].title, None);
+        // Info only applies to the next entry
+        assert_eq!(entries[3].duration, None);
+        assert_eq!(entries[3].location, "/c.mp3");
+    }
+
+    #[test]
+    fn ignores_lengths_that_do_not_fit() {
+        assert_eq!(seconds_to_ms("4294967"), Some(4_294_967_000));
+        assert_eq!(seconds_to_ms("4294968"), None);
+        assert_eq!(seconds_to_ms("99999999999"), None);
+        assert_eq!(seconds_to_ms("-1"), None);
+        assert_eq!(seconds_to_ms("abc"), None);
+        let entries = parse_pls("[playlist]\nFile1=/a.mp3\nLength1=99999999999\n");
+        assert_eq!(entries[0].duration, None);
+    }
+
+    #[test]
+    fn relative_paths_go_up_where_needed() {
+        assert_eq!(
+            relative_path(Path::new("/music/a/b.mp3"), Path::new("/music/a")),
+            Path::new("b.mp3")
+        );
+        assert_eq!(
+            relative_path(Path::new("/music/a/b.mp3"), Path::new("/music/lists/x")),
+            Path::new("../../a/b.mp3")
+        );
+        assert_eq!(
+            relative_path(Path::new("/music/./a/../b.mp3"), Path::new("/music")),
+            Path::new("b.mp3")
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fs, path::PathBuf};
+
+use log::warn;
+use ratatui::widgets::ListItem;
+
+use crate::{
+    commands::playlist_dir, playlist_file, smart_playlists::SmartPlaylist, song::Song,
+    utils::selecthandler::SelectHandlerItem,
+};
+
+#[derive(Clone)]
+pub struct Playlist {
+    pub name: String,
+    pub songs: Vec<Song>,
+    /// Set for smart playlists, whose songs come from these rules
+    pub rules: Option<SmartPlaylist>,
+}
+
+impl SelectHandlerItem for Playlist {
+    fn list_item(&self) -> ListItem<'_> {
+        let icon = if self.rules.is_some() { "✨" } else { "📃" };
+        ListItem::new(format!("{icon} {} ({} songs)", self.name, self.songs.len()))
+    }
+}
+
+impl Playlist {
+    pub fn path(&self) -> PathBuf {
+        self.path_for(&self.name)
+    }
+
+    /// Where the playlist would be stored under `name`.
+    pub fn path_for(&self, name: &str) -> PathBuf {
+        match self.rules {
+            Some(_) => smart_playlist_path(name),
+            None => playlist_path(name),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        playlist_file::write(&self.path(), &self.songs, false)
+    }
+}
+
+pub fn playlist_path(name: &str) -> PathBuf {
+    playlist_dir().join(format!("{name}.m3u"))
+}
+
+pub fn smart_playlist_path(name: &str) -> PathBuf {
+    playlist_dir().join(format!("{name}.toml"))
+}
+
+/// Playlist names become file names, so they can't contain path separators.
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("The playlist needs a name".to_string());
+    }
+    if name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("{name:?} can't be used as a playlist name"));
+    }
+    Ok(())
+}
+
+/// Every playlist in the playlist dir, sorted by name. Songs found in `library` are taken from
+/// there, the rest are built from the playlist entries. Smart playlists are evaluated against
+/// `library`.
+pub fn load_all(library: &[Song]) -> Vec<Playlist> {
+    let mut playlists: Vec<Playlist> = fs::read_dir(playlist_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let extension = path.extension()?.to_string_lossy().to_string();
+            let (songs, rules) = match extension.as_str() {
+                "m3u" => (playlist_file::read(&path, library), None),
+                "toml" => match SmartPlaylist::load(&path) {
+                    Ok(rules) => (Ok(rules.evaluate(library)), Some(rules)),
+                    Err(err) => (Err(err), None),
+                },
+                _ => return None,
+            };
+            let songs = songs.inspect_err(|err| warn!("{err}")).ok()?;
+            Some(Playlist { name, songs, rules })
+        })
+        .collect();
+    playlists.sort_by_key(|playlist| playlist.name.to_lowercase());
+    playlists
+}
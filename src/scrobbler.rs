@@ -0,0 +1,306 @@
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::{
+    config::Config,
+    fetch::scrobble::{Listen, Service, SubmitError},
+    utils::atomic_file,
+};
+
+/// Shorter songs are never scrobbled
+const MIN_LENGTH: u64 = 30_000;
+/// Heard this long, a song is scrobbled even if it's less than half
+const SCROBBLE_AFTER: u64 = 4 * 60 * 1000;
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// Half the song or four minutes, whichever comes first, like Last.fm asks for.
+pub fn should_scrobble(listened: u64, length: u64) -> bool {
+    length >= MIN_LENGTH && listened >= (length / 2).min(SCROBBLE_AFTER)
+}
+
+pub enum ScrobbleEvent {
+    NowPlaying(Listen),
+    Scrobble(Listen),
+}
+
+#[derive(Serialize, Deserialize)]
+struct QueuedScrobble {
+    service: Service,
+    listen: Listen,
+}
+
+/// Scrobbles not yet accepted, oldest first, saved in the data dir so they survive a restart.
+struct ScrobbleQueue {
+    path: PathBuf,
+    scrobbles: Vec<QueuedScrobble>,
+}
+
+impl ScrobbleQueue {
+    fn load(path: PathBuf) -> Self {
+        let scrobbles = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                match atomic_file::set_aside(&path) {
+                    Ok(backup) => {
+                        warn!("Moved broken scrobble queue to {}: {err}", backup.display())
+                    }
+                    Err(move_err) => warn!(
+                        "Ignoring scrobble queue {}: {err}, can't move it: {move_err}",
+                        path.display()
+                    ),
+                }
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        ScrobbleQueue { path, scrobbles }
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string(&self.scrobbles)
+            .map_err(io::Error::other)
+            .and_then(|content| atomic_file::write(&self.path, content.as_bytes()));
+        if let Err(err) = result {
+            warn!("Failed to save the scrobble queue: {err}");
+        }
+    }
+
+    /// Sends in order until a service can't be reached. False if anything is left to retry.
+    async fn flush(&mut self, config: &Config) -> bool {
+        let mut flushed = true;
+        let mut index = 0;
+        let mut changed = false;
+        while index < self.scrobbles.len() {
+            let QueuedScrobble { service, listen } = &self.scrobbles[index];
+            match service.scrobble(config, listen).await {
+                Ok(()) => {
+                    self.scrobbles.remove(index);
+                    changed = true;
+                }
+                Err(SubmitError::Rejected(err)) => {
+                    warn!("{service} rejected the scrobble of {}: {err}", listen.title);
+                    self.scrobbles.remove(index);
+                    changed = true;
+                }
+                Err(SubmitError::Retry(err)) => {
+                    info!("Scrobbling to {service} failed, retrying later: {err}");
+                    flushed = false;
+                    // Keeps the order for this service, the others can still go ahead
+                    let failed = *service;
+                    index += 1;
+                    while index < self.scrobbles.len() && self.scrobbles[index].service == failed {
+                        index += 1;
+                    }
+                }
+            }
+        }
+        if changed {
+            self.save();
+        }
+        flushed
+    }
+}
+
+/// Submits to the configured services from a background task. `None` if none are configured.
+pub fn spawn() -> Option<UnboundedSender<ScrobbleEvent>> {
+    let services = Service::configured(Config::get());
+    if services.is_empty() {
+        return None;
+    }
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(run(services, rx));
+    Some(tx)
+}
+
+/// Scrobbles are queued first and flushed right away, on failure again with a growing delay.
+async fn run(services: Vec<Service>, mut rx: UnboundedReceiver<ScrobbleEvent>) {
+    let config = Config::get();
+    let mut queue = ScrobbleQueue::load(config.data_dir.join("scrobble_queue.json"));
+    let mut retry_delay = MIN_RETRY_DELAY;
+    let mut pending = !queue.flush(config).await;
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Some(ScrobbleEvent::NowPlaying(listen)) => {
+                    for service in &services {
+                        if let Err(err) = service.now_playing(config, &listen).await {
+                            info!("Now playing on {service} failed: {err}");
+                        }
+                    }
+                }
+                Some(ScrobbleEvent::Scrobble(listen)) => {
+                    queue.scrobbles.extend(services.iter().map(|service| QueuedScrobble {
+                        service: *service,
+                        listen: listen.clone(),
+                    }));
+                    queue.save();
+                    pending = !queue.flush(config).await;
+                    if !pending {
+                        retry_delay = MIN_RETRY_DELAY;
+                    }
+                }
+                None => break,
+            },
+            _ = tokio::time::sleep(retry_delay), if pending => {
+                pending = !queue.flush(config).await;
+                retry_delay = if pending {
+                    (retry_delay * 2).min(MAX_RETRY_DELAY)
+                } else {
+                    MIN_RETRY_DELAY
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, process,
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::Value;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::config::ListenBrainzConfig;
+
+    #[test]
+    fn scrobbles_after_half_or_four_minutes() {
+        assert!(!should_scrobble(29_000, 29_000));
+        assert!(should_scrobble(15_000, 30_000));
+        assert!(!should_scrobble(14_999, 30_000));
+        assert!(should_scrobble(90_000, 180_000));
+        assert!(!should_scrobble(89_999, 180_000));
+        assert!(should_scrobble(240_000, 600_000));
+        assert!(!should_scrobble(239_999, 600_000));
+    }
+
+    fn listen(title: &str) -> Listen {
+        Listen {
+            artist: "Artist".to_string(),
+            title: title.to_string(),
+            album: None,
+            length: 180_000,
+            started: 1_700_000_000,
+            recording_mbid: None,
+        }
+    }
+
+    fn titles(queue: &ScrobbleQueue) -> Vec<&str> {
+        queue
+            .scrobbles
+            .iter()
+            .map(|scrobble| scrobble.listen.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn moves_a_broken_queue_aside() {
+        let dir = env::temp_dir().join(format!("music-ratatui-broken-queue-{}", process::id()));
+        let path = dir.join("scrobble_queue.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "[{\"service\":").unwrap();
+
+        assert!(ScrobbleQueue::load(path.clone()).scrobbles.is_empty());
+        assert!(!path.exists());
+        let backup = dir.join("scrobble_queue.json.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "[{\"service\":");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    /// Stands in for ListenBrainz, answering with `statuses` in turn and then 200. Returns its
+    /// URL and the titles submitted to it.
+    async fn mock_listenbrainz(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let submitted = Arc::new(Mutex::new(Vec::new()));
+        let titles = submitted.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                let body: Value = serde_json::from_slice(&body).unwrap();
+                let title = &body["payload"][0]["track_metadata"]["track_name"];
+                titles
+                    .lock()
+                    .unwrap()
+                    .push(title.as_str().unwrap().to_string());
+
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                stream
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, submitted)
+    }
+
+    #[tokio::test]
+    async fn flush_sends_in_order_and_keeps_failed_scrobbles() {
+        let (url, submitted) = mock_listenbrainz(vec![200, 503]).await;
+        let mut config = Config::new();
+        config.listenbrainz = Some(ListenBrainzConfig {
+            token: "token".to_string(),
+            url,
+        });
+        let dir = env::temp_dir().join(format!("music-ratatui-scrobbler-{}", process::id()));
+        let path = dir.join("scrobble_queue.json");
+
+        let mut queue = ScrobbleQueue::load(path.clone());
+        assert!(queue.scrobbles.is_empty());
+        queue.scrobbles = ["One", "Two", "Three"]
+            .into_iter()
+            .map(|title| QueuedScrobble {
+                service: Service::ListenBrainz,
+                listen: listen(title),
+            })
+            .collect();
+        queue.save();
+        assert_eq!(
+            titles(&ScrobbleQueue::load(path.clone())),
+            ["One", "Two", "Three"]
+        );
+
+        // Two fails, so Three waits behind it, also across a restart
+        assert!(!queue.flush(&config).await);
+        assert_eq!(*submitted.lock().unwrap(), ["One", "Two"]);
+        assert_eq!(titles(&queue), ["Two", "Three"]);
+        assert_eq!(titles(&ScrobbleQueue::load(path.clone())), ["Two", "Three"]);
+
+        assert!(queue.flush(&config).await);
+        assert_eq!(*submitted.lock().unwrap(), ["One", "Two", "Two", "Three"]);
+        assert!(ScrobbleQueue::load(path).scrobbles.is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
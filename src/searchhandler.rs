@@ -1,25 +1,43 @@
-use std::sync::{Arc, Mutex, mpsc::Sender};
+use std::sync::{Arc, Mutex};
 
-use musicbrainz_rs::entity::recording::Recording;
-use tokio::task::JoinHandle;
+use log::{info, warn};
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
 
 use crate::{
-    events::ApplicationEvent, fetch::search::fetch_recording, song::Song,
-    utils::selecthandler::SelectHandler,
+    config::{Config, download_file_name},
+    display_handlers::browse_handler::{BrowseHandler, BrowseNode, BrowsePageKind},
+    events::ApplicationEvent,
+    fetch::{
+        discography::{fetch_album, fetch_discography},
+        downloader::Downloader,
+        musicbrainz::MusicBrainzClient,
+        search::fetch_recording,
+    },
+    song::{Song, SongType},
+    utils::selecthandler::Movement,
 };
 
 pub struct SearchHandler {
     query: String,
-    pub select_handler: Arc<Mutex<SelectHandler<Song>>>,
+    pub browse_handler: Arc<Mutex<BrowseHandler>>,
     running_search: Option<JoinHandle<()>>,
+    /// `None` if the client couldn't be created, searches do nothing then
+    client: Option<Arc<MusicBrainzClient>>,
+    /// Tells the app to redraw once a background task changed the results
+    event_tx: UnboundedSender<ApplicationEvent>,
 }
 
 impl SearchHandler {
-    pub fn new() -> Self {
+    pub fn new(
+        client: Option<Arc<MusicBrainzClient>>,
+        event_tx: UnboundedSender<ApplicationEvent>,
+    ) -> Self {
         SearchHandler {
             query: "".to_string(),
-            select_handler: Arc::new(Mutex::new(SelectHandler::new())),
+            browse_handler: Arc::new(Mutex::new(BrowseHandler::new())),
             running_search: None,
+            client,
+            event_tx,
         }
     }
 
@@ -30,24 +48,190 @@ impl SearchHandler {
     pub fn remove_last_char(&mut self) {
         self.query.pop();
     }
+
     pub fn search(&mut self) {
-        if let Some(thread) = &mut self.running_search {
-            if !thread.is_finished() {
-                thread.abort();
+        let query_clone = self.query.clone();
+        let Some(client) = self.client() else {
+            return;
+        };
+        self.spawn_browse_task(move |browse_handler_arc| async move {
+            let songs = match fetch_recording(&client, &query_clone).await {
+                Ok(songs) => songs,
+                Err(err) => {
+                    warn!("Search for {query_clone:?} failed: {err}");
+                    Vec::new()
+                }
+            };
+            if let Ok(browse_handler) = &mut browse_handler_arc.lock() {
+                browse_handler.set_results(songs);
+            }
+        });
+    }
+
+    /// Opens the discography of the selected song's artist.
+    pub fn open_artist(&mut self) {
+        let Some(BrowseNode::Song(song)) = self.selected() else {
+            return;
+        };
+        let (Some(artist_id), Some(artist)) = (song.musicbrainz.artist, song.artist) else {
+            return;
+        };
+        let Some(client) = self.client() else {
+            return;
+        };
+        self.spawn_browse_task(move |browse_handler_arc| async move {
+            match fetch_discography(&client, &artist_id).await {
+                Ok(release_groups) => {
+                    if let Ok(browse_handler) = &mut browse_handler_arc.lock() {
+                        browse_handler.push_artist(artist, release_groups);
+                    }
+                }
+                Err(err) => warn!("Failed to fetch discography of {artist}: {err}"),
+            }
+        });
+    }
+
+    /// Opens the selected release group. Returns the selected song if a song is selected instead.
+    pub fn open_selected(&mut self, library: Vec<Song>) -> Option<Song> {
+        match self.selected()? {
+            BrowseNode::Song(song) => Some(song),
+            BrowseNode::ReleaseGroup(release_group) => {
+                let client = self.client()?;
+                self.spawn_browse_task(move |browse_handler_arc| async move {
+                    match fetch_album(&client, &release_group.id, library).await {
+                        Ok(album) => {
+                            if let Ok(browse_handler) = &mut browse_handler_arc.lock() {
+                                browse_handler.push_album(album.title, album.tracks);
+                            }
+                        }
+                        Err(err) => warn!("Failed to fetch {}: {err}", release_group.title),
+                    }
+                });
+                None
             }
+            BrowseNode::Heading(_) => None,
         }
-        let query_clone = self.query.clone();
-        let select_handler_arc = self.select_handler.clone();
-        let thread = tokio::spawn(async move {
-            let songs = fetch_recording(&query_clone).await.unwrap_or(Vec::new());
-            if let Ok(select_handler) = &mut select_handler_arc.lock() {
-                select_handler.set_items(songs);
+    }
+
+    fn client(&self) -> Option<Arc<MusicBrainzClient>> {
+        if self.client.is_none() {
+            warn!("MusicBrainz is unavailable");
+        }
+        self.client.clone()
+    }
+
+    pub fn go_to_parent(&mut self) {
+        if let Ok(browse_handler) = &mut self.browse_handler.lock() {
+            browse_handler.go_to_parent();
+        }
+    }
+
+    pub fn select_handler_move(&mut self, movement: Movement) {
+        if let Ok(browse_handler) = &mut self.browse_handler.lock() {
+            browse_handler.select_handler_move(movement);
+        }
+    }
+
+    /// Songs on the current page and the index of the selected one.
+    pub fn songs_from_selected(&self) -> Option<(Vec<Song>, usize)> {
+        let browse_handler = self.browse_handler.lock().ok()?;
+        let index = browse_handler.selected_song_index()?;
+        Some((browse_handler.songs(), index))
+    }
+
+    /// On an album page every track, otherwise the selected song.
+    pub fn songs_to_enqueue(&self) -> Vec<Song> {
+        let Ok(browse_handler) = self.browse_handler.lock() else {
+            return Vec::new();
+        };
+        match browse_handler.current_page_kind() {
+            BrowsePageKind::Album => browse_handler.songs(),
+            _ => match browse_handler.select_handler_selected() {
+                Some(BrowseNode::Song(song)) => vec![song.clone()],
+                _ => Vec::new(),
+            },
+        }
+    }
+
+    /// Downloads the songs that would be enqueued, skipping ones that are already on disk.
+    pub fn download(&mut self) {
+        let songs: Vec<Song> = self
+            .songs_to_enqueue()
+            .into_iter()
+            .filter(|song| song.is_online_only())
+            .collect();
+        if songs.is_empty() {
+            return;
+        }
+        let browse_handler_arc = self.browse_handler.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let downloader = match Downloader::new().await {
+                Ok(downloader) => downloader,
+                Err(err) => {
+                    warn!("Failed to set up downloader: {err}");
+                    return;
+                }
+            };
+            for song in songs {
+                let SongType::Online { url } = &song.song_type else {
+                    continue;
+                };
+                let name = download_file_name(
+                    &Config::get().download_template,
+                    &song.title,
+                    song.artist.as_deref(),
+                    song.album.as_deref(),
+                );
+                match downloader.download(url, name).await {
+                    Ok(path) => {
+                        info!("Downloaded {} to {:?}", song.title, path);
+                        if let Ok(browse_handler) = &mut browse_handler_arc.lock() {
+                            browse_handler.mark_downloaded(url, path);
+                        }
+                        let _ = event_tx.send(ApplicationEvent::Redraw);
+                    }
+                    Err(err) => warn!("Failed to download {}: {err}", song.title),
+                }
             }
         });
-        self.running_search = Some(thread);
     }
 
     pub fn get_query(&self) -> &str {
         &self.query
     }
+
+    fn selected(&self) -> Option<BrowseNode> {
+        self.browse_handler
+            .lock()
+            .ok()?
+            .select_handler_selected()
+            .cloned()
+    }
+
+    fn spawn_browse_task<F, Fut>(&mut self, task: F)
+    where
+        F: FnOnce(Arc<Mutex<BrowseHandler>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if let Some(thread) = &mut self.running_search
+            && !thread.is_finished()
+        {
+            thread.abort();
+        }
+        if let Ok(browse_handler) = &mut self.browse_handler.lock() {
+            browse_handler.set_loading(true);
+        }
+        let browse_handler_arc = self.browse_handler.clone();
+        let future = task(browse_handler_arc.clone());
+        let event_tx = self.event_tx.clone();
+        let thread = tokio::spawn(async move {
+            future.await;
+            if let Ok(browse_handler) = &mut browse_handler_arc.lock() {
+                browse_handler.set_loading(false);
+            }
+            let _ = event_tx.send(ApplicationEvent::Redraw);
+        });
+        self.running_search = Some(thread);
+    }
 }
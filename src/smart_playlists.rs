@@ -0,0 +1,186 @@
+use std::{fs, path::Path};
+
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::{
+    library_db::{LibraryDb, SongStats, now},
+    song::Song,
+    songs::{Filter, SortBy, SortOrder, sort_songs},
+};
+
+const DAY: u64 = 60 * 60 * 24;
+const MONTH: u64 = DAY * 30;
+
+/// Written by `smart-playlist-new` as a starting point.
+pub const TEMPLATE: &str = r#"# Songs matching the rules, updated whenever the library changes.
+# "all" needs every rule to match, "any" just one.
+match = "all"
+# title, artist, album, year, play_count, added, rating, last_played or random
+sort = "added"
+order = "desc"
+limit = 100
+
+[[rules]]
+added_within_days = 30
+
+# Other rules:
+# genre = "Jazz"
+# year = [1990, 1999]
+# play_count_above = 10
+# rating_at_least = 4
+# favorite = true
+# skip_count_above = 3
+# not_played_for_months = 6
+# text = "live", title = "...", artist = "...", album = "..."
+# any = [{ genre = "Rock" }, { genre = "Metal" }]
+# all = [{ rating_at_least = 4 }, { not_played_for_months = 3 }]
+"#;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Title, artist or album contains the text
+    Text(String),
+    Title(String),
+    Artist(String),
+    Album(String),
+    Genre(String),
+    /// First and last year, both included
+    Year([i32; 2]),
+    PlayCountAbove(u32),
+    SkipCountAbove(u32),
+    AddedWithinDays(u64),
+    RatingAtLeast(u8),
+    Favorite(bool),
+    /// Songs that were never played match too
+    NotPlayedForMonths(u64),
+    All(Vec<Rule>),
+    Any(Vec<Rule>),
+}
+
+impl Rule {
+    fn matches(&self, song: &Song, stats: Option<&SongStats>, now: u64) -> bool {
+        match self {
+            Rule::Text(text) => Filter::All(text.clone()).matches(song),
+            Rule::Title(text) => Filter::Title(text.clone()).matches(song),
+            Rule::Artist(text) => Filter::Artist(text.clone()).matches(song),
+            Rule::Album(text) => Filter::Album(text.clone()).matches(song),
+            Rule::Genre(genre) => song
+                .genre
+                .as_ref()
+                .is_some_and(|song_genre| song_genre.eq_ignore_ascii_case(genre)),
+            Rule::Year([from, to]) => song.year.is_some_and(|year| (*from..=*to).contains(&year)),
+            Rule::PlayCountAbove(count) => stats.is_some_and(|stats| stats.play_count > *count),
+            Rule::SkipCountAbove(count) => stats.is_some_and(|stats| stats.skip_count > *count),
+            Rule::AddedWithinDays(days) => stats
+                .is_some_and(|stats| now.saturating_sub(stats.added) <= days.saturating_mul(DAY)),
+            Rule::RatingAtLeast(rating) => {
+                stats.is_some_and(|stats| stats.rating.is_some_and(|stars| stars >= *rating))
+            }
+            Rule::Favorite(favorite) => stats.is_some_and(|stats| stats.favorite) == *favorite,
+            Rule::NotPlayedForMonths(months) => stats
+                .and_then(|stats| stats.last_played)
+                .is_none_or(|last_played| {
+                    now.saturating_sub(last_played) > months.saturating_mul(MONTH)
+                }),
+            Rule::All(rules) => rules.iter().all(|rule| rule.matches(song, stats, now)),
+            Rule::Any(rules) => rules.iter().any(|rule| rule.matches(song, stats, now)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartSort {
+    Title,
+    Artist,
+    Album,
+    Year,
+    PlayCount,
+    Added,
+    Rating,
+    LastPlayed,
+    Random,
+}
+
+impl SmartSort {
+    /// `None` for random order.
+    fn sort_by(self) -> Option<SortBy> {
+        match self {
+            SmartSort::Title => Some(SortBy::Title),
+            SmartSort::Artist => Some(SortBy::Artist),
+            SmartSort::Album => Some(SortBy::Album),
+            SmartSort::Year => Some(SortBy::Year),
+            SmartSort::PlayCount => Some(SortBy::PlayCount),
+            SmartSort::Added => Some(SortBy::Added),
+            SmartSort::Rating => Some(SortBy::Rating),
+            SmartSort::LastPlayed => Some(SortBy::LastPlayed),
+            SmartSort::Random => None,
+        }
+    }
+}
+
+/// A saved query over the library, stored as TOML next to the other playlists.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmartPlaylist {
+    #[serde(default, rename = "match")]
+    match_mode: MatchMode,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    sort: Option<SmartSort>,
+    #[serde(default)]
+    order: SortOrder,
+    limit: Option<usize>,
+}
+
+impl SmartPlaylist {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        toml::from_str(&content).map_err(|err| {
+            format!(
+                "Invalid smart playlist {}: {}",
+                path.display(),
+                err.message()
+            )
+        })
+    }
+
+    /// The matching songs of `library`, sorted and limited.
+    pub fn evaluate(&self, library: &[Song]) -> Vec<Song> {
+        let now = now();
+        let db = LibraryDb::get();
+        let mut songs: Vec<Song> = library
+            .iter()
+            .filter(|song| {
+                let stats = db.stats(song);
+                match self.match_mode {
+                    MatchMode::All => self.rules.iter().all(|rule| rule.matches(song, stats, now)),
+                    MatchMode::Any => self.rules.iter().any(|rule| rule.matches(song, stats, now)),
+                }
+            })
+            .cloned()
+            .collect();
+        // Sorting reads the DB again
+        drop(db);
+        match self.sort.map(SmartSort::sort_by) {
+            Some(Some(sort_by)) => sort_songs(&mut songs, &sort_by, &self.order),
+            Some(None) => songs.shuffle(&mut rand::rng()),
+            None => {}
+        }
+        if let Some(limit) = self.limit {
+            songs.truncate(limit);
+        }
+        songs
+    }
+}
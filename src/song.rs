@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use ratatui::widgets::ListItem;
 
-use crate::utils::selecthandler::SelectHandlerItem;
+use crate::{library_db::LibraryDb, utils::selecthandler::SelectHandlerItem};
 
 #[derive(Clone)]
 pub enum SongType {
@@ -12,13 +12,26 @@ pub enum SongType {
     OnlineWithoutUrl,
 }
 
+#[derive(Clone, Default)]
+pub struct MusicBrainzIds {
+    pub recording: Option<String>,
+    pub artist: Option<String>,
+    pub release: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct Song {
     pub title: String,
     pub artist: Option<String>,
     pub album: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<i32>,
     pub total_time: u32,
     pub song_type: SongType,
+    /// How well the online source matches the recording, between 0 and 1
+    pub match_confidence: Option<f32>,
+    pub musicbrainz: MusicBrainzIds,
+    pub cover_art: Option<PathBuf>,
 }
 
 impl Song {
@@ -33,9 +46,44 @@ impl Song {
     pub fn is_online_downloaded(&self) -> bool {
         matches!(self.song_type, SongType::OnlineDownloaded { .. })
     }
+
+    /// Path or url, the same song has the same one in every list.
+    pub fn location(&self) -> Option<String> {
+        match &self.song_type {
+            SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => {
+                Some(path.to_string_lossy().to_string())
+            }
+            SongType::Online { url } => Some(url.clone()),
+            SongType::OnlineWithoutUrl => None,
+        }
+    }
+}
+
+/// A rating as filled and empty stars, like ★★★☆☆.
+pub fn stars(rating: u8) -> String {
+    let rating = rating.min(5) as usize;
+    format!("{}{}", "★".repeat(rating), "☆".repeat(5 - rating))
 }
+
 impl SelectHandlerItem for Song {
     fn list_item(&self) -> ListItem<'_> {
-        ListItem::new(format!("🎵 {} ({})", self.title.clone(), self.total_time))
+        let mut text = match self.match_confidence {
+            Some(confidence) => format!(
+                "🎵 {} ({}) [{:.0}%]",
+                self.title.clone(),
+                self.total_time,
+                confidence * 100.0
+            ),
+            None => format!("🎵 {} ({})", self.title.clone(), self.total_time),
+        };
+        if let Some(stats) = LibraryDb::get().stats(self) {
+            if let Some(rating) = stats.rating {
+                text.push_str(&format!(" {}", stars(rating)));
+            }
+            if stats.favorite {
+                text.push_str(" ♥");
+            }
+        }
+        ListItem::new(text)
     }
 }
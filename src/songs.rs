@@ -1,14 +1,30 @@
 use std::collections::HashMap;
 
-use crate::song::Song;
+use serde::Deserialize;
+
+use crate::{library_db::LibraryDb, song::Song};
 
 pub enum SortBy {
     Title,
     Artist,
     Album,
+    Year,
+    Rating,
+    PlayCount,
+    LastPlayed,
+    Added,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Number(u64),
 }
 
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SortOrder {
+    #[default]
     ASC,
     DESC,
 }
@@ -21,9 +37,60 @@ pub enum Filter {
     Album(String),
 }
 
+impl Filter {
+    /// Case-insensitive substring match, `All` looks at title, artist and album.
+    pub fn matches(&self, song: &Song) -> bool {
+        let contains = |field: Option<&String>, text: &str| {
+            field.is_some_and(|field| field.to_lowercase().contains(&text.to_lowercase()))
+        };
+        match self {
+            Filter::None => true,
+            Filter::All(text) => {
+                contains(Some(&song.title), text)
+                    || contains(song.artist.as_ref(), text)
+                    || contains(song.album.as_ref(), text)
+            }
+            Filter::Title(text) => contains(Some(&song.title), text),
+            Filter::Artist(text) => contains(song.artist.as_ref(), text),
+            Filter::Album(text) => contains(song.album.as_ref(), text),
+        }
+    }
+}
+
 pub struct SongDisplay {
     songs: HashMap<String, Song>,
     sorted_by: SortBy,
     sort_order: SortOrder,
     filter: Filter,
 }
+
+/// Sorts text case-insensitively, songs without the field go last either way. Ties keep their
+/// order. Rating, play count and times come from the library DB.
+pub fn sort_songs(songs: &mut [Song], sort_by: &SortBy, sort_order: &SortOrder) {
+    let db = LibraryDb::get();
+    let key = |song: &Song| {
+        let text = |field: Option<&String>| field.map(|field| SortKey::Text(field.to_lowercase()));
+        let stats = db.stats(song);
+        match sort_by {
+            SortBy::Title => text(Some(&song.title)),
+            SortBy::Artist => text(song.artist.as_ref()),
+            SortBy::Album => text(song.album.as_ref()),
+            SortBy::Year => song.year.map(|year| SortKey::Number(year.max(0) as u64)),
+            SortBy::Rating => stats
+                .and_then(|stats| stats.rating)
+                .map(|rating| SortKey::Number(rating.into())),
+            SortBy::PlayCount => stats.map(|stats| SortKey::Number(stats.play_count.into())),
+            SortBy::LastPlayed => stats
+                .and_then(|stats| stats.last_played)
+                .map(SortKey::Number),
+            SortBy::Added => stats.map(|stats| SortKey::Number(stats.added)),
+        }
+    };
+    songs.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a), Some(b)) => match sort_order {
+            SortOrder::ASC => a.cmp(&b),
+            SortOrder::DESC => b.cmp(&a),
+        },
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    });
+}
@@ -0,0 +1,253 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use chrono::{Duration, Local, NaiveDate};
+use serde::Serialize;
+
+use crate::{
+    history::HistoryEntry,
+    song::{Song, SongType},
+};
+
+/// Entries in each top list
+const TOP_COUNT: usize = 10;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Period {
+    #[default]
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl Period {
+    pub fn label(self) -> &'static str {
+        match self {
+            Period::Week => "week",
+            Period::Month => "month",
+            Period::Year => "year",
+            Period::All => "all time",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Period::Week => Period::Month,
+            Period::Month => Period::Year,
+            Period::Year => Period::All,
+            Period::All => Period::Week,
+        }
+    }
+
+    /// Days back from today, today included. `None` for all time.
+    fn days(self) -> Option<i64> {
+        match self {
+            Period::Week => Some(7),
+            Period::Month => Some(30),
+            Period::Year => Some(365),
+            Period::All => None,
+        }
+    }
+}
+
+/// Plays and listened ms of an artist, album or track.
+#[derive(Serialize)]
+pub struct PlayCount {
+    pub name: String,
+    pub plays: u32,
+    pub listened: u64,
+}
+
+/// Songs in the library with a format or genre.
+#[derive(Serialize)]
+pub struct LibraryCount {
+    pub name: String,
+    pub songs: usize,
+}
+
+#[derive(Serialize)]
+pub struct DayTotal {
+    pub date: NaiveDate,
+    pub listened: u64,
+}
+
+/// Listening figures for a period from the history, plus the make-up of the library.
+/// Durations are ms. Only plays past the play threshold count towards the top lists.
+#[derive(Serialize)]
+pub struct Stats {
+    pub period: &'static str,
+    pub plays: u32,
+    pub skips: u32,
+    pub listened: u64,
+    pub top_artists: Vec<PlayCount>,
+    pub top_albums: Vec<PlayCount>,
+    pub top_tracks: Vec<PlayCount>,
+    /// Every day of the period, oldest first
+    pub daily: Vec<DayTotal>,
+    pub library_songs: usize,
+    pub formats: Vec<LibraryCount>,
+    pub genres: Vec<LibraryCount>,
+}
+
+impl Stats {
+    pub fn compute(entries: &[HistoryEntry], library: &[Song], period: Period) -> Self {
+        let today = Local::now().date_naive();
+        let first_day = match period.days() {
+            Some(days) => today - Duration::days(days - 1),
+            None => entries
+                .iter()
+                .map(|entry| entry.local_time().date_naive())
+                .min()
+                .unwrap_or(today),
+        };
+        let entries: Vec<&HistoryEntry> = entries
+            .iter()
+            .filter(|entry| entry.local_time().date_naive() >= first_day)
+            .collect();
+
+        let mut artists = HashMap::new();
+        let mut albums = HashMap::new();
+        let mut tracks = HashMap::new();
+        let mut daily: HashMap<NaiveDate, u64> = HashMap::new();
+        for entry in &entries {
+            *daily.entry(entry.local_time().date_naive()).or_default() += entry.listened;
+            if !entry.completed {
+                continue;
+            }
+            let artist = entry.artist.as_deref().unwrap_or("Unknown artist");
+            count(&mut artists, artist.to_string(), entry);
+            if let Some(album) = &entry.album {
+                count(&mut albums, format!("{album} ({artist})"), entry);
+            }
+            count(&mut tracks, format!("{} - {artist}", entry.title), entry);
+        }
+
+        let mut formats = HashMap::new();
+        let mut genres = HashMap::new();
+        for song in library {
+            *formats.entry(format_name(song)).or_default() += 1;
+            let genre = song.genre.clone().unwrap_or("Unknown".to_string());
+            *genres.entry(genre).or_default() += 1;
+        }
+
+        Stats {
+            period: period.label(),
+            plays: entries.iter().filter(|entry| entry.completed).count() as u32,
+            skips: entries.iter().filter(|entry| !entry.completed).count() as u32,
+            listened: entries.iter().map(|entry| entry.listened).sum(),
+            top_artists: top(artists),
+            top_albums: top(albums),
+            top_tracks: top(tracks),
+            daily: first_day
+                .iter_days()
+                .take_while(|date| *date <= today)
+                .map(|date| DayTotal {
+                    date,
+                    listened: daily.get(&date).copied().unwrap_or(0),
+                })
+                .collect(),
+            library_songs: library.len(),
+            formats: by_size(formats),
+            genres: by_size(genres),
+        }
+    }
+
+    /// JSON or CSV, by the file extension.
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let content = match extension.as_str() {
+            "json" => serde_json::to_string_pretty(self).map_err(|err| err.to_string())?,
+            "csv" => self.to_csv(),
+            _ => return Err(format!("{} isn't a json or csv file", path.display())),
+        };
+        fs::write(path, content).map_err(|err| format!("Failed to write {}: {err}", path.display()))
+    }
+
+    /// One row per figure, the columns that don't apply to a section are empty.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("section,name,plays,listened_ms,songs\n");
+        csv.push_str(&format!(
+            "total,{},{},{},{}\n",
+            self.period, self.plays, self.listened, self.library_songs
+        ));
+        csv.push_str(&format!("skips,{},{},,\n", self.period, self.skips));
+        for (section, counts) in [
+            ("artist", &self.top_artists),
+            ("album", &self.top_albums),
+            ("track", &self.top_tracks),
+        ] {
+            for count in counts {
+                csv.push_str(&format!(
+                    "{section},{},{},{},\n",
+                    csv_field(&count.name),
+                    count.plays,
+                    count.listened
+                ));
+            }
+        }
+        for day in &self.daily {
+            csv.push_str(&format!("day,{},,{},\n", day.date, day.listened));
+        }
+        for (section, counts) in [("format", &self.formats), ("genre", &self.genres)] {
+            for count in counts {
+                csv.push_str(&format!(
+                    "{section},{},,,{}\n",
+                    csv_field(&count.name),
+                    count.songs
+                ));
+            }
+        }
+        csv
+    }
+}
+
+fn count(counts: &mut HashMap<String, (u32, u64)>, name: String, entry: &HistoryEntry) {
+    let (plays, listened) = counts.entry(name).or_default();
+    *plays += 1;
+    *listened += entry.listened;
+}
+
+/// Most played first, ties go to the longer listened.
+fn top(counts: HashMap<String, (u32, u64)>) -> Vec<PlayCount> {
+    let mut counts: Vec<PlayCount> = counts
+        .into_iter()
+        .map(|(name, (plays, listened))| PlayCount {
+            name,
+            plays,
+            listened,
+        })
+        .collect();
+    counts.sort_by(|a, b| (b.plays, b.listened, &a.name).cmp(&(a.plays, a.listened, &b.name)));
+    counts.truncate(TOP_COUNT);
+    counts
+}
+
+fn by_size(counts: HashMap<String, usize>) -> Vec<LibraryCount> {
+    let mut counts: Vec<LibraryCount> = counts
+        .into_iter()
+        .map(|(name, songs)| LibraryCount { name, songs })
+        .collect();
+    counts.sort_by(|a, b| b.songs.cmp(&a.songs).then_with(|| a.name.cmp(&b.name)));
+    counts
+}
+
+fn format_name(song: &Song) -> String {
+    match &song.song_type {
+        SongType::Local { path } | SongType::OnlineDownloaded { path, .. } => path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or("unknown".to_string()),
+        SongType::Online { .. } | SongType::OnlineWithoutUrl => "stream".to_string(),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::Path;
+
+use id3::TagLike;
+
+use crate::utils::flac;
+
+/// Owner of the POPM frame, players keep one per user
+const POPM_USER: &str = "music-ratatui";
+
+pub struct EmbeddedPicture {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl EmbeddedPicture {
+    pub fn extension(&self) -> &'static str {
+        match self.mime_type.as_str() {
+            "image/png" => "png",
+            "image/bmp" => "bmp",
+            _ => "jpg",
+        }
+    }
+}
+
+pub enum EmbeddedLyrics {
+    /// Lines with their start time in ms
+    Synced(Vec<(u64, String)>),
+    /// Plain text, which may itself be in LRC format
+    Text(String),
+}
+
+/// Tags read from an audio file, independent of the container format.
+#[derive(Default)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<i32>,
+    pub musicbrainz_release_id: Option<String>,
+    pub picture: Option<EmbeddedPicture>,
+}
+
+pub fn read(path: &Path) -> Option<TrackTags> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "flac" => read_flac(path),
+        "m4a" | "mp4" | "m4b" => read_mp4(path),
+        _ => read_id3(path),
+    }
+}
+
+/// Lyrics from USLT/SYLT frames, the Vorbis `LYRICS` comment or the MP4 lyrics atom.
+pub fn read_lyrics(path: &Path) -> Option<EmbeddedLyrics> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let text = match extension.as_str() {
+        "flac" => {
+            let metadata = flac::read_metadata(path).ok()?;
+            metadata
+                .comment("LYRICS")
+                .or(metadata.comment("UNSYNCEDLYRICS"))
+                .map(|s| s.to_string())
+        }
+        "m4a" | "mp4" | "m4b" => mp4ameta::Tag::read_from_path(path)
+            .ok()?
+            .lyrics()
+            .map(|s| s.to_string()),
+        _ => {
+            let tag = id3::Tag::read_from_path(path).ok()?;
+            let synced = tag.synchronised_lyrics().find(|lyrics| {
+                lyrics.timestamp_format == id3::frame::TimestampFormat::Ms
+                    && !lyrics.content.is_empty()
+            });
+            if let Some(lyrics) = synced {
+                return Some(EmbeddedLyrics::Synced(
+                    lyrics
+                        .content
+                        .iter()
+                        .map(|(time, text)| (*time as u64, text.clone()))
+                        .collect(),
+                ));
+            }
+            tag.lyrics().next().map(|lyrics| lyrics.text.clone())
+        }
+    };
+    text.filter(|text| !text.trim().is_empty())
+        .map(EmbeddedLyrics::Text)
+}
+
+/// Stores a 0 to 5 star rating and the play count in POPM (mp3) or FMPS (flac, mp4) tags.
+/// Other formats are left alone.
+pub fn write_stats(path: &Path, rating: Option<u8>, play_count: u32) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    // FMPS values are between 0 and 1
+    let fmps_rating = rating.map(|rating| format!("{:.1}", rating as f32 / 5.0));
+    let result = match extension.as_str() {
+        "mp3" => write_id3_stats(path, rating, play_count).map_err(|err| err.to_string()),
+        "flac" => flac::write_comments(
+            path,
+            &[
+                ("FMPS_RATING", fmps_rating),
+                ("FMPS_PLAYCOUNT", Some(play_count.to_string())),
+            ],
+        )
+        .map_err(|err| err.to_string()),
+        "m4a" | "mp4" | "m4b" => {
+            write_mp4_stats(path, fmps_rating, play_count).map_err(|err| err.to_string())
+        }
+        _ => return Ok(()),
+    };
+    result.map_err(|err| format!("Failed to write tags to {}: {err}", path.display()))
+}
+
+fn write_id3_stats(path: &Path, rating: Option<u8>, play_count: u32) -> id3::Result<()> {
+    let mut tag = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => {
+            id3::Tag::with_version(id3::Version::Id3v24)
+        }
+        Err(err) => return Err(err),
+    };
+    // The common mapping of stars to POPM values, 0 means unrated
+    let rating = match rating {
+        None | Some(0) => 0,
+        Some(1) => 1,
+        Some(2) => 64,
+        Some(3) => 128,
+        Some(4) => 196,
+        Some(_) => 255,
+    };
+    tag.add_frame(id3::frame::Popularimeter {
+        user: POPM_USER.to_string(),
+        rating,
+        counter: play_count.into(),
+    });
+    // Existing tags keep their version, players that only read v2.3 would lose an upgraded one.
+    // v2.2 has no POPM frame, it goes to v2.3 which is read the most widely.
+    let version = match tag.version() {
+        id3::Version::Id3v22 => id3::Version::Id3v23,
+        version => version,
+    };
+    tag.write_to_path(path, version)
+}
+
+fn write_mp4_stats(
+    path: &Path,
+    fmps_rating: Option<String>,
+    play_count: u32,
+) -> mp4ameta::Result<()> {
+    let mut tag = mp4ameta::Tag::read_from_path(path)?;
+    let rating_ident = mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "FMPS_Rating");
+    match fmps_rating {
+        Some(rating) => tag.set_data(rating_ident, mp4ameta::Data::Utf8(rating)),
+        None => tag.remove_data_of(&rating_ident),
+    }
+    tag.set_data(
+        mp4ameta::FreeformIdent::new_static("com.apple.iTunes", "FMPS_Playcount"),
+        mp4ameta::Data::Utf8(play_count.to_string()),
+    );
+    tag.write_to_path(path)
+}
+
+fn read_id3(path: &Path) -> Option<TrackTags> {
+    let tag = id3::Tag::read_from_path(path).ok()?;
+    let picture = tag
+        .pictures()
+        .find(|picture| picture.picture_type == id3::frame::PictureType::CoverFront)
+        .or(tag.pictures().next())
+        .map(|picture| EmbeddedPicture {
+            mime_type: picture.mime_type.clone(),
+            data: picture.data.clone(),
+        });
+    Some(TrackTags {
+        title: tag.title().map(|s| s.to_string()),
+        artist: tag.artist().map(|s| s.to_string()),
+        album: tag.album().map(|s| s.to_string()),
+        album_artist: tag.album_artist().map(|s| s.to_string()),
+        genre: tag.genre_parsed().map(|s| s.to_string()),
+        year: tag
+            .year()
+            .or(tag.date_recorded().map(|timestamp| timestamp.year)),
+        musicbrainz_release_id: tag
+            .extended_texts()
+            .find(|text| text.description == "MusicBrainz Album Id")
+            .map(|text| text.value.clone()),
+        picture,
+    })
+}
+
+fn read_flac(path: &Path) -> Option<TrackTags> {
+    let metadata = flac::read_metadata(path).ok()?;
+    Some(TrackTags {
+        title: metadata.comment("TITLE").map(|s| s.to_string()),
+        artist: metadata.comment("ARTIST").map(|s| s.to_string()),
+        album: metadata.comment("ALBUM").map(|s| s.to_string()),
+        album_artist: metadata.comment("ALBUMARTIST").map(|s| s.to_string()),
+        genre: metadata.comment("GENRE").map(|s| s.to_string()),
+        year: metadata.comment("DATE").and_then(parse_year),
+        musicbrainz_release_id: metadata
+            .comment("MUSICBRAINZ_ALBUMID")
+            .map(|s| s.to_string()),
+        picture: metadata.front_cover().map(|picture| EmbeddedPicture {
+            mime_type: picture.mime_type.clone(),
+            data: picture.data.clone(),
+        }),
+    })
+}
+
+fn read_mp4(path: &Path) -> Option<TrackTags> {
+    let tag = mp4ameta::Tag::read_from_path(path).ok()?;
+    let picture = tag.artwork().map(|artwork| EmbeddedPicture {
+        mime_type: match artwork.fmt {
+            mp4ameta::ImgFmt::Png => "image/png".to_string(),
+            mp4ameta::ImgFmt::Bmp => "image/bmp".to_string(),
+            mp4ameta::ImgFmt::Jpeg => "image/jpeg".to_string(),
+        },
+        data: artwork.data.to_vec(),
+    });
+    Some(TrackTags {
+        title: tag.title().map(|s| s.to_string()),
+        artist: tag.artist().map(|s| s.to_string()),
+        album: tag.album().map(|s| s.to_string()),
+        album_artist: tag.album_artist().map(|s| s.to_string()),
+        genre: tag.genre().map(|s| s.to_string()),
+        year: tag.year().and_then(parse_year),
+        musicbrainz_release_id: tag
+            .strings_of(&mp4ameta::FreeformIdent::new_static(
+                "com.apple.iTunes",
+                "MusicBrainz Album Id",
+            ))
+            .next()
+            .map(|s| s.to_string()),
+        picture,
+    })
+}
+
+/// The year of a date like `2003`, `2003-04` or `2003-04-21`.
+fn parse_year(date: &str) -> Option<i32> {
+    date.trim().get(..4)?.parse().ok()
+}
@@ -0,0 +1,286 @@
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use crossterm::{cursor::MoveTo, queue, terminal::window_size};
+use image::{DynamicImage, RgbImage, imageops::FilterType};
+use log::warn;
+use ratatui::{buffer::Buffer, layout::Rect, style::Color};
+
+const FALLBACK_CELL_SIZE: (u32, u32) = (8, 16);
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+    HalfBlocks,
+}
+
+impl GraphicsProtocol {
+    /// Guesses what the terminal supports from the environment. Terminals that can't be
+    /// identified get the half-block fallback, which works everywhere with true color.
+    pub fn detect() -> Self {
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term == "xterm-ghostty"
+        {
+            GraphicsProtocol::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm") {
+            GraphicsProtocol::Iterm2
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            GraphicsProtocol::Sixel
+        } else {
+            GraphicsProtocol::HalfBlocks
+        }
+    }
+}
+
+struct LoadedImage {
+    path: PathBuf,
+    image: DynamicImage,
+}
+
+/// Draws the current cover art. Half blocks are drawn into the ratatui buffer; the graphics
+/// protocols are written straight to the terminal after each frame, on top of cells ratatui skips.
+pub struct TerminalImage {
+    protocol: GraphicsProtocol,
+    loaded: Option<LoadedImage>,
+    pending: Option<Rect>,
+    displayed: Option<(PathBuf, Rect)>,
+}
+
+impl TerminalImage {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        TerminalImage {
+            protocol,
+            loaded: None,
+            pending: None,
+            displayed: None,
+        }
+    }
+
+    pub fn render(&mut self, path: Option<&Path>, area: Rect, buffer: &mut Buffer) {
+        self.pending = None;
+        let Some(path) = path else {
+            return;
+        };
+        if area.width == 0 || area.height == 0 || !self.load(path) {
+            return;
+        }
+        match self.protocol {
+            GraphicsProtocol::HalfBlocks => self.render_half_blocks(area, buffer),
+            _ => {
+                for y in area.top()..area.bottom() {
+                    for x in area.left()..area.right() {
+                        buffer[(x, y)].set_skip(true);
+                    }
+                }
+                self.pending = Some(area);
+            }
+        }
+    }
+
+    /// Sends the image to the terminal if it changed since the last frame.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.protocol == GraphicsProtocol::HalfBlocks {
+            return Ok(());
+        }
+        let wanted = match (&self.loaded, self.pending) {
+            (Some(loaded), Some(area)) => Some((loaded.path.clone(), area)),
+            _ => None,
+        };
+        if wanted == self.displayed {
+            return Ok(());
+        }
+
+        let mut stdout = io::stdout();
+        if self.protocol == GraphicsProtocol::Kitty {
+            write!(stdout, "\x1b_Ga=d,q=2\x1b\\")?;
+        }
+        if let (Some(loaded), Some((_, area))) = (&self.loaded, &wanted) {
+            queue!(stdout, MoveTo(area.x, area.y))?;
+            match self.protocol {
+                GraphicsProtocol::Kitty => write_kitty(&mut stdout, &loaded.image, *area)?,
+                GraphicsProtocol::Iterm2 => write_iterm2(&mut stdout, &loaded.path, *area)?,
+                GraphicsProtocol::Sixel => write_sixel(&mut stdout, &loaded.image, *area)?,
+                GraphicsProtocol::HalfBlocks => {}
+            }
+        }
+        stdout.flush()?;
+        self.displayed = wanted;
+        Ok(())
+    }
+
+    fn load(&mut self, path: &Path) -> bool {
+        if self.loaded.as_ref().map(|loaded| loaded.path.as_path()) == Some(path) {
+            return true;
+        }
+        match image::open(path) {
+            Ok(image) => {
+                self.loaded = Some(LoadedImage {
+                    path: path.to_path_buf(),
+                    image,
+                });
+                true
+            }
+            Err(err) => {
+                warn!("Failed to decode cover art {:?}: {err}", path);
+                self.loaded = None;
+                false
+            }
+        }
+    }
+
+    fn render_half_blocks(&self, area: Rect, buffer: &mut Buffer) {
+        let Some(loaded) = &self.loaded else {
+            return;
+        };
+        // Every cell shows two vertically stacked pixels
+        let image = loaded
+            .image
+            .resize(
+                area.width as u32,
+                area.height as u32 * 2,
+                FilterType::Triangle,
+            )
+            .to_rgb8();
+        let offset_x = (area.width as u32 - image.width()) / 2;
+        let offset_y = (area.height as u32 * 2 - image.height()) / 4;
+        for y in 0..image.height().div_ceil(2) {
+            for x in 0..image.width() {
+                let top = image.get_pixel(x, y * 2);
+                let bottom = image.get_pixel(x, (y * 2 + 1).min(image.height() - 1));
+                let cell = &mut buffer[(
+                    area.x + (x + offset_x) as u16,
+                    area.y + (y + offset_y) as u16,
+                )];
+                cell.set_char('▀')
+                    .set_fg(Color::Rgb(top[0], top[1], top[2]))
+                    .set_bg(Color::Rgb(bottom[0], bottom[1], bottom[2]));
+            }
+        }
+    }
+}
+
+fn cell_size() -> (u32, u32) {
+    match window_size() {
+        Ok(size) if size.width > 0 && size.columns > 0 && size.height > 0 && size.rows > 0 => (
+            size.width as u32 / size.columns as u32,
+            size.height as u32 / size.rows as u32,
+        ),
+        _ => FALLBACK_CELL_SIZE,
+    }
+}
+
+fn fit_to_area(image: &DynamicImage, area: Rect) -> RgbImage {
+    let (cell_width, cell_height) = cell_size();
+    image
+        .resize(
+            area.width as u32 * cell_width,
+            area.height as u32 * cell_height,
+            FilterType::Triangle,
+        )
+        .to_rgb8()
+}
+
+fn write_kitty(out: &mut impl Write, image: &DynamicImage, area: Rect) -> io::Result<()> {
+    let image = fit_to_area(image, area);
+    let encoded = STANDARD.encode(image.as_raw());
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = if index + 1 < chunks.len() { 1 } else { 0 };
+        let chunk = String::from_utf8_lossy(chunk);
+        if index == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},c={},r={},C=1,q=2,m={more};{chunk}\x1b\\",
+                image.width(),
+                image.height(),
+                area.width,
+                area.height
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={more};{chunk}\x1b\\")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_iterm2(out: &mut impl Write, path: &Path, area: Rect) -> io::Result<()> {
+    let data = fs::read(path)?;
+    write!(
+        out,
+        "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07",
+        data.len(),
+        area.width,
+        area.height,
+        STANDARD.encode(&data)
+    )
+}
+
+fn write_sixel(out: &mut impl Write, image: &DynamicImage, area: Rect) -> io::Result<()> {
+    let image = fit_to_area(image, area);
+    out.write_all(encode_sixel(&image).as_bytes())
+}
+
+// Quantizes to a 6x6x6 color cube, which keeps the encoder simple and is plenty for cover art
+fn encode_sixel(image: &RgbImage) -> String {
+    let (width, height) = image.dimensions();
+    let palette_index = |pixel: &image::Rgb<u8>| -> usize {
+        let level = |channel: u8| (channel as usize * 5 + 127) / 255;
+        level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2])
+    };
+
+    let mut sixel = format!("\x1bP0;1q\"1;1;{width};{height}");
+    for index in 0..216 {
+        let percent = |level: usize| level * 100 / 5;
+        let _ = write!(
+            sixel,
+            "#{index};2;{};{};{}",
+            percent(index / 36),
+            percent(index / 6 % 6),
+            percent(index % 6)
+        );
+    }
+
+    for band in (0..height).step_by(6) {
+        let band_height = (height - band).min(6);
+        let mut rows: Vec<Vec<u8>> = vec![vec![0; width as usize]; 216];
+        let mut used = [false; 216];
+        for x in 0..width {
+            for offset in 0..band_height {
+                let color = palette_index(image.get_pixel(x, band + offset));
+                rows[color][x as usize] |= 1 << offset;
+                used[color] = true;
+            }
+        }
+        for (color, row) in rows.iter().enumerate().filter(|(color, _)| used[*color]) {
+            let _ = write!(sixel, "#{color}");
+            let mut x = 0;
+            while x < row.len() {
+                let bits = row[x];
+                let run = row[x..].iter().take_while(|other| **other == bits).count();
+                let character = (63 + bits) as char;
+                if run > 3 {
+                    let _ = write!(sixel, "!{run}{character}");
+                } else {
+                    sixel.extend(std::iter::repeat_n(character, run));
+                }
+                x += run;
+            }
+            sixel.push('$');
+        }
+        sixel.push('-');
+    }
+    sixel.push_str("\x1b\\");
+    sixel
+}
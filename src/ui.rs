@@ -1,29 +1,38 @@
+use std::path::Path;
+
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Margin, Rect},
-    style::{Color, Modifier, Style, Stylize},
+    style::{Modifier, Style, Stylize},
     symbols,
-    text::Text,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, canvas::Line},
+    text::{Line, Span, Text},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, Borders, Clear, LineGauge, List, ListItem, ListState,
+        Paragraph, Sparkline,
+    },
 };
 
 use crate::{
     App, CurrentScreen, FocusedWindowMain, MediaDisplayType,
+    config::Config,
+    display_handlers::{help_handler::HelpLine, notification_handler::NotificationLevel},
     events::{format_ms_to_duration_string, musicplayer::PlayerStatus},
+    stats::{LibraryCount, PlayCount},
     utils::selecthandler::{SelectHandlerItem, Selectable},
 };
 
 pub fn render(frame: &mut Frame, app: &mut App) {
+    let media_height = if app.album_art.is_some() { 8 } else { 5 };
     let layout = ratatui::layout::Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Fill(1), Constraint::Length(6)])
+        .constraints([Constraint::Fill(1), Constraint::Length(media_height + 1)])
         .split(frame.area());
 
     create_upper_rect(app, frame, layout[0]);
 
     let layout = ratatui::layout::Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(5), Constraint::Length(1)])
+        .constraints([Constraint::Length(media_height), Constraint::Length(1)])
         .split(layout[1]);
 
     let media_rect = layout[0];
@@ -31,15 +40,114 @@ pub fn render(frame: &mut Frame, app: &mut App) {
 
     render_controls(app, frame, controls_rect);
 
+    let cover_art = app
+        .get_current_song()
+        .and_then(|song| song.cover_art.clone());
+    // Cells are roughly twice as tall as they are wide, so this keeps the art square
+    let album_art_width = match (&app.album_art, &cover_art) {
+        (Some(_), Some(_)) => media_rect.height.saturating_sub(2) * 2 + 2,
+        _ => 0,
+    };
     let layout = ratatui::layout::Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(20), Constraint::Fill(1)])
+        .constraints([
+            Constraint::Length(album_art_width),
+            Constraint::Length(20),
+            Constraint::Fill(1),
+        ])
         .split(media_rect);
-    let media_info_rect = layout[0];
-    let media_progress_rect = layout[1];
+    let album_art_rect = layout[0];
+    let media_info_rect = layout[1];
+    let media_progress_rect = layout[2];
 
+    // Terminal graphics are drawn above the cells, an overlay on top of them would be hidden
+    let covered = overlays(app, frame.area())
+        .iter()
+        .any(|overlay| overlay.intersects(album_art_rect));
+    let cover_art = cover_art.filter(|_| !covered);
+    render_album_art(app, frame, album_art_rect, cover_art.as_deref());
     render_media_info(app, frame, media_info_rect);
     render_media_progressbar(app, frame, media_progress_rect);
+
+    match app.current_screen {
+        CurrentScreen::Help(_) => render_help(app, frame),
+        CurrentScreen::Stats(_) => render_stats(app, frame),
+        _ => {}
+    }
+    render_notifications(app, frame);
+}
+
+/// The names of all media views, with `label` in brackets in place of the shown one.
+fn view_tabs(shown: MediaDisplayType, label: &str) -> String {
+    [
+        (MediaDisplayType::Songs, "Songs"),
+        (MediaDisplayType::Folders, "Folder"),
+        (MediaDisplayType::Playlists, "Playlists"),
+        (MediaDisplayType::Search, "Search"),
+        (MediaDisplayType::Lyrics, "Lyrics"),
+        (MediaDisplayType::Log, "Log"),
+        (MediaDisplayType::History, "History"),
+    ]
+    .into_iter()
+    .map(|(view, name)| {
+        if view == shown {
+            format!("[{label}]")
+        } else {
+            name.to_string()
+        }
+    })
+    .collect::<Vec<String>>()
+    .join(" | ")
+}
+
+/// Where the popups drawn over the views will be this frame.
+fn overlays(app: &mut App, area: Rect) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    match app.current_screen {
+        CurrentScreen::Help(_) => rects.push(help_rect(area)),
+        CurrentScreen::Stats(_) if app.stats.is_some() => rects.push(stats_rect(area)),
+        _ => {}
+    }
+    let notifications = app.notification_handler.visible().len();
+    if notifications > 0 {
+        rects.push(notifications_rect(notifications, area));
+    }
+    rects
+}
+
+/// Newest at the bottom, in the top right corner below the search input.
+fn notifications_rect(count: usize, area: Rect) -> Rect {
+    let width = area.width.saturating_sub(2).min(60);
+    let height = (count as u16 + 2).min(area.height.saturating_sub(3));
+    Rect {
+        x: area.right().saturating_sub(width + 1),
+        y: area.y + 3,
+        width,
+        height,
+    }
+}
+
+fn render_notifications(app: &mut App, frame: &mut Frame) {
+    let notifications = app.notification_handler.visible();
+    if notifications.is_empty() {
+        return;
+    }
+    let rect = notifications_rect(notifications.len(), frame.area());
+    let lines: Vec<Line> = notifications
+        .iter()
+        .map(|notification| {
+            let line = Line::from(notification.message.as_str());
+            match notification.level {
+                NotificationLevel::Error => line.fg(Config::get().theme.error),
+                NotificationLevel::Warning => line.fg(Config::get().theme.warning),
+            }
+        })
+        .collect();
+    frame.render_widget(Clear, rect);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Notifications")),
+        rect,
+    );
 }
 
 fn create_upper_rect(app: &mut App, frame: &mut Frame, rect: Rect) {
@@ -66,12 +174,16 @@ fn create_upper_rect(app: &mut App, frame: &mut Frame, rect: Rect) {
 fn render_search(app: &mut App, frame: &mut Frame, rect: Rect) {
     let input = Paragraph::new(app.search_handler.get_query())
         .style(match &app.current_screen {
-            CurrentScreen::Main(focused_window) => match focused_window {
-                FocusedWindowMain::Search => Style::default().fg(Color::Yellow),
-                _ => Style::default(),
-            },
+            CurrentScreen::Main(focused_window) | CurrentScreen::Command(focused_window) => {
+                match focused_window {
+                    FocusedWindowMain::Search => Style::default().fg(Config::get().theme.accent),
+                    _ => Style::default(),
+                }
+            }
+            CurrentScreen::Help(_) | CurrentScreen::Stats(_) => Style::default(),
         })
         .block(Block::bordered().title("Input"));
+    app.search_rect = rect;
     frame.render_widget(input, rect);
 }
 
@@ -89,7 +201,12 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
 
     let list_state_ref: &mut ListState;
     let raw_selectable_items: Vec<Selectable>;
-    let block_title_str: &str;
+    let block_title_str: String;
+    let browse_handler_arc = app.search_handler.browse_handler.clone();
+    let mut browse_handler;
+    let lyrics_handler_arc = app.lyrics_handler.clone();
+    let mut lyrics_handler;
+    let mut highlighted_index = None;
 
     match app.selected_media_display_type {
         MediaDisplayType::Songs => {
@@ -99,7 +216,7 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
                 .iter()
                 .map(|item| Selectable::Song(item.clone()))
                 .collect();
-            block_title_str = "[Songs] | Folder";
+            block_title_str = view_tabs(MediaDisplayType::Songs, "Songs");
         }
         MediaDisplayType::Folders => {
             let (state, items) = app
@@ -111,7 +228,64 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
                 .iter()
                 .map(|item| Selectable::Node(item.clone()))
                 .collect();
-            block_title_str = "Songs | [Folder]";
+            block_title_str = view_tabs(MediaDisplayType::Folders, "Folder");
+        }
+        MediaDisplayType::Playlists => {
+            let title = app.playlist_handler.title();
+            let (state, items) = app
+                .playlist_handler
+                .select_handler
+                .select_handler_state_and_items();
+            list_state_ref = state;
+            raw_selectable_items = items.into_iter().map(Selectable::PlaylistNode).collect();
+            block_title_str = view_tabs(MediaDisplayType::Playlists, &title);
+        }
+        MediaDisplayType::Search => {
+            browse_handler = browse_handler_arc
+                .lock()
+                .expect("Browse handler lock poisoned");
+            let breadcrumb = browse_handler.breadcrumb();
+            let (state, items) = browse_handler
+                .select_handler()
+                .select_handler_state_and_items();
+            list_state_ref = state;
+            raw_selectable_items = items
+                .iter()
+                .map(|item| Selectable::BrowseNode(item.clone()))
+                .collect();
+            block_title_str = view_tabs(MediaDisplayType::Search, &breadcrumb);
+        }
+        MediaDisplayType::Lyrics => {
+            lyrics_handler = lyrics_handler_arc
+                .lock()
+                .expect("Lyrics handler lock poisoned");
+            let title = lyrics_handler.title();
+            highlighted_index = lyrics_handler.current_line();
+            let (state, items) = lyrics_handler
+                .select_handler
+                .select_handler_state_and_items();
+            list_state_ref = state;
+            raw_selectable_items = items.into_iter().map(Selectable::LyricLine).collect();
+            block_title_str = view_tabs(MediaDisplayType::Lyrics, &title);
+        }
+        MediaDisplayType::Log => {
+            let title = app.log_handler.title();
+            let (state, items) = app
+                .log_handler
+                .select_handler
+                .select_handler_state_and_items();
+            list_state_ref = state;
+            raw_selectable_items = items.into_iter().map(Selectable::LogLine).collect();
+            block_title_str = view_tabs(MediaDisplayType::Log, &title);
+        }
+        MediaDisplayType::History => {
+            let (state, items) = app
+                .history_handler
+                .select_handler
+                .select_handler_state_and_items();
+            list_state_ref = state;
+            raw_selectable_items = items.into_iter().map(Selectable::HistoryNode).collect();
+            block_title_str = view_tabs(MediaDisplayType::History, "History");
         }
     };
 
@@ -121,13 +295,16 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
         .collect();
 
     let is_focused = match &app.current_screen {
-        CurrentScreen::Main(focused_window) => match focused_window {
-            FocusedWindowMain::Media => true,
-            _ => false,
-        },
+        CurrentScreen::Main(focused_window) | CurrentScreen::Command(focused_window) => {
+            match focused_window {
+                FocusedWindowMain::Media => true,
+                _ => false,
+            }
+        }
+        CurrentScreen::Help(_) | CurrentScreen::Stats(_) => false,
     };
 
-    let block_title = block_title_str.to_string() + if is_focused { "(*)" } else { "" };
+    let block_title = block_title_str + if is_focused { "(*)" } else { "" };
     let media_select_block = Block::default()
         .title(block_title)
         .border_set(border_set)
@@ -140,7 +317,14 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
                 .enumerate()
                 .map(|(index, item)| {
                     if Some(index) == selected_index && is_focused {
-                        item.clone().style(Style::default().bg(Color::Yellow))
+                        item.clone()
+                            .style(Style::default().bg(Config::get().theme.selection))
+                    } else if Some(index) == highlighted_index {
+                        item.clone().style(
+                            Style::default()
+                                .fg(Config::get().theme.playing)
+                                .add_modifier(Modifier::BOLD),
+                        )
                     } else {
                         item.clone().style(Style::default())
                     }
@@ -149,17 +333,28 @@ fn render_media_selection(app: &mut App, frame: &mut Frame, rect: Rect) {
         )
         .block(media_select_block);
 
+    app.media_list_len = list_items.len();
     frame.render_stateful_widget(list, rect, list_state_ref);
+    app.media_list_offset = list_state_ref.offset();
+    app.media_list_rect = Rect {
+        x: rect.x + 1,
+        y: rect.y + 1,
+        width: rect.width.saturating_sub(2),
+        height: rect.height.saturating_sub(1),
+    };
 }
 
 fn render_queue(app: &mut App, frame: &mut Frame, rect: Rect) {
     let selected_queue_index = app.queue_select_handler.state().selected();
     let is_focused = match &mut app.current_screen {
-        CurrentScreen::Main(focused_window) => match focused_window {
-            FocusedWindowMain::Queue => true,
-            FocusedWindowMain::Media => false,
-            FocusedWindowMain::Search => false,
-        },
+        CurrentScreen::Main(focused_window) | CurrentScreen::Command(focused_window) => {
+            match focused_window {
+                FocusedWindowMain::Queue => true,
+                FocusedWindowMain::Media => false,
+                FocusedWindowMain::Search => false,
+            }
+        }
+        CurrentScreen::Help(_) | CurrentScreen::Stats(_) => false,
     };
     let block_title = "Queue".to_string() + if is_focused { "(*)" } else { "" };
     let list = List::default()
@@ -171,16 +366,19 @@ fn render_queue(app: &mut App, frame: &mut Frame, rect: Rect) {
                 .map(|(index, song)| {
                     if Some(index) == app.player_information.playing_index {
                         if Some(index) == selected_queue_index && is_focused {
-                            ListItem::new(format!("🎶 {}", song.title.clone()))
-                                .style(Style::default().light_green().bg(Color::Yellow))
+                            ListItem::new(format!("🎶 {}", song.title.clone())).style(
+                                Style::default()
+                                    .fg(Config::get().theme.playing)
+                                    .bg(Config::get().theme.selection),
+                            )
                         } else {
                             ListItem::new(format!("🎶 {}", song.title.clone()))
-                                .style(Style::default().light_green())
+                                .style(Style::default().fg(Config::get().theme.playing))
                         }
                     } else {
                         if Some(index) == selected_queue_index && is_focused {
                             ListItem::new(song.title.clone())
-                                .style(Style::default().bg(Color::Yellow))
+                                .style(Style::default().bg(Config::get().theme.selection))
                         } else {
                             ListItem::new(song.title.clone()).style(Style::default())
                         }
@@ -198,9 +396,36 @@ fn render_queue(app: &mut App, frame: &mut Frame, rect: Rect) {
                 })
                 .borders(Borders::RIGHT | Borders::TOP),
         );
+    app.queue_list_rect = Rect {
+        x: rect.x,
+        y: rect.y + 1,
+        width: rect.width.saturating_sub(1),
+        height: rect.height.saturating_sub(1),
+    };
     frame.render_stateful_widget(list, rect, &mut app.queue_select_handler.state());
 }
 
+fn render_album_art(app: &mut App, frame: &mut Frame, rect: Rect, cover_art: Option<&Path>) {
+    let Some(album_art) = &mut app.album_art else {
+        return;
+    };
+    if rect.width == 0 {
+        album_art.render(None, rect, frame.buffer_mut());
+        return;
+    }
+    let block = Block::default()
+        .border_set(symbols::border::Set {
+            top_left: symbols::line::NORMAL.vertical_right,
+            top_right: symbols::line::NORMAL.horizontal_down,
+            bottom_right: symbols::line::NORMAL.horizontal_up,
+            ..symbols::border::PLAIN
+        })
+        .borders(Borders::ALL);
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+    album_art.render(cover_art, inner, frame.buffer_mut());
+}
+
 fn render_media_info(app: &App, frame: &mut Frame, rect: Rect) {
     let mut paragraph = Paragraph::new("");
 
@@ -233,39 +458,298 @@ fn render_media_info(app: &App, frame: &mut Frame, rect: Rect) {
     frame.render_widget(paragraph, rect);
 }
 
-fn render_media_progressbar(app: &App, frame: &mut Frame, rect: Rect) {
-    let progress = match &app.player_information.status {
-        PlayerStatus::Playing(song) => {
-            format!(
-                "{}/{}",
-                format_ms_to_duration_string(app.player_information.passed_time),
-                format_ms_to_duration_string(song.total_time as u64)
-            )
-        }
-        PlayerStatus::Paused(song) => {
-            format!(
-                "{}/{}",
-                format_ms_to_duration_string(app.player_information.passed_time),
-                format_ms_to_duration_string(song.total_time as u64)
-            )
+fn render_media_progressbar(app: &mut App, frame: &mut Frame, rect: Rect) {
+    let block = Block::default()
+        .border_set(symbols::border::Set {
+            top_left: symbols::line::NORMAL.horizontal_down,
+            bottom_left: symbols::line::NORMAL.vertical_left,
+            top_right: symbols::line::NORMAL.vertical_left,
+
+            ..symbols::border::PLAIN
+        })
+        .borders(Borders::BOTTOM | Borders::TOP | Borders::RIGHT);
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+    app.progress_bar_rect = None;
+
+    let song = match &app.player_information.status {
+        PlayerStatus::Playing(song) | PlayerStatus::Paused(song) => song,
+        _ => {
+            frame.render_widget(Paragraph::new("No Audio"), inner);
+            return;
         }
-        _ => "No Audio".to_string(),
     };
-    let paragraph = Paragraph::new(progress).block(
-        Block::default()
-            .border_set(symbols::border::Set {
-                top_left: symbols::line::NORMAL.horizontal_down,
-                bottom_left: symbols::line::NORMAL.vertical_left,
-                top_right: symbols::line::NORMAL.vertical_left,
+    let total_time = song.total_time as u64;
+    let passed_time = app.player_information.passed_time.min(total_time);
+    let label = if app.show_remaining_time {
+        format!(
+            "{} / -{}",
+            format_ms_to_duration_string(passed_time),
+            format_ms_to_duration_string(total_time - passed_time)
+        )
+    } else {
+        format!(
+            "{} / {}",
+            format_ms_to_duration_string(passed_time),
+            format_ms_to_duration_string(total_time)
+        )
+    };
+    let ratio = if total_time > 0 {
+        passed_time as f64 / total_time as f64
+    } else {
+        0.0
+    };
 
-                ..symbols::border::PLAIN
-            })
-            .borders(Borders::BOTTOM | Borders::TOP | Borders::RIGHT),
-    );
-    frame.render_widget(paragraph, rect);
+    let layout = ratatui::layout::Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .split(inner);
+    let gauge_rect = layout[1];
+    // LineGauge draws the label first and leaves one column before the line
+    let label_width = label.chars().count() as u16 + 1;
+    if gauge_rect.width > label_width {
+        app.progress_bar_rect = Some(Rect {
+            x: gauge_rect.x + label_width,
+            width: gauge_rect.width - label_width,
+            ..gauge_rect
+        });
+    }
+    let gauge = LineGauge::default()
+        .label(label)
+        .ratio(ratio)
+        .line_set(symbols::line::THICK)
+        .filled_style(Style::default().fg(Config::get().theme.playing))
+        .unfilled_style(Style::default().dark_gray());
+    frame.render_widget(gauge, gauge_rect);
 }
 
 fn render_controls(app: &App, frame: &mut Frame, rect: Rect) {
-    let paragraph = Paragraph::new("Controls");
+    if let CurrentScreen::Command(_) = app.current_screen {
+        let input = app.command_line.input();
+        frame.render_widget(Paragraph::new(format!(":{input}")), rect);
+        let cursor = rect.x + 1 + input.chars().count() as u16;
+        frame.set_cursor_position((cursor.min(rect.right().saturating_sub(1)), rect.y));
+        return;
+    }
+    if let Some(message) = &app.status_message {
+        frame.render_widget(
+            Paragraph::new(message.as_str()).fg(Config::get().theme.error),
+            rect,
+        );
+        return;
+    }
+    let contexts = app.key_contexts();
+    let hints = app.keyboard_handler.hints(&contexts);
+
+    // Whole hints only, so a narrow terminal never shows half a key
+    let mut spans: Vec<Span> = Vec::new();
+    let mut width = 0;
+    if let Some(pending) = app.keyboard_handler.pending_keys() {
+        width += pending.chars().count() + 2;
+        spans.push(Span::raw(pending).light_cyan().bold());
+        spans.push(Span::raw("  "));
+    }
+    for (index, (key, action)) in hints.iter().enumerate() {
+        let key = key.to_string();
+        let separator = if index == 0 { "" } else { "  " };
+        let hint_width = separator.len() + key.chars().count() + 1 + action.label().chars().count();
+        let remaining = hints.len() - index - 1;
+        let reserved = if remaining > 0 { 3 } else { 0 };
+        if width + hint_width + reserved > rect.width as usize {
+            spans.push(Span::raw(" …").dark_gray());
+            break;
+        }
+        width += hint_width;
+        spans.push(Span::raw(separator));
+        spans.push(Span::raw(key).fg(Config::get().theme.accent).bold());
+        spans.push(Span::raw(format!(" {}", action.label())));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), rect);
+}
+
+fn help_rect(area: Rect) -> Rect {
+    let width = area.width.saturating_sub(4).min(60);
+    let height = area.height.saturating_sub(4);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn render_help(app: &mut App, frame: &mut Frame) {
+    let rect = help_rect(frame.area());
+
+    let help_lines = app.help_handler.lines(app.keyboard_handler.keymap());
+    let lines: Vec<Line> = if help_lines.is_empty() {
+        vec![Line::from("No matching bindings").dark_gray()]
+    } else {
+        help_lines
+            .iter()
+            .map(|line| match line {
+                HelpLine::Heading(heading) => Line::from(heading.to_string()).bold(),
+                HelpLine::Binding { keys, action } => Line::from(vec![
+                    Span::raw(format!("  {keys:<16}")).fg(Config::get().theme.accent),
+                    Span::raw(action.to_string()),
+                ]),
+            })
+            .collect()
+    };
+
+    let title = if app.help_handler.query().is_empty() {
+        "Help (type to filter, Esc to close)".to_string()
+    } else {
+        format!("Help: {}", app.help_handler.query())
+    };
+    let block = Block::bordered().title(title);
+    let scroll = app
+        .help_handler
+        .scroll(lines.len(), block.inner(rect).height);
+    let paragraph = Paragraph::new(lines).block(block).scroll((scroll, 0));
+    frame.render_widget(Clear, rect);
     frame.render_widget(paragraph, rect);
 }
+
+fn stats_rect(area: Rect) -> Rect {
+    area.inner(Margin::new(2, 1))
+}
+
+fn render_stats(app: &App, frame: &mut Frame) {
+    let Some(stats) = &app.stats else {
+        return;
+    };
+    let rect = stats_rect(frame.area());
+    let block = Block::bordered().title(format!(
+        "Stats: {} (Tab for period, Esc to close)",
+        stats.period
+    ));
+    let inner = block.inner(rect);
+    frame.render_widget(Clear, rect);
+    frame.render_widget(block, rect);
+
+    let layout = ratatui::layout::Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Fill(1),
+            Constraint::Length(10),
+        ])
+        .split(inner);
+
+    let summary = vec![
+        Line::from(format!(
+            "{} plays, {} skips, {} listened",
+            stats.plays,
+            stats.skips,
+            format_ms_to_duration_string(stats.listened)
+        )),
+        Line::from(format!(
+            "Library: {} songs, {}",
+            stats.library_songs,
+            library_counts(&stats.formats)
+        )),
+        Line::from(format!("Genres: {}", library_counts(&stats.genres))),
+    ];
+    frame.render_widget(Paragraph::new(summary), layout[0]);
+
+    let columns = ratatui::layout::Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Fill(1); 3])
+        .split(layout[1]);
+    for (title, counts, column) in [
+        ("Top artists", &stats.top_artists, columns[0]),
+        ("Top albums", &stats.top_albums, columns[1]),
+        ("Top tracks", &stats.top_tracks, columns[2]),
+    ] {
+        frame.render_widget(top_list(title, counts), column);
+    }
+
+    let minutes: Vec<u64> = stats
+        .daily
+        .iter()
+        .map(|day| day.listened / 60_000)
+        .collect();
+    let charts = ratatui::layout::Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Fill(2), Constraint::Fill(1)])
+        .split(layout[2]);
+
+    // As many recent days as fit, a bar and a gap of one cell each
+    let bar_width = 3;
+    let days = (charts[0].width.saturating_sub(2) / (bar_width + 1)) as usize;
+    let bars: Vec<Bar> = stats
+        .daily
+        .iter()
+        .zip(&minutes)
+        .skip(stats.daily.len().saturating_sub(days))
+        .map(|(day, minutes)| {
+            Bar::default()
+                .value(*minutes)
+                .label(Line::from(day.date.format("%d").to_string()))
+        })
+        .collect();
+    let bar_chart = BarChart::default()
+        .block(Block::bordered().title("Minutes per day"))
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Config::get().theme.accent));
+    frame.render_widget(bar_chart, charts[0]);
+
+    let sparkline_width = charts[1].width.saturating_sub(2) as usize;
+    let title = match (stats.daily.first(), stats.daily.last()) {
+        (Some(first), Some(last)) => format!(
+            "{} to {}",
+            first.date.format("%d %b %Y"),
+            last.date.format("%d %b %Y")
+        ),
+        _ => String::new(),
+    };
+    let sparkline = Sparkline::default()
+        .block(Block::bordered().title(title))
+        .data(&minutes[minutes.len().saturating_sub(sparkline_width)..])
+        .style(Style::default().fg(Config::get().theme.playing));
+    frame.render_widget(sparkline, charts[1]);
+}
+
+fn top_list<'a>(title: &'a str, counts: &'a [PlayCount]) -> List<'a> {
+    let items: Vec<ListItem> = if counts.is_empty() {
+        vec![ListItem::new(Line::from("No plays").dark_gray())]
+    } else {
+        counts
+            .iter()
+            .map(|count| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{:>4} ", count.plays)).fg(Config::get().theme.accent),
+                    Span::raw(count.name.as_str()),
+                ]))
+            })
+            .collect()
+    };
+    List::new(items).block(Block::bordered().title(title))
+}
+
+/// The largest counts that fit on one line are shown in full, the rest as a total.
+fn library_counts(counts: &[LibraryCount]) -> String {
+    const SHOWN: usize = 8;
+    let mut text = counts
+        .iter()
+        .take(SHOWN)
+        .map(|count| format!("{} {}", count.name, count.songs))
+        .collect::<Vec<String>>()
+        .join(", ");
+    if counts.len() > SHOWN {
+        let rest: usize = counts[SHOWN..].iter().map(|count| count.songs).sum();
+        text.push_str(&format!(
+            ", {} more in {} others",
+            rest,
+            counts.len() - SHOWN
+        ));
+    }
+    text
+}
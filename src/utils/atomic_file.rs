@@ -0,0 +1,33 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Replaces the file through a temporary one next to it, so a crash leaves either the old or the
+/// new content and never half of it.
+pub fn write(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temporary = with_suffix(path, ".tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+/// Moves a file that can't be read to `<name>.bak`, so the next save doesn't overwrite what may
+/// still be recovered by hand. Returns where it went.
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+    let backup = with_suffix(path, ".bak");
+    fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
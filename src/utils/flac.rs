@@ -0,0 +1,555 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const BLOCK_PADDING: u8 = 1;
+const BLOCK_VORBIS_COMMENT: u8 = 4;
+const BLOCK_PICTURE: u8 = 6;
+const PICTURE_FRONT_COVER: u32 = 3;
+/// Left after the metadata when the file has to be rewritten, so later edits fit in place
+const NEW_PADDING: usize = 4096;
+
+pub struct FlacPicture {
+    pub picture_type: u32,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Vorbis comments as key and value, keys can repeat
+type Comments = Vec<(String, String)>;
+
+/// The parts of a FLAC file's metadata blocks we care about.
+#[derive(Default)]
+pub struct FlacMetadata {
+    pub comments: Comments,
+    pub pictures: Vec<FlacPicture>,
+}
+
+impl FlacMetadata {
+    /// First value of a Vorbis comment, keys are case-insensitive.
+    pub fn comment(&self, key: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .find(|(comment_key, _)| comment_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn front_cover(&self) -> Option<&FlacPicture> {
+        self.pictures
+            .iter()
+            .find(|picture| picture.picture_type == PICTURE_FRONT_COVER)
+            .or(self.pictures.first())
+    }
+}
+
+pub fn read_metadata(path: &Path) -> io::Result<FlacMetadata> {
+    let mut reader = BufReader::new(File::open(path)?);
+    skip_id3v2(&mut reader)?;
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a FLAC file",
+        ));
+    }
+
+    let mut metadata = FlacMetadata::default();
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        match block_type {
+            BLOCK_VORBIS_COMMENT => {
+                let block = read_block(&mut reader, length)?;
+                metadata.comments = parse_vorbis_comments(&block).unwrap_or_default();
+            }
+            BLOCK_PICTURE => {
+                let block = read_block(&mut reader, length)?;
+                if let Some(picture) = parse_picture(&block) {
+                    metadata.pictures.push(picture);
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(length as i64))?;
+            }
+        }
+
+        if is_last {
+            break;
+        }
+    }
+    Ok(metadata)
+}
+
+/// Sets Vorbis comments, replacing every value of the same keys, `None` removes them. Other
+/// comments are kept byte for byte. The metadata is rewritten in place when the padding has
+/// room, otherwise the whole file is copied. Comments that can't be read are an error rather
+/// than dropped.
+pub fn write_comments(path: &Path, comments: &[(&str, Option<String>)]) -> io::Result<()> {
+    let data = fs::read(path)?;
+    let truncated = || io::Error::new(io::ErrorKind::InvalidData, "Truncated FLAC metadata");
+    let start = id3v2_length(&data);
+    if data.get(start..start + 4) != Some(b"fLaC".as_slice()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a FLAC file",
+        ));
+    }
+
+    let mut blocks: Vec<(u8, &[u8])> = Vec::new();
+    let mut position = start + 4;
+    loop {
+        let header = data.get(position..position + 4).ok_or_else(truncated)?;
+        let is_last = header[0] & 0x80 != 0;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let block = data
+            .get(position + 4..position + 4 + length)
+            .ok_or_else(truncated)?;
+        blocks.push((header[0] & 0x7f, block));
+        position += 4 + length;
+        if is_last {
+            break;
+        }
+    }
+    let audio_start = position;
+
+    let (vendor, mut existing) = match blocks
+        .iter()
+        .find(|(block_type, _)| *block_type == BLOCK_VORBIS_COMMENT)
+    {
+        Some((_, block)) => parse_vorbis_block(block).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Unreadable Vorbis comments")
+        })?,
+        None => (b"music-ratatui".as_slice(), Vec::new()),
+    };
+    // Entries without a key can't be replaced, they stay as they are
+    existing.retain(
+        |comment| match comment.iter().position(|byte| *byte == b'=') {
+            Some(end) => !comments
+                .iter()
+                .any(|(key, _)| comment[..end].eq_ignore_ascii_case(key.as_bytes())),
+            None => true,
+        },
+    );
+    let added: Vec<String> = comments
+        .iter()
+        .filter_map(|(key, value)| value.as_ref().map(|value| format!("{key}={value}")))
+        .collect();
+    existing.extend(added.iter().map(|comment| comment.as_bytes()));
+    let comment_block = vorbis_comment_block(vendor, &existing);
+
+    let mut new_blocks: Vec<(u8, &[u8])> = blocks
+        .into_iter()
+        .filter(|(block_type, _)| ![BLOCK_PADDING, BLOCK_VORBIS_COMMENT].contains(block_type))
+        .collect();
+    // STREAMINFO has to stay first
+    new_blocks.insert(
+        new_blocks.len().min(1),
+        (BLOCK_VORBIS_COMMENT, &comment_block),
+    );
+    let used: usize = new_blocks.iter().map(|(_, block)| 4 + block.len()).sum();
+    let available = audio_start - start - 4;
+    // Left over space needs room for at least a padding block header
+    let in_place = available == used || available >= used + 4;
+    let padding = if available == used {
+        None
+    } else if in_place {
+        Some(vec![0u8; available - used - 4])
+    } else {
+        Some(vec![0u8; NEW_PADDING])
+    };
+    if let Some(padding) = &padding {
+        new_blocks.push((BLOCK_PADDING, padding));
+    }
+
+    let mut metadata = Vec::new();
+    for (index, (block_type, block)) in new_blocks.iter().enumerate() {
+        let last_flag = if index + 1 == new_blocks.len() {
+            0x80
+        } else {
+            0
+        };
+        metadata.push(block_type | last_flag);
+        metadata.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+        metadata.extend_from_slice(block);
+    }
+
+    if in_place {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.seek(SeekFrom::Start((start + 4) as u64))?;
+        return file.write_all(&metadata);
+    }
+    let temporary = path.with_extension("flac.tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(&data[..start + 4])?;
+    file.write_all(&metadata)?;
+    file.write_all(&data[audio_start..])?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+fn id3v2_length(data: &[u8]) -> usize {
+    match data.get(0..10) {
+        Some(header) if &header[0..3] == b"ID3" => {
+            10 + header[6..10]
+                .iter()
+                .fold(0usize, |size, byte| (size << 7) | (*byte & 0x7f) as usize)
+        }
+        _ => 0,
+    }
+}
+
+fn skip_id3v2(reader: &mut BufReader<File>) -> io::Result<()> {
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header)?;
+    if &header[0..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |size, byte| (size << 7) | (*byte & 0x7f) as u64);
+        reader.seek(SeekFrom::Start(10 + size))?;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+    Ok(())
+}
+
+fn read_block(reader: &mut BufReader<File>, length: usize) -> io::Result<Vec<u8>> {
+    let mut block = vec![0u8; length];
+    reader.read_exact(&mut block)?;
+    Ok(block)
+}
+
+// Vorbis comments use little-endian lengths
+/// The vendor string and the comments as stored, normally `KEY=value` in UTF-8.
+fn parse_vorbis_block(block: &[u8]) -> Option<(&[u8], Vec<&[u8]>)> {
+    let mut cursor = ByteCursor::new(block);
+    let vendor_length = cursor.u32_le()? as usize;
+    let vendor = cursor.bytes(vendor_length)?;
+    let count = cursor.u32_le()?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let length = cursor.u32_le()? as usize;
+        comments.push(cursor.bytes(length)?);
+    }
+    Some((vendor, comments))
+}
+
+fn parse_vorbis_comments(block: &[u8]) -> Option<Comments> {
+    let (_, comments) = parse_vorbis_block(block)?;
+    Some(
+        comments
+            .into_iter()
+            .filter_map(|comment| {
+                let comment = String::from_utf8_lossy(comment);
+                let (key, value) = comment.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect(),
+    )
+}
+
+fn vorbis_comment_block(vendor: &[u8], comments: &[&[u8]]) -> Vec<u8> {
+    let mut block = Vec::new();
+    block.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    block.extend_from_slice(vendor);
+    block.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        block.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        block.extend_from_slice(comment);
+    }
+    block
+}
+
+// Picture blocks use big-endian lengths
+fn parse_picture(block: &[u8]) -> Option<FlacPicture> {
+    let mut cursor = ByteCursor::new(block);
+    let picture_type = cursor.u32_be()?;
+    let mime_length = cursor.u32_be()? as usize;
+    let mime_type = String::from_utf8_lossy(cursor.bytes(mime_length)?).to_string();
+    let description_length = cursor.u32_be()? as usize;
+    cursor.bytes(description_length)?;
+    // Width, height, color depth and number of colors
+    cursor.bytes(16)?;
+    let data_length = cursor.u32_be()? as usize;
+    let data = cursor.bytes(data_length)?.to_vec();
+    Some(FlacPicture {
+        picture_type,
+        mime_type,
+        data,
+    })
+}
+
+struct ByteCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        ByteCursor { data, position: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(length)?;
+        let bytes = self.data.get(self.position..end)?;
+        self.position = end;
+        Some(bytes)
+    }
+
+    fn u32_le(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32_be(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use super::*;
+
+    const BLOCK_STREAMINFO: u8 = 0;
+    const AUDIO: &[u8] = b"\xff\xf8audio frames";
+
+    fn comment_block(comments: &[&[u8]]) -> Vec<u8> {
+        vorbis_comment_block(b"reference libFLAC", comments)
+    }
+
+    /// A FLAC file with the blocks in order, after an ID3v2 tag with `id3` bytes of body if any.
+    fn flac_file(id3: Option<usize>, blocks: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Some(size) = id3 {
+            data.extend_from_slice(b"ID3\x04\x00\x00");
+            data.extend(
+                (0..4)
+                    .rev()
+                    .map(|shift| ((size >> (shift * 7)) & 0x7f) as u8),
+            );
+            data.extend(vec![0u8; size]);
+        }
+        data.extend_from_slice(b"fLaC");
+        for (index, (block_type, block)) in blocks.iter().enumerate() {
+            let last_flag = if index + 1 == blocks.len() { 0x80 } else { 0 };
+            data.push(block_type | last_flag);
+            data.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+            data.extend_from_slice(block);
+        }
+        data.extend_from_slice(AUDIO);
+        data
+    }
+
+    /// The blocks of a file as type and content.
+    fn blocks(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut blocks = Vec::new();
+        let mut position = id3v2_length(data) + 4;
+        loop {
+            let header = &data[position..position + 4];
+            let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+            blocks.push((
+                header[0] & 0x7f,
+                data[position + 4..position + 4 + length].to_vec(),
+            ));
+            position += 4 + length;
+            if header[0] & 0x80 != 0 {
+                return blocks;
+            }
+        }
+    }
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, data: &[u8]) -> Self {
+            let path = env::temp_dir().join(format!("music-ratatui-{}-{name}.flac", process::id()));
+            fs::write(&path, data).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn rate(path: &Path) -> io::Result<()> {
+        write_comments(
+            path,
+            &[
+                ("FMPS_RATING", Some("0.8".to_string())),
+                ("FMPS_PLAYCOUNT", None),
+            ],
+        )
+    }
+
+    fn existing_comments() -> Vec<u8> {
+        comment_block(&[
+            b"TITLE=Song",
+            b"ARTIST=Artist",
+            b"fmps_playcount=3",
+            b"no key here",
+            b"ALBUM=Album",
+        ])
+    }
+
+    /// The comments after `rate`, the old ones in their order and the new one last.
+    fn assert_rated(data: &[u8]) {
+        let blocks = blocks(data);
+        assert_eq!(blocks[0].0, BLOCK_STREAMINFO);
+        let comments = blocks
+            .iter()
+            .find(|(block_type, _)| *block_type == BLOCK_VORBIS_COMMENT)
+            .unwrap();
+        let (vendor, comments) = parse_vorbis_block(&comments.1).unwrap();
+        assert_eq!(vendor, b"reference libFLAC");
+        assert_eq!(
+            comments,
+            [
+                b"TITLE=Song".as_slice(),
+                b"ARTIST=Artist",
+                b"no key here",
+                b"ALBUM=Album",
+                b"FMPS_RATING=0.8",
+            ]
+        );
+        assert!(data.ends_with(AUDIO));
+    }
+
+    #[test]
+    fn writes_in_place_into_padding() {
+        let original = flac_file(
+            None,
+            &[
+                (BLOCK_STREAMINFO, vec![1; 34]),
+                (BLOCK_VORBIS_COMMENT, existing_comments()),
+                (BLOCK_PICTURE, vec![2; 20]),
+                (BLOCK_PADDING, vec![0; 100]),
+            ],
+        );
+        let file = TempFile::new("in-place", &original);
+        rate(&file.0).unwrap();
+
+        let data = fs::read(&file.0).unwrap();
+        assert_eq!(data.len(), original.len());
+        assert_rated(&data);
+        let blocks = blocks(&data);
+        assert_eq!(blocks[2], (BLOCK_PICTURE, vec![2; 20]));
+        // FMPS_RATING=0.8 is a byte shorter than fmps_playcount=3, the padding takes it
+        assert_eq!(blocks[3], (BLOCK_PADDING, vec![0; 101]));
+
+        let metadata = read_metadata(&file.0).unwrap();
+        assert_eq!(metadata.comment("fmps_rating"), Some("0.8"));
+        assert_eq!(metadata.comment("title"), Some("Song"));
+    }
+
+    #[test]
+    fn rewrites_with_new_padding_when_it_does_not_fit() {
+        let comments = comment_block(&[
+            b"TITLE=Song",
+            b"ARTIST=Artist",
+            b"no key here",
+            b"ALBUM=Album",
+        ]);
+        let original = flac_file(
+            None,
+            &[
+                (BLOCK_STREAMINFO, vec![1; 34]),
+                (BLOCK_VORBIS_COMMENT, comments),
+                (BLOCK_PADDING, vec![0; 2]),
+            ],
+        );
+        let file = TempFile::new("rewrite", &original);
+        rate(&file.0).unwrap();
+
+        let data = fs::read(&file.0).unwrap();
+        assert_rated(&data);
+        let blocks = blocks(&data);
+        assert_eq!(
+            blocks.last().unwrap(),
+            &(BLOCK_PADDING, vec![0; NEW_PADDING])
+        );
+        assert!(!file.0.with_extension("flac.tmp").exists());
+    }
+
+    #[test]
+    fn keeps_an_id3v2_prefix() {
+        let original = flac_file(
+            Some(30),
+            &[
+                (BLOCK_STREAMINFO, vec![1; 34]),
+                (BLOCK_VORBIS_COMMENT, existing_comments()),
+                (BLOCK_PADDING, vec![0; 64]),
+            ],
+        );
+        let file = TempFile::new("id3", &original);
+        rate(&file.0).unwrap();
+
+        let data = fs::read(&file.0).unwrap();
+        assert_eq!(data[..40], original[..40]);
+        assert_eq!(data.len(), original.len());
+        assert_rated(&data);
+        assert_eq!(
+            read_metadata(&file.0).unwrap().comment("ALBUM"),
+            Some("Album")
+        );
+    }
+
+    #[test]
+    fn rewrites_a_comment_block_without_padding() {
+        let original = flac_file(
+            None,
+            &[
+                (BLOCK_STREAMINFO, vec![1; 34]),
+                (BLOCK_VORBIS_COMMENT, existing_comments()),
+            ],
+        );
+        let file = TempFile::new("no-padding", &original);
+        rate(&file.0).unwrap();
+
+        let data = fs::read(&file.0).unwrap();
+        assert_rated(&data);
+        assert_eq!(blocks(&data).len(), 3);
+        assert_eq!(blocks(&data)[2], (BLOCK_PADDING, vec![0; NEW_PADDING]));
+
+        // Now there is room, the next write stays in place
+        write_comments(&file.0, &[("FMPS_RATING", Some("1.0".to_string()))]).unwrap();
+        assert_eq!(fs::read(&file.0).unwrap().len(), data.len());
+        assert_eq!(
+            read_metadata(&file.0).unwrap().comment("FMPS_RATING"),
+            Some("1.0")
+        );
+    }
+
+    #[test]
+    fn refuses_unreadable_comments() {
+        let mut comments = existing_comments();
+        // The last comment claims more bytes than the block has
+        let length = comments.len();
+        comments[length - 15..length - 11].copy_from_slice(&100u32.to_le_bytes());
+        let original = flac_file(
+            None,
+            &[
+                (BLOCK_STREAMINFO, vec![1; 34]),
+                (BLOCK_VORBIS_COMMENT, comments),
+                (BLOCK_PADDING, vec![0; 64]),
+            ],
+        );
+        let file = TempFile::new("unreadable", &original);
+
+        let err = rate(&file.0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(&file.0).unwrap(), original);
+    }
+}
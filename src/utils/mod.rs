@@ -1,2 +1,4 @@
+pub mod atomic_file;
+pub mod flac;
 pub mod input;
 pub mod selecthandler;
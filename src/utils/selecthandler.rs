@@ -1,7 +1,14 @@
 use ratatui::widgets::{ListItem, ListState};
 
 use crate::{
-    display_handlers::folder_handler::{Folder, Node},
+    display_handlers::{
+        browse_handler::BrowseNode,
+        folder_handler::{Folder, Node},
+        history_handler::HistoryNode,
+        playlist_handler::PlaylistNode,
+    },
+    logging::LogLine,
+    lyrics::LyricLine,
     song::Song,
 };
 
@@ -16,6 +23,11 @@ where
 pub enum Selectable {
     Song(Song),
     Node(Node),
+    PlaylistNode(PlaylistNode),
+    BrowseNode(BrowseNode),
+    LyricLine(LyricLine),
+    LogLine(LogLine),
+    HistoryNode(HistoryNode),
 }
 
 impl SelectHandlerItem for Selectable {
@@ -23,10 +35,25 @@ impl SelectHandlerItem for Selectable {
         match self {
             Selectable::Song(song) => song.list_item(),
             Selectable::Node(node) => node.list_item(),
+            Selectable::PlaylistNode(node) => node.list_item(),
+            Selectable::BrowseNode(node) => node.list_item(),
+            Selectable::LyricLine(line) => line.list_item(),
+            Selectable::LogLine(line) => line.list_item(),
+            Selectable::HistoryNode(node) => node.list_item(),
         }
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum Movement {
+    Up(usize),
+    Down(usize),
+    First,
+    Last,
+    /// Index into the list, clamped to the last item
+    To(usize),
+}
+
 pub struct SelectHandler<T: SelectHandlerItem> {
     items: Vec<T>,
     state: ListState,
@@ -57,10 +84,30 @@ impl<T: SelectHandlerItem> SelectHandler<T> {
         self.state.select_previous();
     }
 
+    pub fn move_selection(&mut self, movement: Movement) {
+        if self.items.is_empty() {
+            return;
+        }
+        let last = self.items.len() - 1;
+        let current = self.state.selected().unwrap_or(0);
+        let index = match movement {
+            Movement::Up(steps) => current.saturating_sub(steps),
+            Movement::Down(steps) => current.saturating_add(steps).min(last),
+            Movement::First => 0,
+            Movement::Last => last,
+            Movement::To(index) => index.min(last),
+        };
+        self.state.select(Some(index));
+    }
+
     pub fn select(&self) -> Option<&T> {
         self.state.selected().map(|i| &self.items[i])
     }
 
+    pub fn selected_index(&self) -> Option<usize> {
+        self.state.selected()
+    }
+
     // Getters:
     pub fn items(&self) -> &Vec<T> {
         return &self.items;